@@ -7,9 +7,22 @@ use aes_gcm::{
     Aes256Gcm, Nonce,
 };
 use argon2::password_hash::SaltString;
-use argon2::{Argon2, PasswordHasher};
+use argon2::{Algorithm, Argon2, PasswordHasher, Version};
+use base64::{engine::general_purpose, Engine as _};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
 use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
+use zeroize::Zeroizing;
+
+/// Length in bytes of a per-note content key
+pub const NOTE_KEY_LEN: usize = 32;
 
 #[derive(Error, Debug)]
 pub enum EncryptionError {
@@ -21,20 +34,337 @@ pub enum EncryptionError {
     KeyDerivationFailed(String),
     #[error("Invalid key length")]
     InvalidKeyLength,
+    #[error("Weak password: {0}")]
+    WeakPassword(String),
+    #[error("Malformed ciphertext: {0}")]
+    MalformedCiphertext(String),
+    #[error("Invalid unlock token: {0}")]
+    InvalidUnlockToken(String),
+    #[error("Authentication failed: password does not match the vault")]
+    AuthenticationFailed,
+}
+
+/// Requirements a password must meet before a key is derived from it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    /// Minimum number of characters
+    pub min_length: usize,
+    /// Require at least one uppercase letter
+    pub require_uppercase: bool,
+    /// Require at least one lowercase letter
+    pub require_lowercase: bool,
+    /// Require at least one digit
+    pub require_digit: bool,
+    /// Require at least one non-alphanumeric character
+    pub require_symbol: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            require_uppercase: false,
+            require_lowercase: false,
+            require_digit: false,
+            require_symbol: false,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Check a password against this policy, listing every unmet requirement
+    pub fn check(&self, password: &str) -> Result<(), EncryptionError> {
+        let mut problems = Vec::new();
+
+        let length = password.chars().count();
+        if length < self.min_length {
+            problems.push(format!(
+                "must be at least {} characters (got {})",
+                self.min_length, length
+            ));
+        }
+        if self.require_uppercase && !password.chars().any(|c| c.is_uppercase()) {
+            problems.push("must contain an uppercase letter".to_string());
+        }
+        if self.require_lowercase && !password.chars().any(|c| c.is_lowercase()) {
+            problems.push("must contain a lowercase letter".to_string());
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            problems.push("must contain a digit".to_string());
+        }
+        if self.require_symbol && !password.chars().any(|c| !c.is_alphanumeric()) {
+            problems.push("must contain a symbol".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(EncryptionError::WeakPassword(problems.join("; ")))
+        }
+    }
+}
+
+/// Argon2id cost parameters used to derive a vault key
+///
+/// Store these alongside the vault's salt: the same password only yields the
+/// same key under the same parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Argon2Params {
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    /// Number of passes over memory
+    pub iterations: u32,
+    /// Degree of parallelism (lanes)
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Params {
+    /// The parameters `new_from_password` has always used
+    fn default() -> Self {
+        Self {
+            memory_kib: argon2::Params::DEFAULT_M_COST,
+            iterations: argon2::Params::DEFAULT_T_COST,
+            parallelism: argon2::Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl Argon2Params {
+    fn hasher(&self) -> Result<Argon2<'static>, EncryptionError> {
+        let params = argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|e| EncryptionError::KeyDerivationFailed(e.to_string()))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+/// Memory ceiling for `calibrate_argon2`, in KiB (1 GiB)
+const CALIBRATION_MAX_MEMORY_KIB: u32 = 1 << 20;
+
+/// Iteration ceiling for `calibrate_argon2`
+const CALIBRATION_MAX_ITERATIONS: u32 = 16;
+
+/// Maximum number of trial derivations `calibrate_argon2` performs
+const CALIBRATION_MAX_STEPS: usize = 24;
+
+/// Pick the strongest Argon2 parameters deriving within `target` on this device
+///
+/// Starts from the defaults and doubles the memory cost, then raises the
+/// iteration count once memory reaches 1 GiB, for as long as a trial derivation
+/// stays under `target`. The defaults are a floor: they are returned unchanged
+/// when even they exceed the target. The search stops after a bounded number of
+/// trials, so total calibration time stays a small multiple of `target`.
+pub fn calibrate_argon2(target: Duration) -> Argon2Params {
+    let mut chosen = Argon2Params::default();
+    let mut candidate = chosen;
+
+    for _ in 0..CALIBRATION_MAX_STEPS {
+        match estimate_derivation_time(&candidate) {
+            Ok(elapsed) if elapsed <= target => chosen = candidate,
+            _ => break,
+        }
+
+        if candidate.memory_kib < CALIBRATION_MAX_MEMORY_KIB {
+            candidate.memory_kib = (candidate.memory_kib * 2).min(CALIBRATION_MAX_MEMORY_KIB);
+        } else if candidate.iterations < CALIBRATION_MAX_ITERATIONS {
+            candidate.iterations += 1;
+        } else {
+            break;
+        }
+    }
+
+    chosen
+}
+
+/// Measure how long one key derivation with `params` takes on this device
+///
+/// Runs a single derivation, so expect some jitter between calls; use it to
+/// warn about slow unlocks or to suggest re-running `calibrate_argon2`.
+/// Fails with `KeyDerivationFailed` when the parameters are invalid.
+pub fn estimate_derivation_time(params: &Argon2Params) -> Result<Duration, EncryptionError> {
+    let hasher = params.hasher()?;
+    let mut output = Zeroizing::new([0u8; 32]);
+    let start = Instant::now();
+    hasher
+        .hash_password_into(
+            b"calibration password",
+            b"calibration salt",
+            output.as_mut(),
+        )
+        .map_err(|e| EncryptionError::KeyDerivationFailed(e.to_string()))?;
+    Ok(start.elapsed())
+}
+
+/// HKDF info string deriving the unlock token subkey from the vault key
+const UNLOCK_TOKEN_INFO: &[u8] = b"null-space unlock token v1";
+
+/// HKDF info string deriving the password auth tag subkey from the vault key
+const AUTH_TAG_INFO: &[u8] = b"null-space auth tag v1";
+
+/// A short-lived proof that the vault was unlocked, for caching by the host
+///
+/// Issued by `EncryptionManager::issue_unlock_token` and checked with
+/// `verify_unlock_token`. It reveals nothing about the key, but anyone holding
+/// it can pass verification until it expires, so keep it in memory or secure
+/// storage only.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnlockToken {
+    /// Issue time in Unix seconds
+    pub issued_at: u64,
+    /// HMAC-SHA256 over `issued_at`
+    pub mac: Vec<u8>,
+}
+
+/// Magic bytes and format version opening every encrypted blob
+const BLOB_MAGIC: [u8; 4] = *b"NSE\x01";
+
+/// Length of the blob header: magic/version, then a big-endian CRC32 of the rest
+const BLOB_HEADER_LEN: usize = 8;
+
+/// AES-GCM nonce length in bytes
+const NONCE_LEN: usize = 12;
+
+/// AES-GCM authentication tag length in bytes
+const TAG_LEN: usize = 16;
+
+/// Leading bytes of every counter-mode nonce; the remaining 8 are the counter
+const COUNTER_NONCE_PREFIX: [u8; 4] = *b"NSc1";
+
+/// Persistable position of a manager's counter-mode nonce sequence
+///
+/// Hosts must save this after encrypting in counter mode and restore it in the
+/// next session, otherwise nonces would repeat under the same key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonceState {
+    /// Identifier of the key this state belongs to (see `EncryptionManager::key_id`)
+    pub key_id: String,
+    /// Counter value the next nonce will use
+    pub next_counter: u64,
+}
+
+/// Counter-mode nonce bookkeeping
+struct CounterNonces {
+    next: AtomicU64,
+    /// Set when the key was used before but its state wasn't restored
+    blocked: bool,
+}
+
+/// Bounded LRU of decrypted plaintexts, keyed by SHA-256 of the ciphertext
+///
+/// Most recently used entries sit at the back. Evicted and cleared plaintexts
+/// are wiped as their `Zeroizing` buffers drop.
+struct DecryptCache {
+    entries: VecDeque<([u8; 32], Zeroizing<Vec<u8>>)>,
+    max_entries: usize,
+    max_bytes: usize,
+    bytes: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl DecryptCache {
+    fn get(&mut self, key: &[u8; 32]) -> Option<Vec<u8>> {
+        let Some(pos) = self.entries.iter().position(|(k, _)| k == key) else {
+            self.misses += 1;
+            return None;
+        };
+        self.hits += 1;
+        let entry = self.entries.remove(pos)?;
+        let plaintext = entry.1.to_vec();
+        self.entries.push_back(entry);
+        Some(plaintext)
+    }
+
+    fn insert(&mut self, key: [u8; 32], plaintext: &[u8]) {
+        // Plaintexts larger than the whole budget are never cached
+        if plaintext.len() > self.max_bytes || self.max_entries == 0 {
+            return;
+        }
+        while self.entries.len() >= self.max_entries
+            || self.bytes + plaintext.len() > self.max_bytes
+        {
+            match self.entries.pop_front() {
+                Some((_, evicted)) => self.bytes -= evicted.len(),
+                None => break,
+            }
+        }
+        self.bytes += plaintext.len();
+        self.entries
+            .push_back((key, Zeroizing::new(plaintext.to_vec())));
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+}
+
+/// Hit and size counters for the decryption cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DecryptCacheStats {
+    /// Decryptions answered from the cache
+    pub hits: u64,
+    /// Decryptions that had to run AES-GCM
+    pub misses: u64,
+    /// Plaintexts currently cached
+    pub entries: usize,
+    /// Total size of the cached plaintexts
+    pub bytes: usize,
+}
+
+/// Convert a salt from a legacy format into one `new_from_password` accepts
+///
+/// Early vaults stored salts as padded or URL-safe base64, or as arbitrary raw
+/// text. Normalization is deterministic, so the same legacy salt always yields
+/// the same key:
+/// 1. A salt that is already valid is returned unchanged.
+/// 2. Otherwise `=` padding is stripped and `_` mapped to `/`.
+/// 3. If that is still invalid, the raw bytes are B64-encoded.
+pub fn normalize_salt(raw: &str) -> Result<String, EncryptionError> {
+    if SaltString::from_b64(raw).is_ok() {
+        return Ok(raw.to_string());
+    }
+
+    let cleaned = raw.trim_end_matches('=').replace('_', "/");
+    if SaltString::from_b64(&cleaned).is_ok() {
+        return Ok(cleaned);
+    }
+
+    SaltString::encode_b64(raw.as_bytes())
+        .and_then(|salt| SaltString::from_b64(salt.as_str()))
+        .map(|salt| salt.to_string())
+        .map_err(|e| EncryptionError::KeyDerivationFailed(e.to_string()))
 }
 
 /// Manages encryption and decryption operations
 pub struct EncryptionManager {
     cipher: Aes256Gcm,
+    key: Zeroizing<[u8; 32]>,
+    salt: String,
+    counter_nonces: Option<CounterNonces>,
+    decrypt_cache: Option<Mutex<DecryptCache>>,
 }
 
 impl EncryptionManager {
     /// Create a new encryption manager with a derived key from password
     pub fn new_from_password(password: &str, salt: &str) -> Result<Self, EncryptionError> {
+        Self::new_from_password_with_params(password, salt, &Argon2Params::default())
+    }
+
+    /// Create a new encryption manager, deriving the key with explicit Argon2 costs
+    ///
+    /// Use the parameters recorded for the vault, e.g. from `calibrate_argon2`.
+    pub fn new_from_password_with_params(
+        password: &str,
+        salt: &str,
+        params: &Argon2Params,
+    ) -> Result<Self, EncryptionError> {
+        let salt_string = salt.to_string();
         let salt = SaltString::from_b64(salt)
             .map_err(|e| EncryptionError::KeyDerivationFailed(e.to_string()))?;
 
-        let argon2 = Argon2::default();
+        #[cfg(feature = "metrics")]
+        let timer = crate::metrics::Timer::start(crate::metrics::Operation::KeyDerivation);
+        let argon2 = params.hasher()?;
         let password_hash = argon2
             .hash_password(password.as_bytes(), &salt)
             .map_err(|e| EncryptionError::KeyDerivationFailed(e.to_string()))?;
@@ -44,16 +374,40 @@ impl EncryptionManager {
             .ok_or(EncryptionError::KeyDerivationFailed(
                 "No hash generated".to_string(),
             ))?;
+        #[cfg(feature = "metrics")]
+        drop(timer);
 
         let key_bytes = hash_bytes.as_bytes();
         if key_bytes.len() < 32 {
             return Err(EncryptionError::InvalidKeyLength);
         }
 
-        let cipher = Aes256Gcm::new_from_slice(&key_bytes[..32])
+        let mut key = Zeroizing::new([0u8; 32]);
+        key.copy_from_slice(&key_bytes[..32]);
+
+        let cipher = Aes256Gcm::new_from_slice(key.as_ref())
             .map_err(|e| EncryptionError::KeyDerivationFailed(e.to_string()))?;
 
-        Ok(Self { cipher })
+        Ok(Self {
+            cipher,
+            key,
+            salt: salt_string,
+            counter_nonces: None,
+            decrypt_cache: None,
+        })
+    }
+
+    /// Create a new encryption manager, rejecting passwords that fail the policy
+    ///
+    /// Use this when setting a new password; `new_from_password` stays permissive
+    /// so existing data protected by weaker passwords can still be opened.
+    pub fn new_from_password_checked(
+        password: &str,
+        salt: &str,
+        policy: &PasswordPolicy,
+    ) -> Result<Self, EncryptionError> {
+        policy.check(password)?;
+        Self::new_from_password(password, salt)
     }
 
     /// Generate a new random salt for key derivation
@@ -61,43 +415,470 @@ impl EncryptionManager {
         SaltString::generate(&mut OsRng).to_string()
     }
 
+    /// Generate a new random per-note content key
+    pub fn generate_note_key() -> Zeroizing<[u8; NOTE_KEY_LEN]> {
+        let mut key = Zeroizing::new([0u8; NOTE_KEY_LEN]);
+        OsRng.fill_bytes(key.as_mut());
+        key
+    }
+
+    /// The salt this manager's key was derived with
+    pub fn salt(&self) -> &str {
+        &self.salt
+    }
+
+    /// Stable identifier for this manager's key, safe to store in cleartext
+    ///
+    /// Hosts use it as the marker recording that a key has been used in counter
+    /// mode, and to check a saved `NonceState` belongs to the right key.
+    pub fn key_id(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(b"null-space key id v1");
+        hasher.update(self.key.as_ref());
+        to_hex(&hasher.finalize()[..16])
+    }
+
+    /// Issue an unlock token stamped with the current time
+    ///
+    /// The token is a MAC under a subkey derived from the vault key with HKDF,
+    /// so checking it costs a hash instead of a full Argon2 derivation.
+    pub fn issue_unlock_token(&self) -> UnlockToken {
+        self.unlock_token_at(unix_now())
+    }
+
+    /// Check an unlock token was issued by this key no more than `max_age` ago
+    ///
+    /// The MAC is compared in constant time. Tokens stamped in the future are
+    /// rejected along with expired ones.
+    pub fn verify_unlock_token(
+        &self,
+        token: &UnlockToken,
+        max_age: Duration,
+    ) -> Result<(), EncryptionError> {
+        self.unlock_token_mac(token.issued_at)
+            .verify_slice(&token.mac)
+            .map_err(|_| EncryptionError::InvalidUnlockToken("MAC mismatch".to_string()))?;
+
+        let age = unix_now().checked_sub(token.issued_at).ok_or_else(|| {
+            EncryptionError::InvalidUnlockToken("Issued in the future".to_string())
+        })?;
+        if age > max_age.as_secs() {
+            return Err(EncryptionError::InvalidUnlockToken(format!(
+                "Expired {}s ago",
+                age - max_age.as_secs()
+            )));
+        }
+        Ok(())
+    }
+
+    fn unlock_token_at(&self, issued_at: u64) -> UnlockToken {
+        UnlockToken {
+            issued_at,
+            mac: self
+                .unlock_token_mac(issued_at)
+                .finalize()
+                .into_bytes()
+                .to_vec(),
+        }
+    }
+
+    /// HMAC state over a token timestamp, keyed with the HKDF subkey
+    fn unlock_token_mac(&self, issued_at: u64) -> Hmac<Sha256> {
+        let mut mac = self.subkey_mac(UNLOCK_TOKEN_INFO);
+        mac.update(&issued_at.to_be_bytes());
+        mac
+    }
+
+    /// Password verifier to store with the vault, base64-encoded
+    ///
+    /// Lets `verify_password` tell whether a password is right without
+    /// decrypting any note. The tag is a MAC under an HKDF subkey, so it
+    /// reveals nothing about the key itself.
+    pub fn auth_tag(&self) -> String {
+        let tag = self.subkey_mac(AUTH_TAG_INFO).finalize().into_bytes();
+        general_purpose::STANDARD.encode(tag)
+    }
+
+    /// Check a password against a vault's salt and stored `auth_tag`
+    ///
+    /// Runs one full key derivation; the tags are compared in constant time.
+    /// Fails with `MalformedCiphertext` when the tag isn't valid base64, and
+    /// with `KeyDerivationFailed` when the salt is invalid.
+    pub fn verify_password(
+        password: &str,
+        salt: &str,
+        auth_tag: &str,
+    ) -> Result<bool, EncryptionError> {
+        let expected = general_purpose::STANDARD.decode(auth_tag).map_err(|_| {
+            EncryptionError::MalformedCiphertext("Auth tag is not valid base64".to_string())
+        })?;
+        let manager = Self::new_from_password(password, salt)?;
+        Ok(manager
+            .subkey_mac(AUTH_TAG_INFO)
+            .verify_slice(&expected)
+            .is_ok())
+    }
+
+    /// HMAC state keyed with an HKDF subkey of the vault key
+    fn subkey_mac(&self, info: &[u8]) -> Hmac<Sha256> {
+        let mut subkey = Zeroizing::new([0u8; 32]);
+        Hkdf::<Sha256>::new(None, self.key.as_ref())
+            .expand(info, subkey.as_mut())
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        <Hmac<Sha256> as Mac>::new_from_slice(subkey.as_ref())
+            .expect("HMAC accepts keys of any length")
+    }
+
+    /// Switch to counter-mode nonces, starting a fresh sequence
+    ///
+    /// `key_marker` is the key ID the host stored the last time this key was used
+    /// in counter mode, if any. When it matches this key, encryption is refused
+    /// until `restore_nonce_state` is called, since starting over would reuse nonces.
+    pub fn enable_counter_nonces(&mut self, key_marker: Option<&str>) {
+        let blocked = key_marker == Some(self.key_id().as_str());
+        self.counter_nonces = Some(CounterNonces {
+            next: AtomicU64::new(0),
+            blocked,
+        });
+    }
+
+    /// Export the counter-mode nonce position so the host can persist it
+    ///
+    /// Returns `None` when the manager is using random nonces.
+    pub fn export_nonce_state(&self) -> Option<NonceState> {
+        self.counter_nonces.as_ref().map(|c| NonceState {
+            key_id: self.key_id(),
+            next_counter: c.next.load(Ordering::SeqCst),
+        })
+    }
+
+    /// Resume counter-mode nonces from a previously exported state
+    pub fn restore_nonce_state(&mut self, state: NonceState) -> Result<(), EncryptionError> {
+        if state.key_id != self.key_id() {
+            return Err(EncryptionError::EncryptionFailed(
+                "Nonce state belongs to a different key".to_string(),
+            ));
+        }
+
+        self.counter_nonces = Some(CounterNonces {
+            next: AtomicU64::new(state.next_counter),
+            blocked: false,
+        });
+        Ok(())
+    }
+
     /// Encrypt data
     pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, EncryptionError> {
-        let mut nonce_bytes = [0u8; 12];
-        OsRng.fill_bytes(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
+        match &self.counter_nonces {
+            None => seal(&self.cipher, plaintext),
+            Some(counter) => {
+                if counter.blocked {
+                    return Err(EncryptionError::EncryptionFailed(
+                        "Counter nonce state must be restored before reusing this key".to_string(),
+                    ));
+                }
 
-        let ciphertext = self
-            .cipher
-            .encrypt(nonce, plaintext)
-            .map_err(|e| EncryptionError::EncryptionFailed(e.to_string()))?;
+                let value = counter
+                    .next
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_add(1))
+                    .map_err(|_| {
+                        EncryptionError::EncryptionFailed("Nonce counter exhausted".to_string())
+                    })?;
 
-        // Prepend nonce to ciphertext
-        let mut result = nonce_bytes.to_vec();
-        result.extend_from_slice(&ciphertext);
-        Ok(result)
+                let mut nonce_bytes = [0u8; NONCE_LEN];
+                nonce_bytes[..4].copy_from_slice(&COUNTER_NONCE_PREFIX);
+                nonce_bytes[4..].copy_from_slice(&value.to_be_bytes());
+                seal_with_nonce(&self.cipher, nonce_bytes, plaintext)
+            }
+        }
     }
 
     /// Decrypt data
+    ///
+    /// Served from the decryption cache when one is enabled and holds this ciphertext.
     pub fn decrypt(&self, encrypted_data: &[u8]) -> Result<Vec<u8>, EncryptionError> {
-        if encrypted_data.len() < 12 {
-            return Err(EncryptionError::DecryptionFailed(
-                "Data too short to contain nonce".to_string(),
-            ));
+        let Some(cache) = &self.decrypt_cache else {
+            return open(&self.cipher, encrypted_data);
+        };
+
+        let key: [u8; 32] = Sha256::digest(encrypted_data).into();
+        if let Some(plaintext) = lock_cache(cache).get(&key) {
+            return Ok(plaintext);
+        }
+        let plaintext = open(&self.cipher, encrypted_data)?;
+        lock_cache(cache).insert(key, &plaintext);
+        Ok(plaintext)
+    }
+
+    /// Keep recently decrypted plaintexts in memory to skip repeated AES-GCM work
+    ///
+    /// Useful when the same note is opened, searched and previewed in one
+    /// session. The cache holds at most `max_entries` plaintexts totalling at
+    /// most `max_bytes`, evicting the least recently used first.
+    ///
+    /// This is a tradeoff: cached plaintext stays resident for as long as the
+    /// manager lives instead of only while the caller holds it, so a memory
+    /// dump or swap file could expose more notes. Entries are zeroized on
+    /// eviction, on `clear_decrypt_cache` and when the manager drops. Callers
+    /// should clear the cache when the vault locks or the app is backgrounded.
+    /// Calling this again replaces (and wipes) any existing cache.
+    pub fn enable_decrypt_cache(&mut self, max_entries: usize, max_bytes: usize) {
+        self.decrypt_cache = Some(Mutex::new(DecryptCache {
+            entries: VecDeque::new(),
+            max_entries,
+            max_bytes,
+            bytes: 0,
+            hits: 0,
+            misses: 0,
+        }));
+    }
+
+    /// Stop caching decrypted plaintexts, wiping everything cached so far
+    pub fn disable_decrypt_cache(&mut self) {
+        self.decrypt_cache = None;
+    }
+
+    /// Wipe every cached plaintext, keeping the cache enabled
+    pub fn clear_decrypt_cache(&self) {
+        if let Some(cache) = &self.decrypt_cache {
+            lock_cache(cache).clear();
+        }
+    }
+
+    /// Counters for the decryption cache, or `None` when it is disabled
+    pub fn decrypt_cache_stats(&self) -> Option<DecryptCacheStats> {
+        self.decrypt_cache.as_ref().map(|cache| {
+            let cache = lock_cache(cache);
+            DecryptCacheStats {
+                hits: cache.hits,
+                misses: cache.misses,
+                entries: cache.entries.len(),
+                bytes: cache.bytes,
+            }
+        })
+    }
+
+    /// Decrypt data into a buffer that wipes itself when dropped
+    ///
+    /// Prefer this over `decrypt` when holding decrypted note content, so the
+    /// plaintext doesn't linger in freed heap memory.
+    pub fn decrypt_zeroizing(
+        &self,
+        encrypted_data: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, EncryptionError> {
+        self.decrypt(encrypted_data).map(Zeroizing::new)
+    }
+
+    /// Wrap (encrypt) a per-note content key with the vault master key
+    ///
+    /// The wrapped key can be stored or shared alongside a note encrypted with
+    /// `encrypt_with_key`, without exposing the master key itself.
+    pub fn wrap_key(&self, note_key: &[u8; NOTE_KEY_LEN]) -> Result<Vec<u8>, EncryptionError> {
+        self.encrypt(note_key)
+    }
+
+    /// Unwrap a per-note content key previously wrapped with `wrap_key`
+    pub fn unwrap_key(
+        &self,
+        wrapped_key: &[u8],
+    ) -> Result<Zeroizing<[u8; NOTE_KEY_LEN]>, EncryptionError> {
+        let unwrapped = Zeroizing::new(self.decrypt(wrapped_key)?);
+        if unwrapped.len() != NOTE_KEY_LEN {
+            return Err(EncryptionError::InvalidKeyLength);
+        }
+
+        let mut key = Zeroizing::new([0u8; NOTE_KEY_LEN]);
+        key.copy_from_slice(&unwrapped);
+        Ok(key)
+    }
+
+    /// Encrypt data with a per-note content key
+    pub fn encrypt_with_key(
+        note_key: &[u8; NOTE_KEY_LEN],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let cipher =
+            Aes256Gcm::new_from_slice(note_key).map_err(|_| EncryptionError::InvalidKeyLength)?;
+        seal(&cipher, plaintext)
+    }
+
+    /// Decrypt data with a per-note content key
+    pub fn decrypt_with_key(
+        note_key: &[u8; NOTE_KEY_LEN],
+        encrypted_data: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let cipher =
+            Aes256Gcm::new_from_slice(note_key).map_err(|_| EncryptionError::InvalidKeyLength)?;
+        open(&cipher, encrypted_data)
+    }
+}
+
+/// Encrypt with a random nonce, producing a headed blob (see `seal_with_nonce`)
+fn seal(cipher: &Aes256Gcm, plaintext: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce_bytes);
+    seal_with_nonce(cipher, nonce_bytes, plaintext)
+}
+
+/// Encrypt with the given nonce, producing a headed blob
+///
+/// Layout: `NSE\x01`, a CRC32 of everything after the header, the nonce, then
+/// the ciphertext. The CRC is a cheap check for truncation or corruption; GCM
+/// still provides the actual authentication.
+fn seal_with_nonce(
+    cipher: &Aes256Gcm,
+    nonce_bytes: [u8; NONCE_LEN],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    #[cfg(feature = "metrics")]
+    let _timer = crate::metrics::Timer::start(crate::metrics::Operation::Encrypt);
+    let nonce = Nonce::from_slice(&nonce_bytes);
+
+    let ciphertext = cipher
+        .encrypt(nonce, plaintext)
+        .map_err(|e| EncryptionError::EncryptionFailed(e.to_string()))?;
+
+    let mut result = Vec::with_capacity(BLOB_HEADER_LEN + nonce_bytes.len() + ciphertext.len());
+    result.extend_from_slice(&BLOB_MAGIC);
+    result.extend_from_slice(&[0; 4]);
+    result.extend_from_slice(&nonce_bytes);
+    result.extend_from_slice(&ciphertext);
+    let crc = crc32fast::hash(&result[BLOB_HEADER_LEN..]);
+    result[4..BLOB_HEADER_LEN].copy_from_slice(&crc.to_be_bytes());
+    Ok(result)
+}
+
+/// Cipher an encrypted blob was sealed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CipherAlgorithm {
+    /// AES-256 in GCM mode with a 96-bit nonce
+    Aes256Gcm,
+}
+
+/// Metadata of an encrypted blob, read without the key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlobInfo {
+    /// Whether the blob predates the header (plain nonce || ciphertext)
+    pub legacy: bool,
+    /// Header format version, `None` for legacy blobs
+    pub format_version: Option<u8>,
+    /// Cipher the format version implies; every version so far uses AES-256-GCM
+    pub algorithm: CipherAlgorithm,
+    /// Whether the header checksum matches; legacy blobs have none and report `true`
+    pub checksum_valid: bool,
+    /// Whether the nonce comes from the counter-mode sequence rather than the RNG
+    pub counter_nonce: bool,
+    /// Length of the ciphertext including the authentication tag
+    pub ciphertext_len: usize,
+}
+
+/// Describe an encrypted blob from its header, without decrypting it
+///
+/// A checksum mismatch is reported in `BlobInfo::checksum_valid` rather than as
+/// an error so damaged blobs can still be diagnosed. Fails with
+/// `MalformedCiphertext` when the blob is too short to hold a header (if it has
+/// one), a nonce and a tag.
+pub fn inspect_blob(data: &[u8]) -> Result<BlobInfo, EncryptionError> {
+    let (payload, format_version, checksum_valid) = match strip_header(data) {
+        Ok(payload) if payload.len() == data.len() => (payload, None, true),
+        Ok(payload) => (payload, Some(BLOB_MAGIC[3]), true),
+        Err(_) if data.len() >= BLOB_HEADER_LEN => {
+            (&data[BLOB_HEADER_LEN..], Some(BLOB_MAGIC[3]), false)
         }
+        Err(e) => return Err(e),
+    };
+    if payload.len() < NONCE_LEN + TAG_LEN {
+        return Err(EncryptionError::MalformedCiphertext(format!(
+            "{} bytes is too short for a nonce and authentication tag",
+            payload.len()
+        )));
+    }
 
-        let (nonce_bytes, ciphertext) = encrypted_data.split_at(12);
-        let nonce = Nonce::from_slice(nonce_bytes);
+    Ok(BlobInfo {
+        legacy: format_version.is_none(),
+        format_version,
+        algorithm: CipherAlgorithm::Aes256Gcm,
+        checksum_valid,
+        counter_nonce: payload.starts_with(&COUNTER_NONCE_PREFIX),
+        ciphertext_len: payload.len() - NONCE_LEN,
+    })
+}
 
-        self.cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| EncryptionError::DecryptionFailed(e.to_string()))
+/// Check and strip the blob header, returning the nonce and ciphertext
+///
+/// Blobs written before the header existed are plain nonce || ciphertext and
+/// are returned unchanged. A random legacy nonce starts with the magic bytes
+/// only with probability 2^-32.
+fn strip_header(encrypted_data: &[u8]) -> Result<&[u8], EncryptionError> {
+    if !encrypted_data.starts_with(&BLOB_MAGIC) {
+        return Ok(encrypted_data);
+    }
+    if encrypted_data.len() < BLOB_HEADER_LEN {
+        return Err(EncryptionError::MalformedCiphertext(
+            "Truncated header".to_string(),
+        ));
     }
+
+    let (header, payload) = encrypted_data.split_at(BLOB_HEADER_LEN);
+    let expected = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    if crc32fast::hash(payload) != expected {
+        return Err(EncryptionError::MalformedCiphertext(
+            "Checksum mismatch".to_string(),
+        ));
+    }
+    Ok(payload)
+}
+
+/// Decrypt data produced by `seal`
+fn open(cipher: &Aes256Gcm, encrypted_data: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+    let encrypted_data = strip_header(encrypted_data)?;
+
+    #[cfg(feature = "metrics")]
+    let _timer = crate::metrics::Timer::start(crate::metrics::Operation::Decrypt);
+    // Even an empty plaintext produces a nonce and a full tag
+    if encrypted_data.len() < NONCE_LEN + TAG_LEN {
+        return Err(EncryptionError::MalformedCiphertext(format!(
+            "{} bytes is too short for a nonce and authentication tag",
+            encrypted_data.len()
+        )));
+    }
+
+    let (nonce_bytes, ciphertext) = encrypted_data.split_at(NONCE_LEN);
+    let nonce = Nonce::from_slice(nonce_bytes);
+
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|e| EncryptionError::DecryptionFailed(e.to_string()))
+}
+
+/// Current time in Unix seconds
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Lock the decryption cache, recovering from a panic in another thread
+///
+/// The cache holds no invariants a panic could break beyond its byte count,
+/// which is only an eviction heuristic.
+fn lock_cache(cache: &Mutex<DecryptCache>) -> std::sync::MutexGuard<'_, DecryptCache> {
+    cache
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Lowercase hex encoding
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
 impl Drop for EncryptionManager {
     fn drop(&mut self) {
-        // Zeroize sensitive data on drop
+        // Zeroize sensitive data on drop (the key buffer wipes itself)
+        self.clear_decrypt_cache();
     }
 }
 
@@ -105,6 +886,71 @@ impl Drop for EncryptionManager {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_calibrate_argon2_meets_target() {
+        // Base the target on this machine's speed so debug builds and slow CI
+        // runners still exercise at least one step up from the defaults
+        let baseline = estimate_derivation_time(&Argon2Params::default()).unwrap();
+        let target = baseline * 3;
+
+        let params = calibrate_argon2(target);
+        assert!(params.memory_kib >= Argon2Params::default().memory_kib);
+
+        let elapsed = estimate_derivation_time(&params).unwrap();
+        assert!(elapsed <= target * 2, "{:?} exceeds {:?}", elapsed, target);
+        assert!(
+            elapsed >= target / 8,
+            "{:?} far below {:?}",
+            elapsed,
+            target
+        );
+    }
+
+    #[test]
+    fn test_estimate_derivation_time() {
+        let params = Argon2Params::default();
+        let first = estimate_derivation_time(&params).unwrap();
+        let second = estimate_derivation_time(&params).unwrap();
+        assert!(first > Duration::ZERO);
+        // Loose bound: shared CI machines can be noisy
+        assert!(
+            first < second * 5 && second < first * 5,
+            "{:?} vs {:?}",
+            first,
+            second
+        );
+
+        let invalid = Argon2Params {
+            memory_kib: 1,
+            ..params
+        };
+        assert!(matches!(
+            estimate_derivation_time(&invalid),
+            Err(EncryptionError::KeyDerivationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_default_params_match_legacy_derivation() {
+        let salt = EncryptionManager::generate_salt();
+        let legacy = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let explicit = EncryptionManager::new_from_password_with_params(
+            "password",
+            &salt,
+            &Argon2Params::default(),
+        )
+        .unwrap();
+        assert_eq!(legacy.key_id(), explicit.key_id());
+
+        let stronger = Argon2Params {
+            iterations: 3,
+            ..Argon2Params::default()
+        };
+        let other =
+            EncryptionManager::new_from_password_with_params("password", &salt, &stronger).unwrap();
+        assert_ne!(legacy.key_id(), other.key_id());
+    }
+
     #[test]
     fn test_encrypt_decrypt() {
         let password = "test_password_123";
@@ -136,4 +982,354 @@ mod tests {
         let decrypted2 = manager.decrypt(&encrypted2).unwrap();
         assert_eq!(decrypted1, decrypted2);
     }
+
+    #[test]
+    fn test_wrap_unwrap_key() {
+        let salt = EncryptionManager::generate_salt();
+        let manager = EncryptionManager::new_from_password("test_password_123", &salt).unwrap();
+
+        let note_key = EncryptionManager::generate_note_key();
+        let wrapped = manager.wrap_key(&note_key).unwrap();
+        assert_ne!(wrapped.as_slice(), note_key.as_slice());
+
+        let unwrapped = manager.unwrap_key(&wrapped).unwrap();
+        assert_eq!(*unwrapped, *note_key);
+
+        // A different master key must not be able to unwrap it
+        let other = EncryptionManager::new_from_password("other_password", &salt).unwrap();
+        assert!(other.unwrap_key(&wrapped).is_err());
+    }
+
+    #[test]
+    fn test_per_note_key_round_trip() {
+        let salt = EncryptionManager::generate_salt();
+        let manager = EncryptionManager::new_from_password("test_password_123", &salt).unwrap();
+
+        let note_key = EncryptionManager::generate_note_key();
+        let plaintext = b"Shared note content";
+        let encrypted = EncryptionManager::encrypt_with_key(&note_key, plaintext).unwrap();
+
+        // The master key alone cannot read a note encrypted under its own key
+        assert!(manager.decrypt(&encrypted).is_err());
+
+        let wrapped = manager.wrap_key(&note_key).unwrap();
+        let unwrapped = manager.unwrap_key(&wrapped).unwrap();
+        let decrypted = EncryptionManager::decrypt_with_key(&unwrapped, &encrypted).unwrap();
+        assert_eq!(plaintext, decrypted.as_slice());
+    }
+
+    #[test]
+    fn test_password_policy() {
+        let salt = EncryptionManager::generate_salt();
+        let policy = PasswordPolicy::default();
+
+        assert!(matches!(
+            EncryptionManager::new_from_password_checked("", &salt, &policy),
+            Err(EncryptionError::WeakPassword(_))
+        ));
+        assert!(matches!(
+            EncryptionManager::new_from_password_checked("short", &salt, &policy),
+            Err(EncryptionError::WeakPassword(_))
+        ));
+        assert!(
+            EncryptionManager::new_from_password_checked("long enough", &salt, &policy).is_ok()
+        );
+
+        let strict = PasswordPolicy {
+            require_uppercase: true,
+            require_digit: true,
+            ..PasswordPolicy::default()
+        };
+        match strict.check("alllowercase") {
+            Err(EncryptionError::WeakPassword(msg)) => {
+                assert!(msg.contains("uppercase"));
+                assert!(msg.contains("digit"));
+            }
+            other => panic!("expected weak password, got {:?}", other),
+        }
+        assert!(strict.check("Compliant123").is_ok());
+
+        // The unchecked constructor stays permissive
+        assert!(EncryptionManager::new_from_password("", &salt).is_ok());
+    }
+
+    #[test]
+    fn test_unlock_tokens() {
+        let salt = EncryptionManager::generate_salt();
+        let manager = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let max_age = Duration::from_secs(300);
+
+        let token = manager.issue_unlock_token();
+        assert!(manager.verify_unlock_token(&token, max_age).is_ok());
+
+        let expired = manager.unlock_token_at(unix_now() - 301);
+        assert!(matches!(
+            manager.verify_unlock_token(&expired, max_age),
+            Err(EncryptionError::InvalidUnlockToken(_))
+        ));
+        let future = manager.unlock_token_at(unix_now() + 60);
+        assert!(manager.verify_unlock_token(&future, max_age).is_err());
+
+        // Extending a token's lifetime invalidates its MAC
+        let forged = UnlockToken {
+            issued_at: token.issued_at + 3600,
+            ..token.clone()
+        };
+        assert!(manager.verify_unlock_token(&forged, max_age).is_err());
+
+        let other = EncryptionManager::new_from_password("other", &salt).unwrap();
+        assert!(other.verify_unlock_token(&token, max_age).is_err());
+    }
+
+    #[test]
+    fn test_verify_password() {
+        let salt = EncryptionManager::generate_salt();
+        let tag = EncryptionManager::new_from_password("right", &salt)
+            .unwrap()
+            .auth_tag();
+
+        assert!(EncryptionManager::verify_password("right", &salt, &tag).unwrap());
+        assert!(!EncryptionManager::verify_password("wrong", &salt, &tag).unwrap());
+        assert!(matches!(
+            EncryptionManager::verify_password("right", &salt, "not base64!"),
+            Err(EncryptionError::MalformedCiphertext(_))
+        ));
+    }
+
+    #[test]
+    fn test_inspect_blob() {
+        let salt = EncryptionManager::generate_salt();
+        let mut manager = EncryptionManager::new_from_password("password", &salt).unwrap();
+
+        let random = manager.encrypt(b"hello").unwrap();
+        let info = inspect_blob(&random).unwrap();
+        assert_eq!(
+            info,
+            BlobInfo {
+                legacy: false,
+                format_version: Some(1),
+                algorithm: CipherAlgorithm::Aes256Gcm,
+                checksum_valid: true,
+                counter_nonce: false,
+                ciphertext_len: 5 + TAG_LEN,
+            }
+        );
+
+        manager.enable_counter_nonces(None);
+        let counter = manager.encrypt(b"hello").unwrap();
+        assert!(inspect_blob(&counter).unwrap().counter_nonce);
+
+        let legacy = &random[BLOB_HEADER_LEN..];
+        let info = inspect_blob(legacy).unwrap();
+        assert!(info.legacy);
+        assert_eq!(info.format_version, None);
+        assert!(info.checksum_valid);
+        assert_eq!(info.ciphertext_len, 5 + TAG_LEN);
+
+        let mut damaged = random.clone();
+        *damaged.last_mut().unwrap() ^= 1;
+        assert!(!inspect_blob(&damaged).unwrap().checksum_valid);
+
+        assert!(matches!(
+            inspect_blob(&random[..BLOB_HEADER_LEN + 4]),
+            Err(EncryptionError::MalformedCiphertext(_))
+        ));
+    }
+
+    #[test]
+    fn test_nonce_state_persistence() {
+        let salt = EncryptionManager::generate_salt();
+        let mut manager = EncryptionManager::new_from_password("test_password_123", &salt).unwrap();
+        assert!(manager.export_nonce_state().is_none());
+
+        manager.enable_counter_nonces(None);
+        let first = manager.encrypt(b"one").unwrap();
+        let second = manager.encrypt(b"two").unwrap();
+        let nonce = |blob: &[u8]| blob[BLOB_HEADER_LEN..BLOB_HEADER_LEN + NONCE_LEN].to_vec();
+        assert_ne!(nonce(&first), nonce(&second));
+
+        let state = manager.export_nonce_state().unwrap();
+        assert_eq!(state.next_counter, 2);
+        assert_eq!(state.key_id, manager.key_id());
+        drop(manager);
+
+        // A new session that knows the key was used must restore before encrypting
+        let mut resumed = EncryptionManager::new_from_password("test_password_123", &salt).unwrap();
+        resumed.enable_counter_nonces(Some(&state.key_id));
+        assert!(resumed.encrypt(b"three").is_err());
+
+        resumed.restore_nonce_state(state).unwrap();
+        let third = resumed.encrypt(b"three").unwrap();
+        assert_ne!(nonce(&third), nonce(&first));
+        assert_ne!(nonce(&third), nonce(&second));
+        assert_eq!(resumed.export_nonce_state().unwrap().next_counter, 3);
+
+        // Earlier ciphertexts still decrypt
+        assert_eq!(resumed.decrypt(&first).unwrap(), b"one");
+
+        // State from another key is rejected
+        let mut other = EncryptionManager::new_from_password("other_password", &salt).unwrap();
+        let foreign = resumed.export_nonce_state().unwrap();
+        assert!(other.restore_nonce_state(foreign).is_err());
+    }
+
+    #[test]
+    fn test_blob_checksum() {
+        let salt = EncryptionManager::generate_salt();
+        let manager = EncryptionManager::new_from_password("test_password_123", &salt).unwrap();
+
+        let encrypted = manager.encrypt(b"checked").unwrap();
+        assert!(encrypted.starts_with(&BLOB_MAGIC));
+
+        // A flipped ciphertext byte fails the CRC, before GCM is attempted
+        let mut corrupted = encrypted.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0x01;
+        assert!(matches!(
+            manager.decrypt(&corrupted),
+            Err(EncryptionError::MalformedCiphertext(_))
+        ));
+
+        // Truncation is caught the same way
+        assert!(matches!(
+            manager.decrypt(&encrypted[..encrypted.len() - 4]),
+            Err(EncryptionError::MalformedCiphertext(_))
+        ));
+
+        // A wrong key passes the CRC and fails authentication instead
+        let other = EncryptionManager::new_from_password("other_password", &salt).unwrap();
+        assert!(matches!(
+            other.decrypt(&encrypted),
+            Err(EncryptionError::DecryptionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_rejects_short_blobs() {
+        let salt = EncryptionManager::generate_salt();
+        let manager = EncryptionManager::new_from_password("test_password_123", &salt).unwrap();
+
+        for len in [0, 12, 15, 27] {
+            assert!(
+                matches!(
+                    manager.decrypt(&vec![0u8; len]),
+                    Err(EncryptionError::MalformedCiphertext(_))
+                ),
+                "{} byte blob",
+                len
+            );
+        }
+
+        // A headed blob whose payload is too short, with a matching CRC
+        let mut headed = BLOB_MAGIC.to_vec();
+        let payload = [0u8; 15];
+        headed.extend_from_slice(&crc32fast::hash(&payload).to_be_bytes());
+        headed.extend_from_slice(&payload);
+        assert!(matches!(
+            manager.decrypt(&headed),
+            Err(EncryptionError::MalformedCiphertext(_))
+        ));
+
+        // The smallest valid blob: empty plaintext
+        let empty = manager.encrypt(b"").unwrap();
+        assert_eq!(empty.len(), BLOB_HEADER_LEN + NONCE_LEN + TAG_LEN);
+        assert!(manager.decrypt(&empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_decrypt_legacy_headerless_blob() {
+        let salt = EncryptionManager::generate_salt();
+        let manager = EncryptionManager::new_from_password("test_password_123", &salt).unwrap();
+
+        let nonce_bytes = [7u8; NONCE_LEN];
+        let ciphertext = manager
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce_bytes), b"legacy".as_ref())
+            .unwrap();
+        let mut legacy = nonce_bytes.to_vec();
+        legacy.extend_from_slice(&ciphertext);
+
+        assert_eq!(manager.decrypt(&legacy).unwrap(), b"legacy");
+    }
+
+    #[test]
+    fn test_normalize_salt() {
+        let salt = EncryptionManager::generate_salt();
+        assert_eq!(normalize_salt(&salt).unwrap(), salt);
+
+        // Padded base64 loses its padding
+        assert_eq!(
+            normalize_salt("c29tZXNhbHR2YWx1ZQ==").unwrap(),
+            "c29tZXNhbHR2YWx1ZQ"
+        );
+
+        // Arbitrary raw text is encoded deterministically
+        let raw = normalize_salt("my old salt!").unwrap();
+        assert_eq!(raw, normalize_salt("my old salt!").unwrap());
+        assert!(EncryptionManager::new_from_password("password", &raw).is_ok());
+
+        assert!(normalize_salt("").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_zeroizing() {
+        let salt = EncryptionManager::generate_salt();
+        let manager = EncryptionManager::new_from_password("test_password_123", &salt).unwrap();
+
+        let encrypted = manager.encrypt(b"Sensitive content").unwrap();
+        let decrypted: Zeroizing<Vec<u8>> = manager.decrypt_zeroizing(&encrypted).unwrap();
+        assert_eq!(decrypted.as_slice(), b"Sensitive content");
+
+        let mut tampered = encrypted.clone();
+        tampered[20] ^= 1;
+        assert!(manager.decrypt_zeroizing(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_cache_hits_and_clear() {
+        let salt = EncryptionManager::generate_salt();
+        let mut manager = EncryptionManager::new_from_password("pw", &salt).unwrap();
+        assert!(manager.decrypt_cache_stats().is_none());
+        manager.enable_decrypt_cache(2, 1024);
+
+        let encrypted = manager.encrypt(b"large note body").unwrap();
+        assert_eq!(manager.decrypt(&encrypted).unwrap(), b"large note body");
+        assert_eq!(manager.decrypt(&encrypted).unwrap(), b"large note body");
+        let stats = manager.decrypt_cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+
+        manager.clear_decrypt_cache();
+        assert_eq!(manager.decrypt_cache_stats().unwrap().bytes, 0);
+        manager.decrypt(&encrypted).unwrap();
+        let stats = manager.decrypt_cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses), (1, 2));
+
+        // Tampered ciphertext hashes differently, so it misses and still fails
+        let mut tampered = encrypted.clone();
+        tampered[20] ^= 1;
+        assert!(manager.decrypt(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_cache_evicts_least_recent() {
+        let salt = EncryptionManager::generate_salt();
+        let mut manager = EncryptionManager::new_from_password("pw", &salt).unwrap();
+        manager.enable_decrypt_cache(2, 1024);
+
+        let blobs: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|p| manager.encrypt(p.as_bytes()).unwrap())
+            .collect();
+        manager.decrypt(&blobs[0]).unwrap();
+        manager.decrypt(&blobs[1]).unwrap();
+        manager.decrypt(&blobs[0]).unwrap();
+        manager.decrypt(&blobs[2]).unwrap();
+        assert_eq!(manager.decrypt_cache_stats().unwrap().entries, 2);
+
+        // "b" was least recently used and got evicted; "a" is still cached
+        manager.decrypt(&blobs[0]).unwrap();
+        manager.decrypt(&blobs[1]).unwrap();
+        let stats = manager.decrypt_cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses), (2, 4));
+    }
 }
@@ -5,16 +5,178 @@
 #![allow(clippy::not_unsafe_ptr_arg_deref)]
 
 use base64::{engine::general_purpose, Engine as _};
+use serde::Deserialize;
+use zeroize::Zeroizing;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int, c_void};
 use std::path::{Path, PathBuf};
 use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::crypto::EncryptionManager;
-use crate::models::{ConflictResolution, Note};
+use crate::crypto::{EncryptionManager, PasswordPolicy};
+use crate::models::{ConflictResolution, Note, NOTE_SCHEMA_VERSION};
 use crate::search::SearchEngine;
 use crate::storage::FileStorage;
-use crate::vault::VaultManager;
+use crate::vault::{VaultError, VaultManager};
+
+/// Fields every note JSON object must carry
+const REQUIRED_NOTE_FIELDS: [&str; 7] = [
+    "id",
+    "title",
+    "content",
+    "tags",
+    "created_at",
+    "updated_at",
+    "version",
+];
+
+/// Every serialized `Note` field with its JSON type, as reported by
+/// null_space_note_schema. Keep in step with `models::Note`.
+const NOTE_FIELD_TYPES: [(&str, &str); 12] = [
+    ("id", "uuid"),
+    ("title", "string"),
+    ("content", "string"),
+    ("tags", "string[]"),
+    ("created_at", "datetime"),
+    ("updated_at", "datetime"),
+    ("version", "integer"),
+    ("pinned", "boolean"),
+    ("schema_version", "integer"),
+    ("derived_from", "uuid"),
+    ("search_keywords", "string[]"),
+    ("attachments", "attachment[]"),
+];
+
+/// Search limit used when the caller passes zero or a negative value
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+/// Upper bound on results a single FFI search may return
+const MAX_SEARCH_LIMIT: usize = 1000;
+
+thread_local! {
+    /// Message describing the most recent failure on this thread
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Record an error message for retrieval via null_space_last_error
+pub(crate) fn set_last_error(message: impl Into<String>) {
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message.into()));
+}
+
+/// Clear the error recorded for this thread
+fn clear_last_error() {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+}
+
+/// Convert a caller-supplied search limit into a bounded `usize`
+fn clamp_search_limit(limit: c_int) -> usize {
+    if limit <= 0 {
+        DEFAULT_SEARCH_LIMIT
+    } else {
+        (limit as usize).min(MAX_SEARCH_LIMIT)
+    }
+}
+
+/// Largest JSON document any FFI function accepts, in bytes
+const MAX_JSON_INPUT_BYTES: usize = 64 * 1024 * 1024;
+
+/// Deepest array/object nesting any FFI function accepts
+///
+/// Well below serde_json's own recursion limit; notes and vaults need only a
+/// few levels.
+const MAX_JSON_DEPTH: usize = 32;
+
+/// Parse host-supplied JSON, rejecting oversized or deeply nested input
+///
+/// The size and depth are checked in one linear scan before serde_json sees
+/// the input, so hostile payloads fail fast with a specific message instead
+/// of using unbounded memory or stack.
+fn parse_json_input<T: serde::de::DeserializeOwned>(json_str: &str) -> Result<T, String> {
+    if json_str.len() > MAX_JSON_INPUT_BYTES {
+        return Err(format!(
+            "JSON input is {} bytes, over the {} byte limit",
+            json_str.len(),
+            MAX_JSON_INPUT_BYTES
+        ));
+    }
+
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for byte in json_str.bytes() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > MAX_JSON_DEPTH {
+                    return Err(format!(
+                        "JSON nesting exceeds the {} level limit",
+                        MAX_JSON_DEPTH
+                    ));
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    serde_json::from_str(json_str).map_err(|e| format!("Malformed JSON: {}", e))
+}
+
+/// Parse and validate a single note JSON object
+///
+/// Distinguishes malformed JSON, missing fields and a malformed UUID so the
+/// caller gets an actionable message instead of a bare failure.
+fn parse_note(value: serde_json::Value) -> Result<Note, String> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| "Note JSON must be an object".to_string())?;
+
+    for field in REQUIRED_NOTE_FIELDS {
+        if !object.contains_key(field) {
+            return Err(format!("Missing required field '{}'", field));
+        }
+    }
+
+    match object["id"].as_str() {
+        Some(id) => {
+            uuid::Uuid::parse_str(id).map_err(|e| format!("Invalid UUID in 'id': {}", e))?;
+        }
+        None => return Err("Field 'id' must be a UUID string".to_string()),
+    }
+
+    serde_json::from_value(value).map_err(|e| format!("Invalid note JSON: {}", e))
+}
+
+/// Parse and validate note JSON text
+fn parse_note_json(json_str: &str) -> Result<Note, String> {
+    parse_note(parse_json_input(json_str)?)
+}
+
+/// Parse and validate a JSON array of notes
+fn parse_notes_json(json_str: &str) -> Result<Vec<Note>, String> {
+    let value: serde_json::Value = parse_json_input(json_str)?;
+
+    match value {
+        serde_json::Value::Array(items) => items
+            .into_iter()
+            .enumerate()
+            .map(|(i, item)| parse_note(item).map_err(|e| format!("Note {}: {}", i, e)))
+            .collect(),
+        _ => Err("Notes JSON must be an array".to_string()),
+    }
+}
 
 /// Initialize the library (currently a no-op, but reserved for future use)
 #[no_mangle]
@@ -34,6 +196,47 @@ pub extern "C" fn null_space_free(ptr: *mut c_void) {
     }
 }
 
+/// Get the message describing the last error on the calling thread
+///
+/// # Returns
+/// The error message, or null if no error has been recorded.
+/// The returned string must be freed with null_space_free_string.
+#[no_mangle]
+pub extern "C" fn null_space_last_error() -> *mut c_char {
+    LAST_ERROR.with(|e| match e.borrow().as_ref() {
+        Some(message) => match CString::new(message.as_str()) {
+            Ok(c_str) => c_str.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    })
+}
+
+/// Clear the last error recorded on the calling thread
+#[no_mangle]
+pub extern "C" fn null_space_clear_last_error() {
+    clear_last_error();
+}
+
+/// Get the recorded operation timings as JSON
+///
+/// # Returns
+/// A JSON `MetricsSnapshot`, or null on error.
+/// The returned string must be freed with null_space_free_string.
+#[cfg(feature = "metrics")]
+#[no_mangle]
+pub extern "C" fn null_space_metrics_json() -> *mut c_char {
+    let json = match serde_json::to_string(&crate::metrics::snapshot()) {
+        Ok(j) => j,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    match CString::new(json) {
+        Ok(c_str) => c_str.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
 /// Generate a random salt for key derivation
 /// Returns a C string that must be freed with null_space_free_string
 #[no_mangle]
@@ -55,6 +258,7 @@ pub extern "C" fn null_space_generate_salt() -> *mut c_char {
 ///
 /// # Returns
 /// A base64-encoded string containing the encrypted data, or null on error.
+/// Passwords that fail the default `PasswordPolicy` are rejected.
 /// The returned string must be freed with null_space_free_string.
 #[no_mangle]
 pub extern "C" fn null_space_encrypt(
@@ -73,7 +277,10 @@ pub extern "C" fn null_space_encrypt(
         match CStr::from_ptr(data).to_str() {
             Ok(s) => s,
             Err(e) => {
-                eprintln!("[FFI ERROR] null_space_encrypt: Failed to convert data to UTF-8: {}", e);
+                eprintln!(
+                    "[FFI ERROR] null_space_encrypt: Failed to convert data to UTF-8: {}",
+                    e
+                );
                 return ptr::null_mut();
             }
         }
@@ -83,7 +290,10 @@ pub extern "C" fn null_space_encrypt(
         match CStr::from_ptr(password).to_str() {
             Ok(s) => s,
             Err(e) => {
-                eprintln!("[FFI ERROR] null_space_encrypt: Failed to convert password to UTF-8: {}", e);
+                eprintln!(
+                    "[FFI ERROR] null_space_encrypt: Failed to convert password to UTF-8: {}",
+                    e
+                );
                 return ptr::null_mut();
             }
         }
@@ -93,17 +303,27 @@ pub extern "C" fn null_space_encrypt(
         match CStr::from_ptr(salt).to_str() {
             Ok(s) => s,
             Err(e) => {
-                eprintln!("[FFI ERROR] null_space_encrypt: Failed to convert salt to UTF-8: {}", e);
+                eprintln!(
+                    "[FFI ERROR] null_space_encrypt: Failed to convert salt to UTF-8: {}",
+                    e
+                );
                 return ptr::null_mut();
             }
         }
     };
 
-    // Create encryption manager
-    let manager = match EncryptionManager::new_from_password(password_str, salt_str) {
+    // Create encryption manager, enforcing the password policy for new data
+    let manager = match EncryptionManager::new_from_password_checked(
+        password_str,
+        salt_str,
+        &PasswordPolicy::default(),
+    ) {
         Ok(m) => m,
         Err(e) => {
-            eprintln!("[FFI ERROR] null_space_encrypt: Failed to create encryption manager: {}", e);
+            eprintln!(
+                "[FFI ERROR] null_space_encrypt: Failed to create encryption manager: {}",
+                e
+            );
             return ptr::null_mut();
         }
     };
@@ -124,7 +344,10 @@ pub extern "C" fn null_space_encrypt(
     match CString::new(encoded) {
         Ok(c_str) => c_str.into_raw(),
         Err(e) => {
-            eprintln!("[FFI ERROR] null_space_encrypt: Failed to create C string: {}", e);
+            eprintln!(
+                "[FFI ERROR] null_space_encrypt: Failed to create C string: {}",
+                e
+            );
             ptr::null_mut()
         }
     }
@@ -185,14 +408,14 @@ pub extern "C" fn null_space_decrypt(
         Err(_) => return ptr::null_mut(),
     };
 
-    // Decrypt the data
-    let decrypted = match manager.decrypt(&encrypted_bytes) {
+    // Decrypt the data; the plaintext buffer is wiped once copied out
+    let decrypted = match manager.decrypt_zeroizing(&encrypted_bytes) {
         Ok(d) => d,
         Err(_) => return ptr::null_mut(),
     };
 
     // Convert to string
-    let decrypted_str = match String::from_utf8(decrypted) {
+    let decrypted_str = match std::str::from_utf8(&decrypted) {
         Ok(s) => s,
         Err(_) => return ptr::null_mut(),
     };
@@ -204,6 +427,299 @@ pub extern "C" fn null_space_decrypt(
     }
 }
 
+/// Derive a key once and keep the ready cipher behind a handle
+///
+/// Use with null_space_encrypt_with_key / null_space_decrypt_with_key to avoid
+/// repeating Argon2 and cipher setup for every note in bulk operations. Like
+/// null_space_decrypt, no password policy is applied, so handles can open data
+/// protected by older, weaker passwords.
+///
+/// # Returns
+/// A handle that must be freed with null_space_encryption_free, or null on
+/// error (see null_space_last_error).
+#[no_mangle]
+pub extern "C" fn null_space_encryption_new(
+    password: *const c_char,
+    salt: *const c_char,
+) -> *mut c_void {
+    clear_last_error();
+
+    if password.is_null() || salt.is_null() {
+        set_last_error("Null password or salt");
+        return ptr::null_mut();
+    }
+
+    let (password_str, salt_str) = unsafe {
+        match (
+            CStr::from_ptr(password).to_str(),
+            CStr::from_ptr(salt).to_str(),
+        ) {
+            (Ok(p), Ok(s)) => (p, s),
+            _ => {
+                set_last_error("Password or salt is not valid UTF-8");
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    match EncryptionManager::new_from_password(password_str, salt_str) {
+        Ok(manager) => Box::into_raw(Box::new(manager)) as *mut c_void,
+        Err(e) => {
+            set_last_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Encrypt data with a handle from null_space_encryption_new
+///
+/// # Returns
+/// A base64-encoded string in the same format as null_space_encrypt, or null
+/// on error. The returned string must be freed with null_space_free_string.
+#[no_mangle]
+pub extern "C" fn null_space_encrypt_with_key(
+    handle: *mut c_void,
+    data: *const c_char,
+) -> *mut c_char {
+    clear_last_error();
+
+    if handle.is_null() || data.is_null() {
+        set_last_error("Null handle or data");
+        return ptr::null_mut();
+    }
+
+    let manager = unsafe { &*(handle as *const EncryptionManager) };
+    let data_str = unsafe {
+        match CStr::from_ptr(data).to_str() {
+            Ok(s) => s,
+            Err(e) => {
+                set_last_error(format!("Data is not valid UTF-8: {}", e));
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let encrypted = match manager.encrypt(data_str.as_bytes()) {
+        Ok(e) => e,
+        Err(e) => {
+            set_last_error(e.to_string());
+            return ptr::null_mut();
+        }
+    };
+
+    match CString::new(general_purpose::STANDARD.encode(encrypted)) {
+        Ok(c_str) => c_str.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Decrypt data with a handle from null_space_encryption_new
+///
+/// # Returns
+/// The decrypted plaintext as a C string, or null on error.
+/// The returned string must be freed with null_space_free_string.
+#[no_mangle]
+pub extern "C" fn null_space_decrypt_with_key(
+    handle: *mut c_void,
+    encrypted: *const c_char,
+) -> *mut c_char {
+    clear_last_error();
+
+    if handle.is_null() || encrypted.is_null() {
+        set_last_error("Null handle or data");
+        return ptr::null_mut();
+    }
+
+    let manager = unsafe { &*(handle as *const EncryptionManager) };
+    let encrypted_bytes = unsafe {
+        match CStr::from_ptr(encrypted)
+            .to_str()
+            .ok()
+            .and_then(|s| general_purpose::STANDARD.decode(s).ok())
+        {
+            Some(b) => b,
+            None => {
+                set_last_error("Encrypted data is not valid base64");
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let decrypted = match manager.decrypt_zeroizing(&encrypted_bytes) {
+        Ok(d) => d,
+        Err(e) => {
+            set_last_error(e.to_string());
+            return ptr::null_mut();
+        }
+    };
+
+    match std::str::from_utf8(&decrypted)
+        .ok()
+        .and_then(|s| CString::new(s).ok())
+    {
+        Some(c_str) => c_str.into_raw(),
+        None => {
+            set_last_error("Decrypted data is not a valid string");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free an encryption handle, wiping its key material
+///
+/// # Safety
+/// The handle must not be used after this call.
+#[no_mangle]
+pub extern "C" fn null_space_encryption_free(handle: *mut c_void) {
+    if !handle.is_null() {
+        unsafe {
+            let _ = Box::from_raw(handle as *mut EncryptionManager);
+        }
+    }
+}
+
+/// Password verifier for the key behind a handle from null_space_encryption_new
+///
+/// Store it with the vault so null_space_verify_passwords can check passwords
+/// later without decrypting anything.
+///
+/// # Returns
+/// The base64-encoded auth tag, or null on error.
+/// The returned string must be freed with null_space_free_string.
+#[no_mangle]
+pub extern "C" fn null_space_encryption_auth_tag(handle: *mut c_void) -> *mut c_char {
+    clear_last_error();
+
+    if handle.is_null() {
+        set_last_error("Null handle");
+        return ptr::null_mut();
+    }
+
+    let manager = unsafe { &*(handle as *const EncryptionManager) };
+    match CString::new(manager.auth_tag()) {
+        Ok(c_str) => c_str.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// One vault entry accepted by null_space_verify_passwords
+#[derive(Deserialize)]
+struct PasswordCheck {
+    id: String,
+    salt: String,
+    auth_tag: String,
+}
+
+/// Check one password against several vaults in a single call
+///
+/// # Arguments
+/// * `vaults_json` - JSON array of `{ "id": ..., "salt": ..., "auth_tag": ... }`
+///   objects, with auth tags from null_space_encryption_auth_tag
+/// * `password` - The password to check (null-terminated C string)
+///
+/// Each vault costs one key derivation. Vaults with a malformed salt or auth
+/// tag are reported as not unlocked.
+///
+/// # Returns
+/// A JSON object mapping each vault ID to whether the password unlocks it, or
+/// null on error (see null_space_last_error).
+/// The returned string must be freed with null_space_free_string.
+#[no_mangle]
+pub extern "C" fn null_space_verify_passwords(
+    vaults_json: *const c_char,
+    password: *const c_char,
+) -> *mut c_char {
+    clear_last_error();
+
+    if vaults_json.is_null() || password.is_null() {
+        set_last_error("Null vaults JSON or password");
+        return ptr::null_mut();
+    }
+
+    let (vaults_str, password_str) = unsafe {
+        match (
+            CStr::from_ptr(vaults_json).to_str(),
+            CStr::from_ptr(password).to_str(),
+        ) {
+            (Ok(v), Ok(p)) => (v, p),
+            _ => {
+                set_last_error("Vaults JSON or password is not valid UTF-8");
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let vaults: Vec<PasswordCheck> = match parse_json_input(vaults_str) {
+        Ok(v) => v,
+        Err(e) => {
+            set_last_error(format!("Failed to parse vaults JSON: {}", e));
+            return ptr::null_mut();
+        }
+    };
+
+    let results: BTreeMap<String, bool> = vaults
+        .into_iter()
+        .map(|vault| {
+            let unlocked =
+                EncryptionManager::verify_password(password_str, &vault.salt, &vault.auth_tag)
+                    .unwrap_or(false);
+            (vault.id, unlocked)
+        })
+        .collect();
+
+    match serde_json::to_string(&results)
+        .ok()
+        .and_then(|json| CString::new(json).ok())
+    {
+        Some(c_str) => c_str.into_raw(),
+        None => {
+            set_last_error("Failed to serialize results");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Describe the note JSON layout
+///
+/// # Returns
+/// A JSON object listing every note field with its type and whether it may be
+/// omitted (optional fields may also be null), or null on error. Types are `uuid`, `string`, `string[]`,
+/// `datetime` (RFC 3339), `integer`, `boolean` and `attachment[]` (array of
+/// `{ id, filename, mime, size, note_id }` objects).
+/// The returned string must be freed with null_space_free_string.
+///
+/// # JSON Format
+/// ```json
+/// {
+///   "name": "Note",
+///   "schema_version": 4,
+///   "fields": [{ "name": "id", "type": "uuid", "optional": false }, ...]
+/// }
+/// ```
+#[no_mangle]
+pub extern "C" fn null_space_note_schema() -> *mut c_char {
+    let fields: Vec<serde_json::Value> = NOTE_FIELD_TYPES
+        .iter()
+        .map(|(name, ty)| {
+            serde_json::json!({
+                "name": name,
+                "type": ty,
+                "optional": !REQUIRED_NOTE_FIELDS.contains(name),
+            })
+        })
+        .collect();
+    let schema = serde_json::json!({
+        "name": "Note",
+        "schema_version": NOTE_SCHEMA_VERSION,
+        "fields": fields,
+    });
+
+    match CString::new(schema.to_string()) {
+        Ok(c_str) => c_str.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
 /// Create a new note
 ///
 /// # Arguments
@@ -248,9 +764,12 @@ pub extern "C" fn null_space_create_note(
     };
 
     // Parse tags JSON
-    let tags_vec: Vec<String> = match serde_json::from_str(tags_str) {
+    let tags_vec: Vec<String> = match parse_json_input(tags_str) {
         Ok(t) => t,
-        Err(_) => return ptr::null_mut(),
+        Err(e) => {
+            set_last_error(format!("Failed to parse tags JSON: {}", e));
+            return ptr::null_mut();
+        }
     };
 
     // Create the note
@@ -276,6 +795,7 @@ pub extern "C" fn null_space_create_note(
 ///
 /// # Returns
 /// JSON representation of the updated note with incremented version and timestamp, or null on error.
+/// On error, null_space_last_error describes what was wrong with the input.
 /// The returned string must be freed with null_space_free_string.
 ///
 /// # Note
@@ -284,8 +804,11 @@ pub extern "C" fn null_space_create_note(
 /// The caller should modify the note object on their side before calling this function.
 #[no_mangle]
 pub extern "C" fn null_space_update_note(note_json: *const c_char) -> *mut c_char {
+    clear_last_error();
+
     // Validate input pointer
     if note_json.is_null() {
+        set_last_error("note_json is null");
         return ptr::null_mut();
     }
 
@@ -293,14 +816,20 @@ pub extern "C" fn null_space_update_note(note_json: *const c_char) -> *mut c_cha
     let json_str = unsafe {
         match CStr::from_ptr(note_json).to_str() {
             Ok(s) => s,
-            Err(_) => return ptr::null_mut(),
+            Err(_) => {
+                set_last_error("note_json is not valid UTF-8");
+                return ptr::null_mut();
+            }
         }
     };
 
-    // Parse note from JSON
-    let mut note: Note = match serde_json::from_str(json_str) {
+    // Parse and validate note from JSON
+    let mut note = match parse_note_json(json_str) {
         Ok(n) => n,
-        Err(_) => return ptr::null_mut(),
+        Err(e) => {
+            set_last_error(e);
+            return ptr::null_mut();
+        }
     };
 
     // Update the note (this increments the version and updates timestamp)
@@ -319,12 +848,62 @@ pub extern "C" fn null_space_update_note(note_json: *const c_char) -> *mut c_cha
     }
 }
 
+/// Derive a stable note ID from a namespace and a source key
+///
+/// # Arguments
+/// * `namespace` - UUID string identifying the import source (null-terminated C string)
+/// * `source_key` - Stable key of the external item, e.g. its relative path (null-terminated C string)
+///
+/// # Returns
+/// The UUIDv5 string from `Note::deterministic_id`, or null on error.
+/// On error, null_space_last_error describes what was wrong with the input.
+/// The returned string must be freed with null_space_free_string.
+#[no_mangle]
+pub extern "C" fn null_space_deterministic_note_id(
+    namespace: *const c_char,
+    source_key: *const c_char,
+) -> *mut c_char {
+    clear_last_error();
+
+    if namespace.is_null() || source_key.is_null() {
+        set_last_error("Null namespace or source key");
+        return ptr::null_mut();
+    }
+
+    let (namespace_str, key_str) = unsafe {
+        match (
+            CStr::from_ptr(namespace).to_str(),
+            CStr::from_ptr(source_key).to_str(),
+        ) {
+            (Ok(n), Ok(k)) => (n, k),
+            _ => {
+                set_last_error("Namespace or source key is not valid UTF-8");
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let namespace = match uuid::Uuid::parse_str(namespace_str) {
+        Ok(n) => n,
+        Err(e) => {
+            set_last_error(format!("Invalid namespace UUID: {}", e));
+            return ptr::null_mut();
+        }
+    };
+
+    match CString::new(Note::deterministic_id(&namespace, key_str).to_string()) {
+        Ok(c_str) => c_str.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
 /// Search notes in the index
 ///
 /// # Arguments
 /// * `index_path` - Path to the search index directory (null-terminated C string)
 /// * `query` - Search query string (null-terminated C string)
-/// * `limit` - Maximum number of results to return
+/// * `limit` - Maximum number of results to return. Values of zero or below use a
+///   default of 20, and values above 1000 are clamped to 1000.
 ///
 /// # Returns
 /// JSON array of search results, or null on error.
@@ -356,13 +935,14 @@ pub extern "C" fn null_space_search(
     };
 
     // Create or open search engine
-    let engine = match SearchEngine::new(PathBuf::from(index_path_str)) {
+    let mut engine = match SearchEngine::new(PathBuf::from(index_path_str)) {
         Ok(e) => e,
         Err(_) => return ptr::null_mut(),
     };
+    engine.set_max_results(Some(MAX_SEARCH_LIMIT));
 
     // Perform search
-    let results = match engine.search(query_str, limit as usize) {
+    let results = match engine.search(query_str, clamp_search_limit(limit)) {
         Ok(r) => r,
         Err(_) => return ptr::null_mut(),
     };
@@ -380,23 +960,146 @@ pub extern "C" fn null_space_search(
     }
 }
 
-/// Export a vault to a ZIP file
+/// Open a search index for repeated queries
 ///
-/// # Arguments
-/// * `vault_json` - JSON representation of the vault metadata (null-terminated C string)
-/// * `notes_json` - JSON array of notes to export (null-terminated C string)
-/// * `output_path` - Path where to save the ZIP file (null-terminated C string)
-/// * `password` - Password for encrypting the vault (null-terminated C string)
+/// Avoids reopening the index for every call when paging through results with
+/// null_space_search_page. Results per page are capped at 1000.
 ///
 /// # Returns
-/// 0 on success, negative error code on failure:
+/// A handle that must be freed with null_space_search_engine_free, or null on
+/// error (see null_space_last_error).
+#[no_mangle]
+pub extern "C" fn null_space_search_engine_open(index_path: *const c_char) -> *mut c_void {
+    clear_last_error();
+
+    if index_path.is_null() {
+        set_last_error("Null index path");
+        return ptr::null_mut();
+    }
+
+    let index_path_str = unsafe {
+        match CStr::from_ptr(index_path).to_str() {
+            Ok(s) => s,
+            Err(e) => {
+                set_last_error(format!("Index path is not valid UTF-8: {}", e));
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    match SearchEngine::new(PathBuf::from(index_path_str)) {
+        Ok(mut engine) => {
+            engine.set_max_results(Some(MAX_SEARCH_LIMIT));
+            Box::into_raw(Box::new(engine)) as *mut c_void
+        }
+        Err(e) => {
+            set_last_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Fetch one page of search results from a handle
+///
+/// # Arguments
+/// * `handle` - Handle from null_space_search_engine_open
+/// * `query` - Search query string (null-terminated C string)
+/// * `page` - Zero-based page number; must not be negative
+/// * `page_size` - Results per page, clamped like the limit of null_space_search
+///
+/// # Returns
+/// A JSON object with the page of results and the total match count, or null
+/// on error (see null_space_last_error). Pages past the end, or starting more
+/// than 100,000 hits in, have an empty `results` array. The returned string must be freed with null_space_free_string.
+///
+/// # JSON Format
+/// ```json
+/// {
+///   "results": [{ "note_id": "...", "score": 1.5, "title": "...", "tags": [] }],
+///   "total": 42,
+///   "page": 0,
+///   "page_size": 20
+/// }
+/// ```
+#[no_mangle]
+pub extern "C" fn null_space_search_page(
+    handle: *mut c_void,
+    query: *const c_char,
+    page: c_int,
+    page_size: c_int,
+) -> *mut c_char {
+    clear_last_error();
+
+    if handle.is_null() || query.is_null() {
+        set_last_error("Null handle or query");
+        return ptr::null_mut();
+    }
+    if page < 0 {
+        set_last_error(format!("Page must not be negative, got {}", page));
+        return ptr::null_mut();
+    }
+
+    let engine = unsafe { &*(handle as *const SearchEngine) };
+    let query_str = unsafe {
+        match CStr::from_ptr(query).to_str() {
+            Ok(s) => s,
+            Err(e) => {
+                set_last_error(format!("Query is not valid UTF-8: {}", e));
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let result = match engine.search_page(query_str, page as usize, clamp_search_limit(page_size)) {
+        Ok(r) => r,
+        Err(e) => {
+            set_last_error(e.to_string());
+            return ptr::null_mut();
+        }
+    };
+
+    match serde_json::to_string(&result)
+        .ok()
+        .and_then(|json| CString::new(json).ok())
+    {
+        Some(c_str) => c_str.into_raw(),
+        None => {
+            set_last_error("Failed to serialize search page");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a search engine handle
+///
+/// # Safety
+/// The handle must not be used after this call.
+#[no_mangle]
+pub extern "C" fn null_space_search_engine_free(handle: *mut c_void) {
+    if !handle.is_null() {
+        unsafe {
+            let _ = Box::from_raw(handle as *mut SearchEngine);
+        }
+    }
+}
+
+/// Export a vault to a ZIP file
+///
+/// # Arguments
+/// * `vault_json` - JSON representation of the vault metadata (null-terminated C string)
+/// * `notes_json` - JSON array of notes to export (null-terminated C string)
+/// * `output_path` - Path where to save the ZIP file (null-terminated C string)
+/// * `password` - Password for encrypting the vault (null-terminated C string)
+///
+/// # Returns
+/// 0 on success, negative error code on failure:
 /// * -1: Null pointer in one or more parameters
 /// * -2: Invalid vault_json string encoding
 /// * -3: Invalid notes_json string encoding
 /// * -4: Invalid output_path string encoding
 /// * -5: Invalid password string encoding
 /// * -6: Failed to parse vault JSON
-/// * -7: Failed to parse notes JSON
+/// * -7: Failed to parse notes JSON (details via null_space_last_error)
 /// * -8: Failed to create encryption manager
 /// * -9: Failed to create file storage
 /// * -10: Failed to export vault
@@ -407,6 +1110,101 @@ pub extern "C" fn null_space_export_vault(
     output_path: *const c_char,
     password: *const c_char,
 ) -> c_int {
+    export_vault_impl(vault_json, notes_json, output_path, password, None, None)
+}
+
+/// Export a vault to a ZIP file, stopping early if the token is cancelled
+///
+/// # Arguments
+/// Same as null_space_export_vault, plus:
+/// * `cancel_token` - Handle from null_space_cancel_token_new
+///
+/// # Returns
+/// The same codes as null_space_export_vault, plus:
+/// * -11: Export was cancelled (no partial file is left behind)
+#[no_mangle]
+pub extern "C" fn null_space_export_vault_cancellable(
+    vault_json: *const c_char,
+    notes_json: *const c_char,
+    output_path: *const c_char,
+    password: *const c_char,
+    cancel_token: *mut c_void,
+) -> c_int {
+    if cancel_token.is_null() {
+        return -1;
+    }
+
+    let cancel = unsafe { &*(cancel_token as *const AtomicBool) };
+    export_vault_impl(
+        vault_json,
+        notes_json,
+        output_path,
+        password,
+        Some(cancel),
+        None,
+    )
+}
+
+/// Byte-level progress callback for null_space_export_vault_with_progress
+///
+/// C signature:
+/// `void (*)(uint64_t bytes_written, uint64_t total_bytes, void *user_data)`
+///
+/// Called synchronously on the thread running the export, never concurrently,
+/// with `bytes_written` non-decreasing. It must return quickly and must not
+/// call back into the same export.
+pub type ExportProgressCallback = extern "C" fn(u64, u64, *mut c_void);
+
+/// Export a vault to a ZIP file, reporting progress in bytes
+///
+/// # Arguments
+/// Same as null_space_export_vault, plus:
+/// * `cancel_token` - Handle from null_space_cancel_token_new, or null
+/// * `progress` - Called after the metadata and after every 64 KiB of each
+///   note with the bytes written so far and the estimated total; the final
+///   call has them equal
+/// * `user_data` - Passed through to `progress` untouched
+///
+/// The total is estimated by a validation pass that encrypts every note once
+/// before the real export starts, so the first report arrives after it.
+///
+/// # Returns
+/// The same codes as null_space_export_vault_cancellable.
+#[no_mangle]
+pub extern "C" fn null_space_export_vault_with_progress(
+    vault_json: *const c_char,
+    notes_json: *const c_char,
+    output_path: *const c_char,
+    password: *const c_char,
+    cancel_token: *mut c_void,
+    progress: Option<ExportProgressCallback>,
+    user_data: *mut c_void,
+) -> c_int {
+    let Some(progress) = progress else {
+        return -1;
+    };
+    let cancel =
+        (!cancel_token.is_null()).then(|| unsafe { &*(cancel_token as *const AtomicBool) });
+    export_vault_impl(
+        vault_json,
+        notes_json,
+        output_path,
+        password,
+        cancel,
+        Some((progress, user_data)),
+    )
+}
+
+fn export_vault_impl(
+    vault_json: *const c_char,
+    notes_json: *const c_char,
+    output_path: *const c_char,
+    password: *const c_char,
+    cancel: Option<&AtomicBool>,
+    progress: Option<(ExportProgressCallback, *mut c_void)>,
+) -> c_int {
+    clear_last_error();
+
     // Validate input pointers
     if vault_json.is_null() || notes_json.is_null() || output_path.is_null() || password.is_null() {
         return -1;
@@ -442,15 +1240,21 @@ pub extern "C" fn null_space_export_vault(
     };
 
     // Parse vault metadata
-    let vault: crate::models::Vault = match serde_json::from_str(vault_json_str) {
+    let vault: crate::models::Vault = match parse_json_input(vault_json_str) {
         Ok(v) => v,
-        Err(_) => return -6,
+        Err(e) => {
+            set_last_error(format!("Failed to parse vault JSON: {}", e));
+            return -6;
+        }
     };
 
-    // Parse notes
-    let notes: Vec<Note> = match serde_json::from_str(notes_json_str) {
+    // Parse and validate notes
+    let notes = match parse_notes_json(notes_json_str) {
         Ok(n) => n,
-        Err(_) => return -7,
+        Err(e) => {
+            set_last_error(e);
+            return -7;
+        }
     };
 
     // Create encryption manager
@@ -468,12 +1272,66 @@ pub extern "C" fn null_space_export_vault(
     let vault_manager = VaultManager::new(storage);
 
     // Export vault
-    match vault_manager.export_vault(&vault, &notes, Path::new(output_path_str), Some(&manager)) {
+    let result = match (progress, cancel) {
+        (Some((callback, user_data)), cancel) => vault_manager.export_vault_with_progress(
+            &vault,
+            &notes,
+            Path::new(output_path_str),
+            Some(&manager),
+            cancel,
+            &mut |written, total| callback(written, total, user_data),
+        ),
+        (None, Some(flag)) => vault_manager.export_vault_cancellable(
+            &vault,
+            &notes,
+            Path::new(output_path_str),
+            Some(&manager),
+            flag,
+        ),
+        (None, None) => {
+            vault_manager.export_vault(&vault, &notes, Path::new(output_path_str), Some(&manager))
+        }
+    };
+
+    match result {
         Ok(_) => 0,
+        Err(VaultError::Cancelled) => -11,
         Err(_) => -10,
     }
 }
 
+/// Create a cancellation token for long-running vault operations
+///
+/// # Returns
+/// A handle that must be freed with null_space_cancel_token_free.
+#[no_mangle]
+pub extern "C" fn null_space_cancel_token_new() -> *mut c_void {
+    Box::into_raw(Box::new(AtomicBool::new(false))) as *mut c_void
+}
+
+/// Request cancellation of any operation using this token
+///
+/// Safe to call from a different thread than the one running the operation.
+#[no_mangle]
+pub extern "C" fn null_space_cancel_token_cancel(token: *mut c_void) {
+    if !token.is_null() {
+        unsafe { (*(token as *const AtomicBool)).store(true, Ordering::SeqCst) };
+    }
+}
+
+/// Free a cancellation token
+///
+/// # Safety
+/// The token must not be in use by a running operation.
+#[no_mangle]
+pub extern "C" fn null_space_cancel_token_free(token: *mut c_void) {
+    if !token.is_null() {
+        unsafe {
+            let _ = Box::from_raw(token as *mut AtomicBool);
+        }
+    }
+}
+
 /// Import a vault from a ZIP file
 ///
 /// # Arguments
@@ -577,6 +1435,168 @@ pub extern "C" fn null_space_free_string(ptr: *mut c_char) {
     }
 }
 
+/// Read a null-terminated UTF-16 string, rejecting unpaired surrogates
+///
+/// # Safety
+/// `ptr` must be non-null and point to a null-terminated UTF-16 buffer.
+unsafe fn wide_to_string(ptr: *const u16) -> Option<String> {
+    let mut len = 0;
+    while *ptr.add(len) != 0 {
+        len += 1;
+    }
+    String::from_utf16(std::slice::from_raw_parts(ptr, len)).ok()
+}
+
+/// Copy a UTF-8 C string returned by this module into a new wide string
+///
+/// Takes ownership of `utf8` (freeing it) and returns null if it is null.
+fn utf8_result_to_wide(utf8: *mut c_char) -> *mut u16 {
+    if utf8.is_null() {
+        return ptr::null_mut();
+    }
+    let text = unsafe { CString::from_raw(utf8) };
+    let mut wide: Vec<u16> = text.to_string_lossy().encode_utf16().collect();
+    wide.push(0);
+    Box::into_raw(wide.into_boxed_slice()) as *mut u16
+}
+
+/// Run a UTF-8 FFI function on wide string arguments
+fn with_wide_args<const N: usize>(
+    args: [*const u16; N],
+    f: impl FnOnce([*const c_char; N]) -> *mut c_char,
+) -> *mut u16 {
+    if args.iter().any(|arg| arg.is_null()) {
+        set_last_error("Null argument");
+        return ptr::null_mut();
+    }
+
+    let mut owned = Vec::with_capacity(N);
+    for arg in args {
+        match unsafe { wide_to_string(arg) }.and_then(|s| CString::new(s).ok()) {
+            Some(c_str) => owned.push(c_str),
+            None => {
+                set_last_error("Argument is not valid UTF-16 or contains a null character");
+                return ptr::null_mut();
+            }
+        }
+    }
+
+    let ptrs: [*const c_char; N] = std::array::from_fn(|i| owned[i].as_ptr());
+    utf8_result_to_wide(f(ptrs))
+}
+
+/// UTF-16 variant of null_space_encrypt for Win32 callers
+///
+/// Takes null-terminated UTF-16 arguments; the plaintext is encrypted as
+/// UTF-8, so the ciphertext is interchangeable with null_space_encrypt's.
+///
+/// # Returns
+/// The base64-encoded ciphertext as a null-terminated UTF-16 string, or null
+/// on error. The returned string must be freed with null_space_free_wstring.
+#[no_mangle]
+pub extern "C" fn null_space_encrypt_w(
+    data: *const u16,
+    password: *const u16,
+    salt: *const u16,
+) -> *mut u16 {
+    clear_last_error();
+    with_wide_args([data, password, salt], |[data, password, salt]| {
+        null_space_encrypt(data, password, salt)
+    })
+}
+
+/// UTF-16 variant of null_space_decrypt for Win32 callers
+///
+/// Decrypts directly rather than through null_space_decrypt, so the plaintext
+/// is encoded to UTF-16 straight from a buffer that wipes itself, with no
+/// UTF-8 C string left behind in freed memory. The password is wiped too.
+///
+/// # Returns
+/// The plaintext as a null-terminated UTF-16 string, or null on error (see
+/// null_space_last_error). The returned string must be freed with
+/// null_space_free_wstring.
+#[no_mangle]
+pub extern "C" fn null_space_decrypt_w(
+    encrypted: *const u16,
+    password: *const u16,
+    salt: *const u16,
+) -> *mut u16 {
+    clear_last_error();
+
+    if encrypted.is_null() || password.is_null() || salt.is_null() {
+        set_last_error("Null argument");
+        return ptr::null_mut();
+    }
+    let (encrypted_str, password_str, salt_str) = unsafe {
+        match (
+            wide_to_string(encrypted),
+            wide_to_string(password).map(Zeroizing::new),
+            wide_to_string(salt),
+        ) {
+            (Some(e), Some(p), Some(s)) => (e, p, s),
+            _ => {
+                set_last_error("Argument is not valid UTF-16");
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let encrypted_bytes = match general_purpose::STANDARD.decode(&encrypted_str) {
+        Ok(b) => b,
+        Err(e) => {
+            set_last_error(format!("Ciphertext is not valid base64: {}", e));
+            return ptr::null_mut();
+        }
+    };
+    let manager = match EncryptionManager::new_from_password(&password_str, &salt_str) {
+        Ok(m) => m,
+        Err(e) => {
+            set_last_error(e.to_string());
+            return ptr::null_mut();
+        }
+    };
+    let decrypted = match manager.decrypt_zeroizing(&encrypted_bytes) {
+        Ok(d) => d,
+        Err(e) => {
+            set_last_error(e.to_string());
+            return ptr::null_mut();
+        }
+    };
+    let text = match std::str::from_utf8(&decrypted) {
+        Ok(t) => t,
+        Err(_) => {
+            set_last_error("Plaintext is not valid UTF-8");
+            return ptr::null_mut();
+        }
+    };
+
+    // Sized up front so the buffer is never reallocated, which would leave a
+    // stale copy of the plaintext behind
+    let mut wide: Vec<u16> = Vec::with_capacity(text.encode_utf16().count() + 1);
+    wide.extend(text.encode_utf16());
+    wide.push(0);
+    Box::into_raw(wide.into_boxed_slice()) as *mut u16
+}
+
+/// Free a UTF-16 string allocated by this library
+///
+/// # Safety
+/// The pointer must have been returned by one of the `_w` FFI functions in
+/// this module. Calling this with any other pointer is undefined behavior.
+#[no_mangle]
+pub extern "C" fn null_space_free_wstring(ptr: *mut u16) {
+    if ptr.is_null() {
+        return;
+    }
+    unsafe {
+        let mut len = 0;
+        while *ptr.add(len) != 0 {
+            len += 1;
+        }
+        let _ = Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len + 1));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -628,6 +1648,18 @@ mod tests {
         null_space_free_string(decrypted_ptr);
     }
 
+    #[test]
+    fn test_encrypt_rejects_weak_password() {
+        let salt_ptr = null_space_generate_salt();
+        let data = CString::new("Hello, World!").unwrap();
+        let password = CString::new("short").unwrap();
+
+        let encrypted_ptr = null_space_encrypt(data.as_ptr(), password.as_ptr(), salt_ptr);
+        assert!(encrypted_ptr.is_null());
+
+        null_space_free_string(salt_ptr);
+    }
+
     #[test]
     fn test_create_note() {
         let title = CString::new("Test Note").unwrap();
@@ -691,6 +1723,176 @@ mod tests {
         null_space_free_string(updated_ptr);
     }
 
+    fn last_error() -> String {
+        let ptr = null_space_last_error();
+        assert!(!ptr.is_null());
+        let message = unsafe { CStr::from_ptr(ptr).to_string_lossy().to_string() };
+        null_space_free_string(ptr);
+        message
+    }
+
+    #[test]
+    fn test_update_note_validation_errors() {
+        let note = Note::new("Title".to_string(), "Content".to_string(), vec![]);
+        let mut value = serde_json::to_value(&note).unwrap();
+
+        // Malformed UUID
+        value["id"] = serde_json::json!("not-a-uuid");
+        let json = CString::new(value.to_string()).unwrap();
+        assert!(null_space_update_note(json.as_ptr()).is_null());
+        let bad_uuid = last_error();
+        assert!(bad_uuid.contains("Invalid UUID"));
+
+        // Missing field
+        let mut value = serde_json::to_value(&note).unwrap();
+        value.as_object_mut().unwrap().remove("title");
+        let json = CString::new(value.to_string()).unwrap();
+        assert!(null_space_update_note(json.as_ptr()).is_null());
+        let missing = last_error();
+        assert!(missing.contains("Missing required field 'title'"));
+
+        // Not JSON at all
+        let json = CString::new("{oops").unwrap();
+        assert!(null_space_update_note(json.as_ptr()).is_null());
+        let malformed = last_error();
+        assert!(malformed.contains("Malformed JSON"));
+
+        assert_ne!(bad_uuid, missing);
+        assert_ne!(missing, malformed);
+
+        // A valid call clears the error
+        let json = CString::new(serde_json::to_string(&note).unwrap()).unwrap();
+        let updated = null_space_update_note(json.as_ptr());
+        assert!(!updated.is_null());
+        assert!(null_space_last_error().is_null());
+        null_space_free_string(updated);
+    }
+
+    #[test]
+    fn test_search_limit_clamping() {
+        assert_eq!(clamp_search_limit(-5), DEFAULT_SEARCH_LIMIT);
+        assert_eq!(clamp_search_limit(0), DEFAULT_SEARCH_LIMIT);
+        assert_eq!(clamp_search_limit(7), 7);
+        assert_eq!(clamp_search_limit(c_int::MAX), MAX_SEARCH_LIMIT);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        for i in 0..3 {
+            engine
+                .index_note(
+                    &mut writer,
+                    &format!("note-{}", i),
+                    "Bounded",
+                    "",
+                    &[],
+                    0,
+                    0,
+                )
+                .unwrap();
+        }
+        engine.commit(&mut writer).unwrap();
+        drop(writer);
+
+        let path = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let query = CString::new("bounded").unwrap();
+        for limit in [-1, c_int::MAX] {
+            let results_ptr = null_space_search(path.as_ptr(), query.as_ptr(), limit);
+            assert!(!results_ptr.is_null());
+            let json = unsafe { CStr::from_ptr(results_ptr).to_string_lossy().to_string() };
+            let results: Vec<(f32, String)> = serde_json::from_str(&json).unwrap();
+            assert_eq!(results.len(), 3);
+            null_space_free_string(results_ptr);
+        }
+    }
+
+    #[test]
+    fn test_search_page_handle() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        for i in 0..7 {
+            engine
+                .index_note(&mut writer, &format!("note-{}", i), "Paged", "", &[], 0, 0)
+                .unwrap();
+        }
+        engine.commit(&mut writer).unwrap();
+        drop(writer);
+        drop(engine);
+
+        let path = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let query = CString::new("paged").unwrap();
+        let handle = null_space_search_engine_open(path.as_ptr());
+        assert!(!handle.is_null());
+
+        let mut ids = Vec::new();
+        for page in 0..4 {
+            let page_ptr = null_space_search_page(handle, query.as_ptr(), page, 3);
+            assert!(!page_ptr.is_null());
+            let json = unsafe { CStr::from_ptr(page_ptr).to_string_lossy().to_string() };
+            let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(value["total"], 7);
+            for result in value["results"].as_array().unwrap() {
+                ids.push(result["note_id"].as_str().unwrap().to_string());
+            }
+            null_space_free_string(page_ptr);
+        }
+        ids.sort();
+        let expected: Vec<String> = (0..7).map(|i| format!("note-{}", i)).collect();
+        assert_eq!(ids, expected);
+
+        let far_ptr = null_space_search_page(handle, query.as_ptr(), c_int::MAX, 1000);
+        assert!(!far_ptr.is_null());
+        let json = unsafe { CStr::from_ptr(far_ptr).to_string_lossy().to_string() };
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["results"].as_array().unwrap().len(), 0);
+        assert_eq!(value["total"], 7);
+        null_space_free_string(far_ptr);
+
+        assert!(null_space_search_page(handle, query.as_ptr(), -1, 3).is_null());
+        assert!(!null_space_last_error().is_null());
+        null_space_search_engine_free(handle);
+    }
+
+    #[test]
+    fn test_cancel_token_export() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let salt = EncryptionManager::generate_salt();
+        let vault = crate::models::Vault::new("Vault".to_string(), String::new(), salt);
+        let notes = vec![Note::new("Note".to_string(), String::new(), vec![])];
+
+        let vault_json = CString::new(serde_json::to_string(&vault).unwrap()).unwrap();
+        let notes_json = CString::new(serde_json::to_string(&notes).unwrap()).unwrap();
+        let output = temp_dir.path().join("vault.zip");
+        let output_path = CString::new(output.to_str().unwrap()).unwrap();
+        let password = CString::new("password").unwrap();
+
+        let token = null_space_cancel_token_new();
+        null_space_cancel_token_cancel(token);
+        let code = null_space_export_vault_cancellable(
+            vault_json.as_ptr(),
+            notes_json.as_ptr(),
+            output_path.as_ptr(),
+            password.as_ptr(),
+            token,
+        );
+        assert_eq!(code, -11);
+        assert!(!output.exists());
+        null_space_cancel_token_free(token);
+
+        let token = null_space_cancel_token_new();
+        let code = null_space_export_vault_cancellable(
+            vault_json.as_ptr(),
+            notes_json.as_ptr(),
+            output_path.as_ptr(),
+            password.as_ptr(),
+            token,
+        );
+        assert_eq!(code, 0);
+        assert!(output.exists());
+        null_space_cancel_token_free(token);
+    }
+
     #[test]
     fn test_null_pointer_handling() {
         // Test that functions handle null pointers gracefully
@@ -705,4 +1907,224 @@ mod tests {
         );
         assert!(null_space_import_vault(ptr::null(), ptr::null()).is_null());
     }
+
+    #[test]
+    fn test_verify_passwords() {
+        let password = CString::new("shared password").unwrap();
+        let vault = |id: &str, password: &str| {
+            let salt = EncryptionManager::generate_salt();
+            let manager = EncryptionManager::new_from_password(password, &salt).unwrap();
+            serde_json::json!({ "id": id, "salt": salt, "auth_tag": manager.auth_tag() })
+        };
+        let vaults = serde_json::json!([
+            vault("personal", "shared password"),
+            vault("work", "different password"),
+        ]);
+        let vaults_json = CString::new(vaults.to_string()).unwrap();
+
+        let result_ptr = null_space_verify_passwords(vaults_json.as_ptr(), password.as_ptr());
+        assert!(!result_ptr.is_null());
+        let json = unsafe { CStr::from_ptr(result_ptr).to_string_lossy().to_string() };
+        let results: BTreeMap<String, bool> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            results,
+            BTreeMap::from([("personal".to_string(), true), ("work".to_string(), false)])
+        );
+        null_space_free_string(result_ptr);
+
+        let bad_json = CString::new("{").unwrap();
+        assert!(null_space_verify_passwords(bad_json.as_ptr(), password.as_ptr()).is_null());
+        assert!(!null_space_last_error().is_null());
+    }
+
+    #[test]
+    fn test_wide_encrypt_decrypt_round_trip() {
+        let wide = |s: &str| {
+            s.encode_utf16()
+                .chain(std::iter::once(0))
+                .collect::<Vec<u16>>()
+        };
+        let from_wide = |ptr: *mut u16| {
+            let text = unsafe { wide_to_string(ptr) }.unwrap();
+            null_space_free_wstring(ptr);
+            text
+        };
+
+        // Includes characters outside the Basic Multilingual Plane (surrogate pairs)
+        let plaintext = "Grüße 🔐 𝄞 notes";
+        let data = wide(plaintext);
+        let password = wide("correct horse 🐎");
+        let salt = wide(&EncryptionManager::generate_salt());
+
+        let encrypted = null_space_encrypt_w(data.as_ptr(), password.as_ptr(), salt.as_ptr());
+        assert!(!encrypted.is_null());
+        let encrypted = wide(&from_wide(encrypted));
+
+        let decrypted = null_space_decrypt_w(encrypted.as_ptr(), password.as_ptr(), salt.as_ptr());
+        assert!(!decrypted.is_null());
+        assert_eq!(from_wide(decrypted), plaintext);
+        let wrong = wide("wrong");
+        assert!(null_space_decrypt_w(encrypted.as_ptr(), wrong.as_ptr(), salt.as_ptr()).is_null());
+
+        // An unpaired surrogate is not valid UTF-16
+        let broken = [0xD800u16, 0];
+        assert!(null_space_encrypt_w(broken.as_ptr(), password.as_ptr(), salt.as_ptr()).is_null());
+        assert!(null_space_encrypt_w(ptr::null(), password.as_ptr(), salt.as_ptr()).is_null());
+    }
+
+    #[test]
+    fn test_encryption_handle_round_trips() {
+        let salt = CString::new(EncryptionManager::generate_salt()).unwrap();
+        let password = CString::new("test_password").unwrap();
+
+        let handle = null_space_encryption_new(password.as_ptr(), salt.as_ptr());
+        assert!(!handle.is_null());
+
+        // Many operations share the one derived key and cipher
+        for i in 0..50 {
+            let data = CString::new(format!("Note {}", i)).unwrap();
+            let encrypted = null_space_encrypt_with_key(handle, data.as_ptr());
+            assert!(!encrypted.is_null());
+
+            let decrypted = null_space_decrypt_with_key(handle, encrypted);
+            assert_eq!(
+                unsafe { CStr::from_ptr(decrypted) }.to_str().unwrap(),
+                format!("Note {}", i)
+            );
+            null_space_free_string(encrypted);
+            null_space_free_string(decrypted);
+        }
+
+        // Output is compatible with the password-based functions
+        let data = CString::new("shared format").unwrap();
+        let encrypted = null_space_encrypt_with_key(handle, data.as_ptr());
+        let decrypted = null_space_decrypt(encrypted, password.as_ptr(), salt.as_ptr());
+        assert_eq!(
+            unsafe { CStr::from_ptr(decrypted) }.to_str().unwrap(),
+            "shared format"
+        );
+        null_space_free_string(encrypted);
+        null_space_free_string(decrypted);
+
+        let garbage = CString::new("not base64!").unwrap();
+        assert!(null_space_decrypt_with_key(handle, garbage.as_ptr()).is_null());
+        assert!(!last_error().is_empty());
+
+        null_space_encryption_free(handle);
+        null_space_encryption_free(ptr::null_mut());
+        assert!(null_space_encrypt_with_key(ptr::null_mut(), data.as_ptr()).is_null());
+    }
+
+    #[test]
+    fn test_note_schema_matches_model() {
+        let ptr = null_space_note_schema();
+        let schema: serde_json::Value =
+            serde_json::from_str(unsafe { CStr::from_ptr(ptr) }.to_str().unwrap()).unwrap();
+        null_space_free_string(ptr);
+
+        let fields = schema["fields"].as_array().unwrap();
+        let mut note = Note::new(
+            "Title".to_string(),
+            "Content".to_string(),
+            vec!["tag".to_string()],
+        );
+        // Attachments are omitted when empty, so include one to cover the field
+        note.add_attachment(crate::models::Attachment::new(
+            note.id,
+            "a.png".to_string(),
+            "image/png".to_string(),
+            1,
+        ));
+        let note = serde_json::to_value(note).unwrap();
+        let note = note.as_object().unwrap();
+        assert_eq!(fields.len(), note.len());
+
+        for field in fields {
+            let name = field["name"].as_str().unwrap();
+            let value = note
+                .get(name)
+                .unwrap_or_else(|| panic!("schema lists unknown field {}", name));
+            let optional = field["optional"].as_bool().unwrap();
+            let type_matches = (optional && value.is_null())
+                || match field["type"].as_str().unwrap() {
+                    "uuid" => value
+                        .as_str()
+                        .is_some_and(|s| uuid::Uuid::parse_str(s).is_ok()),
+                    "string" => value.is_string(),
+                    "string[]" => value
+                        .as_array()
+                        .is_some_and(|items| items.iter().all(|i| i.is_string())),
+                    "datetime" => value
+                        .as_str()
+                        .is_some_and(|s| chrono::DateTime::parse_from_rfc3339(s).is_ok()),
+                    "integer" => value.is_u64(),
+                    "boolean" => value.is_boolean(),
+                    "attachment[]" => value.as_array().is_some_and(|items| {
+                        items.iter().all(|i| {
+                            serde_json::from_value::<crate::models::Attachment>(i.clone()).is_ok()
+                        })
+                    }),
+                    other => panic!("unknown type {}", other),
+                };
+            assert!(type_matches, "field {} has the wrong type", name);
+            assert_eq!(optional, !REQUIRED_NOTE_FIELDS.contains(&name));
+        }
+    }
+
+    #[test]
+    fn test_deterministic_note_id() {
+        let namespace = CString::new("6ba7b811-9dad-11d1-80b4-00c04fd430c8").unwrap();
+        let key = CString::new("journal/2024-01-01.md").unwrap();
+
+        let id = |namespace: &CString| {
+            let ptr = null_space_deterministic_note_id(namespace.as_ptr(), key.as_ptr());
+            assert!(!ptr.is_null());
+            let id = unsafe { CStr::from_ptr(ptr).to_string_lossy().to_string() };
+            null_space_free_string(ptr);
+            id
+        };
+        let first = id(&namespace);
+        assert_eq!(first, id(&namespace));
+        assert_eq!(
+            first,
+            Note::deterministic_id(&uuid::Uuid::NAMESPACE_URL, "journal/2024-01-01.md").to_string()
+        );
+
+        let invalid = CString::new("not-a-uuid").unwrap();
+        let ptr = null_space_deterministic_note_id(invalid.as_ptr(), key.as_ptr());
+        assert!(ptr.is_null());
+        let error = null_space_last_error();
+        assert!(!error.is_null());
+        let message = unsafe { CStr::from_ptr(error).to_string_lossy().to_string() };
+        assert!(message.contains("Invalid namespace"), "{}", message);
+        null_space_free_string(error);
+    }
+
+    #[test]
+    fn test_json_input_limits() {
+        let last_error = || {
+            let ptr = null_space_last_error();
+            assert!(!ptr.is_null());
+            let message = unsafe { CStr::from_ptr(ptr).to_string_lossy().to_string() };
+            null_space_free_string(ptr);
+            message
+        };
+
+        let nested = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        let nested = CString::new(nested).unwrap();
+        assert!(null_space_update_note(nested.as_ptr()).is_null());
+        assert!(last_error().contains("nesting"));
+
+        // Brackets inside strings don't count towards the depth
+        let tags = CString::new(format!("[\"{}\"]", "[{".repeat(100))).unwrap();
+        let title = CString::new("T").unwrap();
+        let note_ptr = null_space_create_note(title.as_ptr(), title.as_ptr(), tags.as_ptr());
+        assert!(!note_ptr.is_null());
+        null_space_free_string(note_ptr);
+
+        let huge = format!("[\"{}\"]", "a".repeat(MAX_JSON_INPUT_BYTES));
+        let huge = CString::new(huge).unwrap();
+        assert!(null_space_create_note(title.as_ptr(), title.as_ptr(), huge.as_ptr()).is_null());
+        assert!(last_error().contains("byte limit"));
+    }
 }
@@ -9,12 +9,15 @@
 
 pub mod crypto;
 pub mod ffi;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod models;
 pub mod search;
 pub mod storage;
 pub mod vault;
 
 pub use crypto::{EncryptionError, EncryptionManager};
+pub use models::ModelError;
 pub use search::{SearchEngine, SearchError};
 pub use storage::{FileStorage, StorageError};
 pub use vault::{VaultError, VaultManager};
@@ -29,6 +32,6 @@ mod tests {
     #[test]
     fn test_library_loads() {
         // Basic sanity test
-        assert!(true);
+        assert!(!EncryptionManager::generate_salt().is_empty());
     }
 }
@@ -0,0 +1,185 @@
+//! Per-operation timing metrics
+//!
+//! Only compiled with the `metrics` feature. Timings are accumulated in a
+//! process-wide atomic registry so they can be inspected in the field, e.g. to
+//! confirm that a slow unlock is spent in key derivation.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// An instrumented operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    KeyDerivation,
+    Encrypt,
+    Decrypt,
+    IndexCommit,
+    Search,
+}
+
+impl Operation {
+    const ALL: [Operation; 5] = [
+        Operation::KeyDerivation,
+        Operation::Encrypt,
+        Operation::Decrypt,
+        Operation::IndexCommit,
+        Operation::Search,
+    ];
+
+    fn slot(self) -> &'static Counters {
+        &REGISTRY[self as usize]
+    }
+}
+
+/// Accumulated timings for one operation
+struct Counters {
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            total_nanos: AtomicU64::new(0),
+            max_nanos: AtomicU64::new(0),
+        }
+    }
+}
+
+static REGISTRY: [Counters; Operation::ALL.len()] = [
+    Counters::new(),
+    Counters::new(),
+    Counters::new(),
+    Counters::new(),
+    Counters::new(),
+];
+
+/// Timings recorded for one operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OperationMetrics {
+    pub operation: Operation,
+    pub count: u64,
+    pub total_nanos: u64,
+    pub max_nanos: u64,
+}
+
+/// Point-in-time copy of all recorded timings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MetricsSnapshot {
+    pub operations: Vec<OperationMetrics>,
+}
+
+impl MetricsSnapshot {
+    /// Timings for a single operation
+    pub fn get(&self, operation: Operation) -> &OperationMetrics {
+        &self.operations[operation as usize]
+    }
+}
+
+/// Record one completed operation
+pub fn record(operation: Operation, elapsed: Duration) {
+    let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+    let counters = operation.slot();
+    counters.count.fetch_add(1, Ordering::Relaxed);
+    counters.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+    counters.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+}
+
+/// Copy the current timings out of the registry
+pub fn snapshot() -> MetricsSnapshot {
+    MetricsSnapshot {
+        operations: Operation::ALL
+            .iter()
+            .map(|&operation| {
+                let counters = operation.slot();
+                OperationMetrics {
+                    operation,
+                    count: counters.count.load(Ordering::Relaxed),
+                    total_nanos: counters.total_nanos.load(Ordering::Relaxed),
+                    max_nanos: counters.max_nanos.load(Ordering::Relaxed),
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Clear all recorded timings
+pub fn reset() {
+    for counters in &REGISTRY {
+        counters.count.store(0, Ordering::Relaxed);
+        counters.total_nanos.store(0, Ordering::Relaxed);
+        counters.max_nanos.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Records the elapsed time for an operation when dropped
+pub(crate) struct Timer {
+    operation: Operation,
+    start: Instant,
+}
+
+impl Timer {
+    pub(crate) fn start(operation: Operation) -> Self {
+        Self {
+            operation,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        record(self.operation, self.start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EncryptionManager, SearchEngine};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_operations_are_recorded() {
+        let before = snapshot();
+
+        let salt = EncryptionManager::generate_salt();
+        let manager = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let encrypted = manager.encrypt(b"data").unwrap();
+        manager.decrypt(&encrypted).unwrap();
+
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        engine.commit(&mut writer).unwrap();
+        engine.search("anything", 10).unwrap();
+
+        // Other tests share the registry, so compare against the earlier snapshot
+        let after = snapshot();
+        for operation in Operation::ALL {
+            let (was, now) = (before.get(operation), after.get(operation));
+            assert!(now.count > was.count, "{:?} not counted", operation);
+            assert!(
+                now.total_nanos > was.total_nanos,
+                "{:?} not timed",
+                operation
+            );
+            assert!(now.max_nanos > 0);
+        }
+    }
+
+    #[test]
+    fn test_snapshot_serializes() {
+        record(Operation::Search, Duration::from_micros(5));
+        let json = serde_json::to_value(snapshot()).unwrap();
+        assert!(json["operations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|op| op["operation"] == "search"));
+    }
+}
@@ -1,9 +1,39 @@
 //! Data models for notes, tags, and vaults
 
+use base64::{engine::general_purpose, Engine as _};
 use chrono::{DateTime, Utc};
+use pulldown_cmark::{html, Event, HeadingLevel, Options, Parser, Tag as MdTag, TagEnd};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::OnceLock;
+use thiserror::Error;
 use uuid::Uuid;
 
+/// Current version of the serialized `Note` layout
+pub const NOTE_SCHEMA_VERSION: u32 = 4;
+
+/// Line between the metadata and the content in `Note::to_diffable` output
+///
+/// JSON strings can't contain raw newlines, so the first match always ends
+/// the header.
+const DIFFABLE_SEPARATOR: &str = "\n---\n";
+
+#[derive(Error, Debug)]
+pub enum ModelError {
+    #[error("Invalid note: {0}")]
+    InvalidNote(String),
+    #[error("Unsupported note schema version: {0}")]
+    UnsupportedSchemaVersion(u64),
+    #[error("Note updated_at is earlier than created_at")]
+    UpdatedBeforeCreated,
+    #[error("Invalid note version: {0} (must be at least 1)")]
+    InvalidVersion(u64),
+    #[error("Duplicate tag: {0}")]
+    DuplicateTag(String),
+}
+
 /// A note in the system
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Note {
@@ -21,6 +51,52 @@ pub struct Note {
     pub updated_at: DateTime<Utc>,
     /// Version for conflict detection
     pub version: u64,
+    /// Whether the note is pinned to the top of lists
+    #[serde(default)]
+    pub pinned: bool,
+    /// Layout version this note was serialized with (0 when absent)
+    #[serde(default)]
+    pub schema_version: u32,
+    /// ID of the note this one was copied from, e.g. by a `KeepBoth` import
+    #[serde(default)]
+    pub derived_from: Option<Uuid>,
+    /// Terms to index instead of the content, for notes whose body must stay
+    /// out of the cleartext search index (see `Note::indexed_content`)
+    #[serde(default)]
+    pub search_keywords: Vec<String>,
+    /// Files this note references via `attachment:<id>` links
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+/// A file attached to a note, stored alongside it
+///
+/// Content links to an attachment as `![](attachment:<id>)` (or the
+/// non-image `[label](attachment:<id>)`); see `Note::resolve_attachment_links`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: Uuid,
+    /// Original file name, e.g. `diagram.png`
+    pub filename: String,
+    /// MIME type, e.g. `image/png`
+    pub mime: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Note the attachment belongs to
+    pub note_id: Uuid,
+}
+
+impl Attachment {
+    /// Describe a new attachment of `note_id` with a fresh ID
+    pub fn new(note_id: Uuid, filename: String, mime: String, size: u64) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            filename,
+            mime,
+            size,
+            note_id,
+        }
+    }
 }
 
 impl Note {
@@ -35,21 +111,1127 @@ impl Note {
             created_at: now,
             updated_at: now,
             version: 1,
+            pinned: false,
+            schema_version: NOTE_SCHEMA_VERSION,
+            derived_from: None,
+            search_keywords: Vec::new(),
+            attachments: Vec::new(),
         }
     }
 
+    /// Rehydrate a note from stored parts, validating them
+    ///
+    /// Rejects `updated_at` earlier than `created_at`, a version below 1, and
+    /// duplicate tags. Use this instead of a struct literal in import and sync
+    /// code that receives notes from outside.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_parts(
+        id: Uuid,
+        title: String,
+        content: String,
+        tags: Vec<String>,
+        created_at: DateTime<Utc>,
+        updated_at: DateTime<Utc>,
+        version: u64,
+    ) -> Result<Self, ModelError> {
+        if updated_at < created_at {
+            return Err(ModelError::UpdatedBeforeCreated);
+        }
+        if version < 1 {
+            return Err(ModelError::InvalidVersion(version));
+        }
+        let mut seen = HashSet::new();
+        if let Some(duplicate) = tags.iter().find(|tag| !seen.insert(tag.as_str())) {
+            return Err(ModelError::DuplicateTag(duplicate.clone()));
+        }
+
+        Ok(Self {
+            id,
+            title,
+            content,
+            tags,
+            created_at,
+            updated_at,
+            version,
+            pinned: false,
+            schema_version: NOTE_SCHEMA_VERSION,
+            derived_from: None,
+            search_keywords: Vec::new(),
+            attachments: Vec::new(),
+        })
+    }
+
+    /// Derive a stable note ID from an external source
+    ///
+    /// Uses UUIDv5 (SHA-1 over `namespace` and `source_key`), so importing the same
+    /// source (e.g. a Markdown file path) repeatedly yields the same ID and
+    /// re-imports are caught by conflict detection instead of duplicating notes.
+    pub fn deterministic_id(namespace: &Uuid, source_key: &str) -> Uuid {
+        Uuid::new_v5(namespace, source_key.as_bytes())
+    }
+
     /// Update the note content and increment version
     pub fn update(&mut self, title: String, content: String, tags: Vec<String>) {
         self.title = title;
         self.content = content;
         self.tags = tags;
-        self.updated_at = Utc::now();
+        self.touch_timestamp();
+        self.version += 1;
+    }
+
+    /// Advance `updated_at` to now, but never backwards
+    ///
+    /// With a skewed or reset device clock "now" can be earlier than the last
+    /// update; the timestamp then moves 1ms past the previous one instead, so
+    /// it keeps increasing along with the version and "latest wins" sync holds.
+    fn touch_timestamp(&mut self) {
+        let next = self.updated_at + chrono::Duration::milliseconds(1);
+        self.updated_at = Utc::now().max(next);
+    }
+
+    /// Split the content into one new note per heading of the given level
+    ///
+    /// Each section note takes its title from the heading and its content from
+    /// the text up to the next heading of the same or a higher level, starting
+    /// with a `[[<this title>]]` backlink. Sections get fresh IDs, this note's
+    /// tags, and `derived_from` set to this note. Text before the first heading
+    /// belongs to no section. This note is left unchanged; to turn it into an
+    /// index of the sections, update its content with `section_index`. Levels
+    /// outside 1-6 produce no sections.
+    pub fn split_by_heading(&self, level: u8) -> Vec<Note> {
+        let Ok(target) = HeadingLevel::try_from(level as usize) else {
+            return Vec::new();
+        };
+
+        // (title, body start) of the open section, and finished sections
+        let mut open: Option<(String, usize)> = None;
+        let mut sections: Vec<(String, &str)> = Vec::new();
+        let mut heading: Option<(HeadingLevel, usize, String)> = None;
+
+        for (event, range) in Parser::new_ext(&self.content, Options::all()).into_offset_iter() {
+            match event {
+                Event::Start(MdTag::Heading { level, .. }) => {
+                    heading = Some((level, range.start, String::new()));
+                }
+                Event::Text(text) | Event::Code(text) => {
+                    if let Some((_, _, title)) = heading.as_mut() {
+                        title.push_str(&text);
+                    }
+                }
+                Event::End(TagEnd::Heading(_)) => {
+                    let Some((level, start, title)) = heading.take() else {
+                        continue;
+                    };
+                    if level > target {
+                        continue;
+                    }
+                    if let Some((open_title, body_start)) = open.take() {
+                        sections.push((open_title, &self.content[body_start..start]));
+                    }
+                    if level == target {
+                        open = Some((title.trim().to_string(), range.end));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some((title, body_start)) = open {
+            sections.push((title, &self.content[body_start..]));
+        }
+
+        sections
+            .into_iter()
+            .map(|(title, body)| {
+                let content = format!("[[{}]]\n\n{}", self.title, body.trim());
+                let mut note = Note::new(title, content.trim_end().to_string(), self.tags.clone());
+                note.derived_from = Some(self.id);
+                note
+            })
+            .collect()
+    }
+
+    /// Markdown list linking to each of the given notes, e.g. split sections
+    pub fn section_index(sections: &[Note]) -> String {
+        sections
+            .iter()
+            .map(|note| format!("- [[{}]]\n", note.title))
+            .collect()
+    }
+
+    /// Serialize the note deterministically
+    ///
+    /// Object keys are sorted and there is no whitespace between tokens.
+    /// Timestamps use the RFC 3339 UTC form `Note` always serializes with, and
+    /// all numbers are integers, so equal notes always produce identical bytes.
+    pub fn to_canonical_json(&self) -> String {
+        let value = serde_json::to_value(self).expect("notes always serialize");
+        canonicalize_json(value).to_string()
+    }
+
+    /// Serialize the note for version control, one field per line
+    ///
+    /// The metadata is pretty-printed JSON with sorted keys, followed by a
+    /// `---` line and the content verbatim, so a git diff of an edited note
+    /// shows just the changed fields and content lines. Parse it back with
+    /// `from_diffable`.
+    pub fn to_diffable(&self) -> String {
+        let mut value = serde_json::to_value(self).expect("notes always serialize");
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("content");
+        }
+        let header = serde_json::to_string_pretty(&canonicalize_json(value))
+            .expect("notes always serialize");
+        format!("{}{}{}", header, DIFFABLE_SEPARATOR, self.content)
+    }
+
+    /// Parse a note written by `to_diffable`
+    pub fn from_diffable(text: &str) -> Result<Note, ModelError> {
+        let (header, content) = text
+            .split_once(DIFFABLE_SEPARATOR)
+            .ok_or_else(|| ModelError::InvalidNote("Missing content separator".to_string()))?;
+        let mut value: serde_json::Value =
+            serde_json::from_str(header).map_err(|e| ModelError::InvalidNote(e.to_string()))?;
+        value
+            .as_object_mut()
+            .ok_or_else(|| ModelError::InvalidNote("Header is not an object".to_string()))?
+            .insert("content".to_string(), content.into());
+        migrate_note(value)
+    }
+
+    /// The text the search index should hold for this note's body
+    ///
+    /// When `search_keywords` is non-empty only the keywords are indexed, so
+    /// the content never reaches the index in cleartext even though the note
+    /// itself is stored encrypted. Otherwise the full content is indexed.
+    pub fn indexed_content(&self) -> std::borrow::Cow<'_, str> {
+        if self.search_keywords.is_empty() {
+            std::borrow::Cow::Borrowed(&self.content)
+        } else {
+            std::borrow::Cow::Owned(self.search_keywords.join(" "))
+        }
+    }
+
+    /// Copy of the note with every match of `patterns` in its text replaced by
+    /// `replacement`
+    ///
+    /// The title, content, search keywords and attachment file names are all
+    /// redacted. Patterns apply in order, each to the output of the previous
+    /// one. The copy keeps this note's ID, tags, timestamps and version; the
+    /// original is left untouched. See `default_redaction_patterns` for a
+    /// starting set.
+    pub fn redact(&self, patterns: &[Regex], replacement: &str) -> Note {
+        let apply = |text: &str| {
+            patterns.iter().fold(text.to_string(), |text, pattern| {
+                pattern
+                    .replace_all(&text, regex::NoExpand(replacement))
+                    .into_owned()
+            })
+        };
+
+        let mut redacted = self.clone();
+        redacted.title = apply(&self.title);
+        redacted.content = apply(&self.content);
+        for keyword in &mut redacted.search_keywords {
+            *keyword = apply(keyword);
+        }
+        for attachment in &mut redacted.attachments {
+            attachment.filename = apply(&attachment.filename);
+        }
+        redacted
+    }
+
+    /// Number of words in the content, ignoring Markdown syntax
+    pub fn word_count(&self) -> usize {
+        markdown_to_text(&self.content).split_whitespace().count()
+    }
+
+    /// Stable fingerprint of the note's meaningful content
+    ///
+    /// Hex SHA-256 over the trimmed title, the content with line endings
+    /// normalized to `\n`, and the sorted, deduplicated tags. IDs, timestamps and
+    /// versions are excluded, so notes with identical content share a fingerprint
+    /// regardless of when they were edited.
+    pub fn fingerprint(&self) -> String {
+        let mut tags: Vec<&str> = self.tags.iter().map(String::as_str).collect();
+        tags.sort_unstable();
+        tags.dedup();
+
+        let mut hasher = Sha256::new();
+        let mut write_field = |field: &str| {
+            hasher.update((field.len() as u64).to_be_bytes());
+            hasher.update(field.as_bytes());
+        };
+        write_field(self.title.trim());
+        write_field(&self.content.replace("\r\n", "\n"));
+        for tag in tags {
+            write_field(tag);
+        }
+
+        crate::crypto::to_hex(&hasher.finalize())
+    }
+
+    /// Rename a tag (and its descendants) on this note
+    ///
+    /// `from/child` becomes `to/child`. Duplicates produced by the rename are
+    /// removed. Bumps the version and timestamp only if a tag actually changed.
+    pub fn rename_tag(&mut self, from: &str, to: &str) -> bool {
+        self.rename_tag_with(from, to, &TagConfig::default())
+    }
+
+    /// `rename_tag`, comparing tags as `config` says
+    pub fn rename_tag_with(&mut self, from: &str, to: &str, config: &TagConfig) -> bool {
+        let from_levels: Vec<&str> = from.split('/').collect();
+        let mut changed = false;
+        let mut tags: Vec<String> = Vec::with_capacity(self.tags.len());
+
+        for tag in &self.tags {
+            let levels: Vec<&str> = tag.split('/').collect();
+            let is_renamed = levels.len() >= from_levels.len()
+                && levels
+                    .iter()
+                    .zip(&from_levels)
+                    .all(|(level, from)| config.matches(level, from));
+            let renamed = if !is_renamed {
+                tag.clone()
+            } else if levels.len() == from_levels.len() {
+                to.to_string()
+            } else {
+                format!("{}/{}", to, levels[from_levels.len()..].join("/"))
+            };
+
+            if renamed != *tag {
+                changed = true;
+            }
+            if !tags.iter().any(|t| config.matches(t, &renamed)) {
+                tags.push(renamed);
+            }
+        }
+
+        if changed {
+            self.tags = tags;
+            self.touch_timestamp();
+            self.version += 1;
+        }
+        changed
+    }
+
+    /// Whether the note carries `tag`, compared as `config` says
+    pub fn has_tag(&self, tag: &str, config: &TagConfig) -> bool {
+        self.tags.iter().any(|t| config.matches(t, tag))
+    }
+
+    /// Add a tag unless the note already has it
+    ///
+    /// Bumps the version and timestamp only if the tag was added.
+    pub fn add_tag(&mut self, tag: &str) -> bool {
+        self.add_tag_with(tag, &TagConfig::default())
+    }
+
+    /// `add_tag`, comparing tags as `config` says
+    ///
+    /// With `case_insensitive`, adding `Work` to a note tagged `work` is a
+    /// no-op, so the casing first used is the one kept.
+    pub fn add_tag_with(&mut self, tag: &str, config: &TagConfig) -> bool {
+        if self.has_tag(tag, config) {
+            return false;
+        }
+        self.tags.push(tag.to_string());
+        self.touch_timestamp();
+        self.version += 1;
+        true
+    }
+
+    /// Remove a tag (every occurrence) if the note has it
+    ///
+    /// Bumps the version and timestamp only if a tag was removed.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        self.remove_tag_with(tag, &TagConfig::default())
+    }
+
+    /// `remove_tag`, comparing tags as `config` says
+    ///
+    /// With `case_insensitive`, removing `Work` also removes `work`.
+    pub fn remove_tag_with(&mut self, tag: &str, config: &TagConfig) -> bool {
+        let before = self.tags.len();
+        self.tags.retain(|t| !config.matches(t, tag));
+        if self.tags.len() == before {
+            return false;
+        }
+        self.touch_timestamp();
+        self.version += 1;
+        true
+    }
+
+    /// Collapse every tag to at most `max_depth` levels (see `Tag::truncate_depth`)
+    ///
+    /// Tags that become identical are merged. Returns whether any tag changed;
+    /// if so, the version and update time are bumped.
+    pub fn truncate_tag_depth(&mut self, max_depth: usize) -> bool {
+        let mut tags: Vec<String> = Vec::with_capacity(self.tags.len());
+        for tag in &self.tags {
+            let truncated = Tag::truncate_depth(tag, max_depth);
+            if !tags.contains(&truncated) {
+                tags.push(truncated);
+            }
+        }
+
+        if tags == self.tags {
+            return false;
+        }
+        self.tags = tags;
+        self.touch_timestamp();
+        self.version += 1;
+        true
+    }
+
+    /// Tags declared in a leading YAML-style frontmatter block
+    ///
+    /// Recognizes `tags: [a, b]`, `tags: a, b` and a `tags:` key followed by
+    /// `- item` lines. Values are returned as written, minus quotes and `#`.
+    pub fn frontmatter_tags(&self) -> Vec<String> {
+        let Some((frontmatter, _)) = split_frontmatter(&self.content) else {
+            return Vec::new();
+        };
+
+        let mut tags = Vec::new();
+        let mut in_list = false;
+        for line in frontmatter.lines() {
+            if in_list {
+                if let Some(item) = line.trim_start().strip_prefix("- ") {
+                    tags.push(item.to_string());
+                    continue;
+                }
+                in_list = false;
+            }
+            let Some(value) = line.strip_prefix("tags:") else {
+                continue;
+            };
+            let value = value.trim();
+            if value.is_empty() {
+                in_list = true;
+            } else {
+                let value = value.trim_start_matches('[').trim_end_matches(']');
+                tags.extend(value.split(',').map(str::to_string));
+            }
+        }
+
+        tags.into_iter()
+            .map(|tag| {
+                tag.trim()
+                    .trim_matches(|c| c == '"' || c == '\'')
+                    .trim_start_matches('#')
+                    .to_string()
+            })
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    }
+
+    /// Inline `#hashtags` in the body, in order of first appearance
+    ///
+    /// A hashtag starts with a letter and may contain `/` for nesting. The
+    /// frontmatter, fenced code blocks and headings (`# Title`) are ignored.
+    pub fn inline_hashtags(&self) -> Vec<String> {
+        static HASHTAG: OnceLock<Regex> = OnceLock::new();
+        let hashtag = HASHTAG.get_or_init(|| {
+            Regex::new(r"(?:^|[\s(])#(\p{L}[\p{L}\p{N}_/-]*)").expect("hashtag pattern is valid")
+        });
+        let body = split_frontmatter(&self.content)
+            .map(|(_, body)| body)
+            .unwrap_or(&self.content);
+
+        let mut tags: Vec<String> = Vec::new();
+        let mut in_fence = false;
+        for line in body.lines() {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+                continue;
+            }
+            if in_fence {
+                continue;
+            }
+            for captures in hashtag.captures_iter(line) {
+                let tag = captures[1].trim_end_matches('/').to_string();
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+        }
+        tags
+    }
+
+    /// Reconcile `tags` with the tags declared in frontmatter and inline hashtags
+    ///
+    /// The result is the union of the existing tags (kept first, so the primary
+    /// tag doesn't change), then frontmatter tags, then hashtags. Every tag is
+    /// normalized with `Tag::parse_flexible` on `/` only, and exact duplicates
+    /// are dropped; use `consolidate_tags_with` to also merge case variants.
+    /// Returns whether the tags changed; if so, the version and update time
+    /// are bumped.
+    pub fn consolidate_tags(&mut self) -> bool {
+        self.consolidate_tags_with(&TagConfig::default())
+    }
+
+    /// `consolidate_tags`, comparing tags as `config` says
+    ///
+    /// With `case_insensitive`, `Ideas` and `ideas` merge into the casing seen
+    /// first.
+    pub fn consolidate_tags_with(&mut self, config: &TagConfig) -> bool {
+        let mut tags: Vec<String> = Vec::with_capacity(self.tags.len());
+        let declared = self
+            .frontmatter_tags()
+            .into_iter()
+            .chain(self.inline_hashtags());
+        for tag in self.tags.iter().cloned().chain(declared) {
+            let normalized = Tag::parse_flexible(&tag, &[]).path;
+            if !normalized.is_empty() && !tags.iter().any(|t| config.matches(t, &normalized)) {
+                tags.push(normalized);
+            }
+        }
+
+        if tags == self.tags {
+            return false;
+        }
+        self.tags = tags;
+        self.touch_timestamp();
+        self.version += 1;
+        true
+    }
+
+    /// Attach a file to this note, unless an attachment with its ID exists
+    ///
+    /// The attachment's `note_id` is set to this note. Bumps the version and
+    /// timestamp only if it was added.
+    pub fn add_attachment(&mut self, mut attachment: Attachment) -> bool {
+        if self.attachments.iter().any(|a| a.id == attachment.id) {
+            return false;
+        }
+        attachment.note_id = self.id;
+        self.attachments.push(attachment);
+        self.touch_timestamp();
+        self.version += 1;
+        true
+    }
+
+    /// Detach a file, returning it if the note had it
+    ///
+    /// Links to it in the content are left alone and become dangling.
+    pub fn remove_attachment(&mut self, id: &Uuid) -> Option<Attachment> {
+        let index = self.attachments.iter().position(|a| a.id == *id)?;
+        let removed = self.attachments.remove(index);
+        self.touch_timestamp();
         self.version += 1;
+        Some(removed)
+    }
+
+    /// IDs referenced by `attachment:<id>` links in the content, in order
+    pub fn attachment_refs(&self) -> Vec<Uuid> {
+        attachment_link_regex()
+            .captures_iter(&self.content)
+            .filter_map(|captures| Uuid::parse_str(&captures[1]).ok())
+            .collect()
+    }
+
+    /// Rewrite `attachment:<id>` link targets using `target`
+    ///
+    /// `target` maps each of this note's attachments to the URL or path to
+    /// link to, e.g. its location in an export. Links to IDs the note has no
+    /// attachment for are left unchanged.
+    pub fn resolve_attachment_links<F>(&self, target: F) -> String
+    where
+        F: Fn(&Attachment) -> String,
+    {
+        attachment_link_regex()
+            .replace_all(&self.content, |captures: &regex::Captures| {
+                let attachment = Uuid::parse_str(&captures[1])
+                    .ok()
+                    .and_then(|id| self.attachments.iter().find(|a| a.id == id));
+                match attachment {
+                    Some(attachment) => format!("]({})", target(attachment)),
+                    None => captures[0].to_string(),
+                }
+            })
+            .into_owned()
+    }
+
+    /// The tag grouping features file this note under
+    ///
+    /// The rule is simply the first entry of `tags`: users order tags
+    /// deliberately, so the first is taken as the most important. Blank
+    /// entries are skipped. Folder exports and grouped views use this.
+    pub fn primary_tag(&self) -> Option<&str> {
+        primary_tag(&self.tags)
+    }
+
+    /// `primary_tag`, or `default` for untagged notes
+    pub fn primary_tag_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.primary_tag().unwrap_or(default)
+    }
+
+    /// Pin the note to the top of lists
+    pub fn pin(&mut self) {
+        self.pinned = true;
+        self.touch_timestamp();
+    }
+
+    /// Unpin the note
+    pub fn unpin(&mut self) {
+        self.pinned = false;
+        self.touch_timestamp();
+    }
+
+    /// Find every occurrence of `query` in the content
+    ///
+    /// Returns non-overlapping `(start, end)` byte ranges, always on character
+    /// boundaries so they can be used to slice the content directly. Case-insensitive
+    /// matching compares character by character using Unicode lowercase mappings.
+    pub fn find_matches(&self, query: &str, case_sensitive: bool) -> Vec<(usize, usize)> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        if case_sensitive {
+            return self
+                .content
+                .match_indices(query)
+                .map(|(start, m)| (start, start + m.len()))
+                .collect();
+        }
+
+        let query_chars: Vec<char> = query.chars().collect();
+        let mut matches = Vec::new();
+        let mut next_allowed = 0;
+
+        for (start, _) in self.content.char_indices() {
+            if start < next_allowed {
+                continue;
+            }
+
+            let mut candidate = self.content[start..].char_indices();
+            let mut end = start;
+            let matched = query_chars.iter().all(|q| match candidate.next() {
+                Some((offset, c)) if c.to_lowercase().eq(q.to_lowercase()) => {
+                    end = start + offset + c.len_utf8();
+                    true
+                }
+                _ => false,
+            });
+
+            if matched {
+                matches.push((start, end));
+                next_allowed = end;
+            }
+        }
+
+        matches
+    }
+
+    /// Render the note as a single self-contained HTML document
+    ///
+    /// Markdown is rendered with `pulldown-cmark`. Raw HTML embedded in the note
+    /// is escaped rather than passed through, and link and image targets are
+    /// emptied unless `is_safe_link` allows them, so shared files can't carry
+    /// scripts.
+    pub fn to_html(&self) -> String {
+        let parser = Parser::new_ext(&self.content, Options::all()).map(|event| match event {
+            Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+            Event::Start(MdTag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => Event::Start(MdTag::Link {
+                link_type,
+                dest_url: safe_link(dest_url),
+                title,
+                id,
+            }),
+            Event::Start(MdTag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => Event::Start(MdTag::Image {
+                link_type,
+                dest_url: safe_link(dest_url),
+                title,
+                id,
+            }),
+            other => other,
+        });
+        let mut body = String::new();
+        html::push_html(&mut body, parser);
+
+        let title = escape_html(&self.title);
+        let tags = escape_html(&self.tags.join(", "));
+        let updated = self.updated_at.format("%Y-%m-%d %H:%M UTC");
+
+        format!(
+            "<!DOCTYPE html>\n\
+             <html>\n\
+             <head>\n\
+             <meta charset=\"utf-8\">\n\
+             <title>{title}</title>\n\
+             <style>\n\
+             body {{ font-family: sans-serif; max-width: 48em; margin: 2em auto; padding: 0 1em; line-height: 1.5; }}\n\
+             pre {{ background: #f4f4f4; padding: 1em; overflow-x: auto; }}\n\
+             .meta {{ color: #666; font-size: 0.9em; }}\n\
+             </style>\n\
+             </head>\n\
+             <body>\n\
+             <article>\n\
+             <h1>{title}</h1>\n\
+             <p class=\"meta\">Updated {updated} &middot; {tags}</p>\n\
+             {body}\
+             </article>\n\
+             </body>\n\
+             </html>\n"
+        )
     }
 }
 
-/// A tag with hierarchical structure
+/// URL schemes `Note::to_html` keeps in links and images
+const SAFE_LINK_SCHEMES: &[&str] = &["http", "https", "mailto", "note", "attachment"];
+
+/// Whether a link target is relative or uses a scheme in `SAFE_LINK_SCHEMES`
+///
+/// Browsers ignore whitespace and control characters inside a scheme, so
+/// those are dropped before it is compared.
+fn is_safe_link(dest: &str) -> bool {
+    let cleaned: String = dest
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    match cleaned.find([':', '/', '?', '#']) {
+        Some(i) if cleaned[i..].starts_with(':') => {
+            let scheme = cleaned[..i].to_ascii_lowercase();
+            SAFE_LINK_SCHEMES.contains(&scheme.as_str())
+        }
+        _ => true,
+    }
+}
+
+/// Keep a link target if `is_safe_link` allows it, otherwise empty it
+fn safe_link(dest: pulldown_cmark::CowStr<'_>) -> pulldown_cmark::CowStr<'_> {
+    if is_safe_link(&dest) {
+        dest
+    } else {
+        pulldown_cmark::CowStr::Borrowed("")
+    }
+}
+
+/// Escape text for inclusion in HTML
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Matches the `](attachment:<id>)` tail of a Markdown link or image
+fn attachment_link_regex() -> &'static Regex {
+    static ATTACHMENT_LINK: OnceLock<Regex> = OnceLock::new();
+    ATTACHMENT_LINK.get_or_init(|| {
+        Regex::new(r"\]\(attachment:([0-9A-Fa-f-]{36})\)").expect("attachment pattern is valid")
+    })
+}
+
+/// Split content into its leading `---` frontmatter block and the body after it
+///
+/// Returns `None` when the content doesn't open with a closed frontmatter block.
+fn split_frontmatter(content: &str) -> Option<(&str, &str)> {
+    let rest = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))?;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Strip Markdown syntax, keeping only the prose
+///
+/// Link and image targets and HTML tags are dropped; code is kept as text.
+/// Block boundaries become newlines, so words from adjacent blocks stay apart.
+pub fn markdown_to_text(markdown: &str) -> String {
+    let mut text = String::with_capacity(markdown.len());
+    for event in Parser::new_ext(markdown, Options::all()) {
+        match event {
+            Event::Text(t) | Event::Code(t) | Event::InlineMath(t) | Event::DisplayMath(t) => {
+                text.push_str(&t)
+            }
+            Event::SoftBreak | Event::HardBreak | Event::End(TagEnd::TableCell) => text.push(' '),
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::BlockQuote(_)
+                | TagEnd::CodeBlock
+                | TagEnd::Item
+                | TagEnd::TableRow
+                | TagEnd::TableHead,
+            )
+            | Event::Rule => text.push('\n'),
+            _ => {}
+        }
+    }
+    text.trim_end().to_string()
+}
+
+/// Shorten `s` to at most `max_chars` characters, appending `…` if anything was cut
+///
+/// Counts and cuts on `char` boundaries, so multi-byte text is never split
+/// mid-codepoint. The ellipsis is not counted towards `max_chars`.
+pub fn truncate_chars(s: &str, max_chars: usize) -> String {
+    match s.char_indices().nth(max_chars) {
+        Some((byte_index, _)) => format!("{}…", &s[..byte_index]),
+        None => s.to_string(),
+    }
+}
+
+/// Rename a tag across a collection of notes, returning how many changed
+///
+/// Renaming onto an existing tag merges the two.
+pub fn rename_tag(notes: &mut [Note], from: &str, to: &str) -> usize {
+    rename_tag_with(notes, from, to, &TagConfig::default())
+}
+
+/// `rename_tag`, comparing tags as `config` says
+pub fn rename_tag_with(notes: &mut [Note], from: &str, to: &str, config: &TagConfig) -> usize {
+    notes
+        .iter_mut()
+        .map(|n| n.rename_tag_with(from, to, config))
+        .filter(|changed| *changed)
+        .count()
+}
+
+/// Notes carrying `tag` exactly (not its descendants), compared as `config` says
+pub fn filter_by_tag<'a>(notes: &'a [Note], tag: &str, config: &TagConfig) -> Vec<&'a Note> {
+    notes
+        .iter()
+        .filter(|note| note.has_tag(tag, config))
+        .collect()
+}
+
+/// One level of the tag hierarchy built by `build_tag_tree`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TagTreeNode {
+    /// Full path, in the casing first seen
+    pub path: String,
+    /// Last level of `path`
+    pub name: String,
+    /// Notes carrying this tag or one of its descendants
+    pub count: usize,
+    /// Child tags, sorted by path
+    pub children: Vec<TagTreeNode>,
+}
+
+/// Arrange every tag used by `notes` into a hierarchy of `TagTreeNode`s
+///
+/// Ancestors are included even if no note carries them directly, so
+/// `work/q3` alone yields a `work` node with a `work/q3` child. With
+/// `config.case_insensitive`, `Work` and `work` share one node, displayed in
+/// the casing seen first.
+pub fn build_tag_tree(notes: &[Note], config: &TagConfig) -> Vec<TagTreeNode> {
+    // Keyed by comparison form: (display path, notes under it)
+    let mut paths: BTreeMap<String, (String, HashSet<Uuid>)> = BTreeMap::new();
+    for note in notes {
+        for tag in &note.tags {
+            let levels: Vec<&str> = tag.split('/').filter(|l| !l.is_empty()).collect();
+            for depth in 1..=levels.len() {
+                let display = levels[..depth].join("/");
+                let entry = paths
+                    .entry(config.key(&display).into_owned())
+                    .or_insert_with(|| (display, HashSet::new()));
+                entry.1.insert(note.id);
+            }
+        }
+    }
+
+    // Child keys of each parent key (`None` for the roots), in path order
+    let mut children: HashMap<Option<&str>, Vec<&str>> = HashMap::new();
+    for key in paths.keys() {
+        let parent = key.rsplit_once('/').map(|(parent, _)| parent);
+        children.entry(parent).or_default().push(key);
+    }
+
+    fn build(
+        paths: &BTreeMap<String, (String, HashSet<Uuid>)>,
+        children: &HashMap<Option<&str>, Vec<&str>>,
+        parent: Option<&str>,
+    ) -> Vec<TagTreeNode> {
+        children
+            .get(&parent)
+            .into_iter()
+            .flatten()
+            .map(|key| {
+                let (display, ids) = &paths[*key];
+                TagTreeNode {
+                    path: display.clone(),
+                    name: display.rsplit('/').next().unwrap_or_default().to_string(),
+                    count: ids.len(),
+                    children: build(paths, children, Some(key)),
+                }
+            })
+            .collect()
+    }
+
+    build(&paths, &children, None)
+}
+
+/// `Note::primary_tag` for a bare tag list, such as a search hit's
+pub fn primary_tag(tags: &[String]) -> Option<&str> {
+    tags.iter()
+        .map(String::as_str)
+        .find(|tag| !tag.trim().is_empty())
+}
+
+/// Patterns for common secrets, for use with `Note::redact`
+///
+/// Matches email addresses, AWS access key IDs, GitHub tokens, and keys
+/// prefixed `sk`, `pk`, `api`, `key`, `token` or `secret` (any case) plus `-`
+/// or `_`, such as `sk-…` or `API_KEY_…`, followed by 16 or more characters.
+/// This is a best-effort net, not a guarantee that nothing sensitive remains.
+pub fn default_redaction_patterns() -> Vec<Regex> {
+    [
+        r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+        r"\bAKIA[0-9A-Z]{16}\b",
+        r"\bgh[pousr]_[A-Za-z0-9]{36,}\b",
+        r"(?i)\b(?:sk|pk|api|key|token|secret)[-_][A-Za-z0-9_-]{16,}",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).expect("built-in patterns are valid"))
+    .collect()
+}
+
+/// Collapse every note's tags to at most `max_depth` levels, returning how many changed
+pub fn flatten_deep_tags(notes: &mut [Note], max_depth: usize) -> usize {
+    notes
+        .iter_mut()
+        .map(|n| n.truncate_tag_depth(max_depth))
+        .filter(|changed| *changed)
+        .count()
+}
+
+/// Add a tag to the notes with the given IDs, returning how many changed
+pub fn add_tag(notes: &mut [Note], ids: &[Uuid], tag: &str) -> usize {
+    notes
+        .iter_mut()
+        .filter(|n| ids.contains(&n.id))
+        .map(|n| n.add_tag(tag))
+        .filter(|changed| *changed)
+        .count()
+}
+
+/// `add_tag`, comparing tags as `config` says
+pub fn add_tag_with(notes: &mut [Note], ids: &[Uuid], tag: &str, config: &TagConfig) -> usize {
+    notes
+        .iter_mut()
+        .filter(|n| ids.contains(&n.id))
+        .map(|n| n.add_tag_with(tag, config))
+        .filter(|changed| *changed)
+        .count()
+}
+
+/// Remove a tag from the notes with the given IDs, returning how many changed
+pub fn remove_tag(notes: &mut [Note], ids: &[Uuid], tag: &str) -> usize {
+    remove_tag_with(notes, ids, tag, &TagConfig::default())
+}
+
+/// `remove_tag`, comparing tags as `config` says
+pub fn remove_tag_with(notes: &mut [Note], ids: &[Uuid], tag: &str, config: &TagConfig) -> usize {
+    notes
+        .iter_mut()
+        .filter(|n| ids.contains(&n.id))
+        .map(|n| n.remove_tag_with(tag, config))
+        .filter(|changed| *changed)
+        .count()
+}
+
+/// Tags added and removed going from `old` to `new`
+///
+/// Order within each list follows the note it came from; reordering tags
+/// without changing the set yields no diff.
+pub fn tag_diff(old: &Note, new: &Note) -> (Vec<String>, Vec<String>) {
+    tag_diff_with(old, new, &TagConfig::default())
+}
+
+/// `tag_diff`, comparing tags as `config` says
+///
+/// With `case_insensitive`, recasing a tag (`work` to `Work`) yields no diff.
+pub fn tag_diff_with(old: &Note, new: &Note, config: &TagConfig) -> (Vec<String>, Vec<String>) {
+    let old_tags: HashSet<std::borrow::Cow<str>> = old.tags.iter().map(|t| config.key(t)).collect();
+    let new_tags: HashSet<std::borrow::Cow<str>> = new.tags.iter().map(|t| config.key(t)).collect();
+
+    let mut added: Vec<String> = Vec::new();
+    for tag in &new.tags {
+        if !old_tags.contains(&config.key(tag)) && !added.iter().any(|t| config.matches(t, tag)) {
+            added.push(tag.clone());
+        }
+    }
+    let mut removed: Vec<String> = Vec::new();
+    for tag in &old.tags {
+        if !new_tags.contains(&config.key(tag)) && !removed.iter().any(|t| config.matches(t, tag)) {
+            removed.push(tag.clone());
+        }
+    }
+    (added, removed)
+}
+
+/// Orderings for note lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Most recently updated first
+    Updated,
+    /// Most recently created first
+    Created,
+    /// Alphabetical by title
+    Title,
+    /// Pinned notes first, each group most recently updated first
+    PinnedThenUpdated,
+}
+
+/// Sort notes in place by the given key
+pub fn sort_notes(notes: &mut [Note], by: SortKey) {
+    match by {
+        SortKey::Updated => notes.sort_by_key(|n| std::cmp::Reverse(n.updated_at)),
+        SortKey::Created => notes.sort_by_key(|n| std::cmp::Reverse(n.created_at)),
+        SortKey::Title => notes.sort_by(|a, b| a.title.cmp(&b.title)),
+        SortKey::PinnedThenUpdated => notes.sort_by(|a, b| {
+            b.pinned
+                .cmp(&a.pinned)
+                .then_with(|| b.updated_at.cmp(&a.updated_at))
+        }),
+    }
+}
+
+/// Notes whose version lies in `min..=max` (no upper bound when `max` is `None`)
+pub fn filter_by_version(notes: &[Note], min: u64, max: Option<u64>) -> Vec<&Note> {
+    notes
+        .iter()
+        .filter(|n| n.version >= min && max.is_none_or(|max| n.version <= max))
+        .collect()
+}
+
+/// A reusable skeleton for creating notes (e.g. daily journal, meeting notes)
+///
+/// The title pattern and content may contain `{{var}}` placeholders. Besides
+/// caller-supplied variables, `{{date}}` (today as `YYYY-MM-DD`) and `{{uuid}}`
+/// (the new note's ID) are always available.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteTemplate {
+    /// Name of the template
+    pub name: String,
+    /// Title with placeholders (e.g., "Journal {{date}}")
+    pub title_pattern: String,
+    /// Markdown content with placeholders
+    pub content: String,
+    /// Tags applied to notes created from this template
+    pub tags: Vec<String>,
+}
+
+impl NoteTemplate {
+    /// Create a new template
+    pub fn new(name: String, title_pattern: String, content: String, tags: Vec<String>) -> Self {
+        Self {
+            name,
+            title_pattern,
+            content,
+            tags,
+        }
+    }
+
+    /// Create a note from this template, substituting placeholders
+    ///
+    /// Caller-supplied variables take precedence over the built-in ones.
+    pub fn instantiate(&self, vars: &HashMap<String, String>) -> Note {
+        let mut note = Note::new(String::new(), String::new(), self.tags.clone());
+
+        let mut all_vars = HashMap::new();
+        all_vars.insert(
+            "date".to_string(),
+            note.created_at.format("%Y-%m-%d").to_string(),
+        );
+        all_vars.insert("uuid".to_string(), note.id.to_string());
+        for (key, value) in vars {
+            all_vars.insert(key.clone(), value.clone());
+        }
+
+        note.title = substitute_placeholders(&self.title_pattern, &all_vars);
+        note.content = substitute_placeholders(&self.content, &all_vars);
+        note
+    }
+}
+
+/// Replace `{{var}}` placeholders; unknown placeholders are left untouched
+fn substitute_placeholders(text: &str, vars: &HashMap<String, String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+
+        match after.find("}}") {
+            Some(end) => {
+                let name = after[..end].trim();
+                match vars.get(name) {
+                    Some(value) => result.push_str(value),
+                    None => result.push_str(&rest[start..start + 2 + end + 2]),
+                }
+                rest = &after[end + 2..];
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// How tags are compared by the `*_with` tag helpers
+///
+/// The default compares tags exactly. Set `case_insensitive` to treat `Work`
+/// and `work` as one tag; tags are still stored in the casing first used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TagConfig {
+    pub case_insensitive: bool,
+}
+
+impl TagConfig {
+    /// The form of `tag` that equal tags share
+    pub fn key<'a>(&self, tag: &'a str) -> std::borrow::Cow<'a, str> {
+        if self.case_insensitive {
+            std::borrow::Cow::Owned(tag.to_lowercase())
+        } else {
+            std::borrow::Cow::Borrowed(tag)
+        }
+    }
+
+    /// Whether two tags count as the same tag
+    pub fn matches(&self, a: &str, b: &str) -> bool {
+        self.key(a) == self.key(b)
+    }
+}
+
+/// Separators `Tag::parse_flexible` recognizes by default: `/`, `.` and `>`
+pub const DEFAULT_TAG_SEPARATORS: &[char] = &['/', '.', '>'];
+
+/// A tag with hierarchical structure
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Tag {
     /// Full path of the tag (e.g., "work/project/urgent")
     pub path: String,
@@ -70,26 +1252,299 @@ impl Tag {
             None
         };
 
-        Self {
-            path: path.to_string(),
-            name,
-            parent,
+        Self {
+            path: path.to_string(),
+            name,
+            parent,
+        }
+    }
+
+    /// Parse a tag typed with any of `separators`, normalizing them to `/`
+    ///
+    /// `from_path` only splits on `/`; this is the opt-in alternative for user
+    /// input such as `work.project` or `work > project`. Whitespace around each
+    /// level is trimmed and empty levels are dropped, so all of those end up in
+    /// the same hierarchy as `work/project`. Pass `DEFAULT_TAG_SEPARATORS` for
+    /// the standard set.
+    pub fn parse_flexible(input: &str, separators: &[char]) -> Self {
+        let path = input
+            .split(|c| c == '/' || separators.contains(&c))
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+        Self::from_path(&path)
+    }
+
+    /// Collapse a tag path to at most `max_depth` levels
+    ///
+    /// Deeper levels are dropped, so `a/b/c/d/e` at depth 3 becomes `a/b/c`:
+    /// the result is always one of the original's ancestors (or the path
+    /// itself), keeping the hierarchy consistent. A depth of 0 is treated as 1.
+    pub fn truncate_depth(path: &str, max_depth: usize) -> String {
+        path.split('/')
+            .take(max_depth.max(1))
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Get all ancestor paths
+    pub fn ancestors(&self) -> Vec<String> {
+        let mut ancestors = Vec::new();
+        let parts: Vec<&str> = self.path.split('/').collect();
+
+        for i in 1..parts.len() {
+            ancestors.push(parts[..i].join("/"));
+        }
+
+        ancestors
+    }
+}
+
+/// Upgrade serialized note JSON of any known schema version to a current `Note`
+///
+/// The embedded `schema_version` (0 when absent) selects where to start; each
+/// step upgrades one version, so backward-compatibility logic lives here rather
+/// than being scattered across serde defaults. Migration steps:
+/// - 0 → 1: `pinned` is introduced, defaulting to `false`
+/// - 1 → 2: `derived_from` is introduced, defaulting to none
+/// - 2 → 3: `search_keywords` is introduced, defaulting to empty
+/// - 3 → 4: `attachments` is introduced, defaulting to empty
+pub fn migrate_note(mut value: serde_json::Value) -> Result<Note, ModelError> {
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| ModelError::InvalidNote("expected a JSON object".to_string()))?;
+
+    let mut version = match object.get("schema_version") {
+        None => 0,
+        Some(v) => v.as_u64().ok_or_else(|| {
+            ModelError::InvalidNote("schema_version must be a number".to_string())
+        })?,
+    };
+    if version > u64::from(NOTE_SCHEMA_VERSION) {
+        return Err(ModelError::UnsupportedSchemaVersion(version));
+    }
+
+    while version < u64::from(NOTE_SCHEMA_VERSION) {
+        match version {
+            0 => {
+                object
+                    .entry("pinned")
+                    .or_insert(serde_json::Value::Bool(false));
+            }
+            1 => {
+                object
+                    .entry("derived_from")
+                    .or_insert(serde_json::Value::Null);
+            }
+            2 => {
+                object
+                    .entry("search_keywords")
+                    .or_insert(serde_json::Value::Array(Vec::new()));
+            }
+            3 => {
+                object
+                    .entry("attachments")
+                    .or_insert(serde_json::Value::Array(Vec::new()));
+            }
+            _ => unreachable!("every version below the current one has a migration step"),
+        }
+        version += 1;
+    }
+    object.insert("schema_version".to_string(), version.into());
+
+    serde_json::from_value(value).map_err(|e| ModelError::InvalidNote(e.to_string()))
+}
+
+/// Find existing tags within `max_distance` edits of `query`
+///
+/// Uses Levenshtein distance over characters, so the UI can offer "did you mean"
+/// suggestions for mistyped tags. Results are ordered by distance, then name.
+pub fn fuzzy_match_tags(notes: &[Note], query: &str, max_distance: usize) -> Vec<String> {
+    let mut matches: Vec<(usize, &str)> = Vec::new();
+
+    for tag in notes.iter().flat_map(|n| n.tags.iter()) {
+        if matches.iter().any(|(_, t)| *t == tag) {
+            continue;
+        }
+        let distance = levenshtein(query, tag);
+        if distance <= max_distance {
+            matches.push((distance, tag));
+        }
+    }
+
+    matches.sort();
+    matches.into_iter().map(|(_, t)| t.to_string()).collect()
+}
+
+/// Edit distance between two strings, counted in characters
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+    let mut current = vec![0; b_chars.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b_chars.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b_chars.len()]
+}
+
+/// A structural issue reported by `lint_note`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LintWarning {
+    /// The title is empty or whitespace
+    EmptyTitle,
+    /// The content is empty or whitespace
+    EmptyContent,
+    /// The same tag appears more than once
+    DuplicateTag(String),
+    /// A tag path is empty, has empty segments, or contains whitespace
+    MalformedTag(String),
+    /// A `[[link]]` names a note title that doesn't exist
+    BrokenLink(String),
+}
+
+/// Check a note for structural issues
+///
+/// Links can't be verified without knowing the other notes, so `BrokenLink` is
+/// only reported by `lint_note_with_titles` and `lint_notes`.
+pub fn lint_note(note: &Note) -> Vec<LintWarning> {
+    lint_note_inner(note, None)
+}
+
+/// Check a note for structural issues, resolving `[[links]]` against `titles`
+pub fn lint_note_with_titles(note: &Note, titles: &HashSet<&str>) -> Vec<LintWarning> {
+    lint_note_inner(note, Some(titles))
+}
+
+/// Lint every note, resolving links against the collection's own titles
+///
+/// Only notes with at least one warning are returned.
+pub fn lint_notes(notes: &[Note]) -> Vec<(Uuid, Vec<LintWarning>)> {
+    let titles: HashSet<&str> = notes.iter().map(|n| n.title.trim()).collect();
+    notes
+        .iter()
+        .filter_map(|note| {
+            let warnings = lint_note_with_titles(note, &titles);
+            (!warnings.is_empty()).then_some((note.id, warnings))
+        })
+        .collect()
+}
+
+fn lint_note_inner(note: &Note, titles: Option<&HashSet<&str>>) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+
+    if note.title.trim().is_empty() {
+        warnings.push(LintWarning::EmptyTitle);
+    }
+    if note.content.trim().is_empty() {
+        warnings.push(LintWarning::EmptyContent);
+    }
+
+    let mut seen = HashSet::new();
+    for tag in &note.tags {
+        if !seen.insert(tag.as_str()) {
+            let warning = LintWarning::DuplicateTag(tag.clone());
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+        if tag.split('/').any(|segment| segment.is_empty()) || tag.contains(char::is_whitespace) {
+            warnings.push(LintWarning::MalformedTag(tag.clone()));
         }
     }
 
-    /// Get all ancestor paths
-    pub fn ancestors(&self) -> Vec<String> {
-        let mut ancestors = Vec::new();
-        let parts: Vec<&str> = self.path.split('/').collect();
+    if let Some(titles) = titles {
+        for target in wiki_links(&note.content) {
+            if !titles.contains(target) {
+                warnings.push(LintWarning::BrokenLink(target.to_string()));
+            }
+        }
+    }
 
-        for i in 1..parts.len() {
-            ancestors.push(parts[..i].join("/"));
+    warnings
+}
+
+/// Notes that link to `target`, in their original order
+///
+/// A note links to the target with `[[Title]]` (matched against the trimmed
+/// title, as `lint_notes` does) or with `[text](note:<id>)`. The target itself
+/// is never returned, even if it links to itself.
+pub fn find_backlinks<'a>(notes: &'a [Note], target: &Note) -> Vec<&'a Note> {
+    let title = target.title.trim();
+    notes
+        .iter()
+        .filter(|note| note.id != target.id)
+        .filter(|note| {
+            (!title.is_empty() && wiki_links(&note.content).contains(&title))
+                || note_id_links(&note.content).contains(&target.id)
+        })
+        .collect()
+}
+
+/// Note IDs referenced by `[text](note:<id>)` links
+fn note_id_links(content: &str) -> Vec<Uuid> {
+    static NOTE_LINK: OnceLock<Regex> = OnceLock::new();
+    NOTE_LINK
+        .get_or_init(|| {
+            Regex::new(r"\]\(note:([0-9A-Fa-f-]{36})\)").expect("note link pattern is valid")
+        })
+        .captures_iter(content)
+        .filter_map(|captures| Uuid::parse_str(&captures[1]).ok())
+        .collect()
+}
+
+/// Targets of `[[Target]]` and `[[Target|label]]` links, trimmed
+pub(crate) fn wiki_links(content: &str) -> Vec<&str> {
+    let mut links = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else {
+            break;
+        };
+        let inner = &after[..end];
+        let target = inner.split('|').next().unwrap_or("").trim();
+        if !target.is_empty() {
+            links.push(target);
         }
+        rest = &after[end + 2..];
+    }
+    links
+}
 
-        ancestors
+/// Rebuild a JSON value with every object's keys in sorted order
+///
+/// Done explicitly rather than relying on `serde_json::Map` ordering, which
+/// becomes insertion order if any crate in the build enables `preserve_order`.
+fn canonicalize_json(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, canonicalize_json(value)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(canonicalize_json).collect())
+        }
+        other => other,
     }
 }
 
+/// Decoded length in bytes of salts produced by `EncryptionManager::generate_salt`
+pub const VAULT_SALT_LEN: usize = 16;
+
 /// A vault containing notes
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vault {
@@ -105,6 +1560,9 @@ pub struct Vault {
     pub updated_at: DateTime<Utc>,
     /// Encryption salt
     pub salt: String,
+    /// Password verifier from `EncryptionManager::auth_tag`, if recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_tag: Option<String>,
 }
 
 impl Vault {
@@ -118,8 +1576,31 @@ impl Vault {
             created_at: now,
             updated_at: now,
             salt,
+            auth_tag: None,
         }
     }
+
+    /// Create a vault for `encryption`'s salt, recording its password verifier
+    pub fn new_encrypted(
+        name: String,
+        description: String,
+        encryption: &crate::crypto::EncryptionManager,
+    ) -> Self {
+        let mut vault = Self::new(name, description, encryption.salt().to_string());
+        vault.auth_tag = Some(encryption.auth_tag());
+        vault
+    }
+
+    /// Check the salt is unpadded base64 of `VAULT_SALT_LEN` bytes
+    ///
+    /// A salt altered in cleartext metadata would silently derive the wrong
+    /// key, so this is checked before an encrypted import. Legacy salts in other
+    /// formats fail this check; convert them with `migrate_vault_salt` first.
+    pub fn verify_salt(&self) -> bool {
+        general_purpose::STANDARD_NO_PAD
+            .decode(&self.salt)
+            .is_ok_and(|bytes| bytes.len() == VAULT_SALT_LEN)
+    }
 }
 
 /// Metadata for vault export/import
@@ -131,6 +1612,19 @@ pub struct VaultMetadata {
     pub version: String,
 }
 
+/// What counts as a conflict when the same note exists on both sides
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictSensitivity {
+    /// Only a differing `version`
+    VersionOnly,
+    /// A differing `version` or `updated_at`
+    #[default]
+    VersionAndTimestamp,
+    /// A differing `Note::fingerprint`: title, content or tags actually changed.
+    /// Ignores version and timestamp drift, e.g. from clock skew or a re-save.
+    ContentAware,
+}
+
 /// Conflict resolution strategy
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConflictResolution {
@@ -183,4 +1677,855 @@ mod tests {
         assert_eq!(ancestors[0], "work");
         assert_eq!(ancestors[1], "work/project");
     }
+
+    #[test]
+    fn test_markdown_to_text() {
+        let text = markdown_to_text(
+            "# Heading\n\nSee [the docs](https://example.com/guide) and `code`.\n\n<b>raw</b>",
+        );
+        assert_eq!(text, "Heading\nSee the docs and code.\nraw");
+    }
+
+    #[test]
+    fn test_note_from_parts() {
+        let id = Uuid::new_v4();
+        let created = Utc::now() - chrono::Duration::days(1);
+        let updated = Utc::now();
+        let build = |created, updated, version, tags: &[&str]| {
+            Note::from_parts(
+                id,
+                "Title".to_string(),
+                "Content".to_string(),
+                tags.iter().map(|t| t.to_string()).collect(),
+                created,
+                updated,
+                version,
+            )
+        };
+
+        let note = build(created, updated, 3, &["a", "b"]).unwrap();
+        assert_eq!(note.id, id);
+        assert_eq!(note.version, 3);
+        assert_eq!(note.created_at, created);
+        assert_eq!(note.schema_version, NOTE_SCHEMA_VERSION);
+
+        assert!(matches!(
+            build(updated, created, 1, &[]),
+            Err(ModelError::UpdatedBeforeCreated)
+        ));
+        assert!(matches!(
+            build(created, updated, 0, &[]),
+            Err(ModelError::InvalidVersion(0))
+        ));
+        assert!(matches!(
+            build(created, updated, 1, &["a", "b", "a"]),
+            Err(ModelError::DuplicateTag(tag)) if tag == "a"
+        ));
+    }
+
+    #[test]
+    fn test_filter_by_version() {
+        let notes: Vec<Note> = (1..=5)
+            .map(|version| {
+                let mut note = Note::new(format!("v{}", version), String::new(), vec![]);
+                note.version = version;
+                note
+            })
+            .collect();
+        let versions = |found: Vec<&Note>| found.iter().map(|n| n.version).collect::<Vec<_>>();
+
+        assert_eq!(
+            versions(filter_by_version(&notes, 2, Some(4))),
+            vec![2, 3, 4]
+        );
+        assert_eq!(versions(filter_by_version(&notes, 3, Some(3))), vec![3]);
+        assert_eq!(versions(filter_by_version(&notes, 4, None)), vec![4, 5]);
+        assert!(filter_by_version(&notes, 4, Some(2)).is_empty());
+    }
+
+    #[test]
+    fn test_vault_verify_salt() {
+        let salt = crate::crypto::EncryptionManager::generate_salt();
+        let mut vault = Vault::new("Vault".to_string(), String::new(), salt.clone());
+        assert!(vault.verify_salt());
+
+        for bad in [
+            &salt[..salt.len() - 2],
+            "salt123",
+            "",
+            "!!!!!!!!!!!!!!!!!!!!!!",
+        ] {
+            vault.salt = bad.to_string();
+            assert!(!vault.verify_salt(), "{:?} accepted", bad);
+        }
+        vault.salt = format!("{}==", salt);
+        assert!(!vault.verify_salt());
+    }
+
+    #[test]
+    fn test_bulk_add_remove_tag() {
+        let mut notes: Vec<Note> = (0..4)
+            .map(|i| {
+                Note::new(
+                    format!("Note {}", i),
+                    String::new(),
+                    vec!["base".to_string()],
+                )
+            })
+            .collect();
+        notes[1].tags.push("urgent".to_string());
+        let selected = [notes[0].id, notes[1].id, notes[2].id];
+
+        // Note 1 already has the tag, note 3 isn't selected
+        assert_eq!(add_tag(&mut notes, &selected, "urgent"), 2);
+        assert_eq!(notes[0].tags, vec!["base", "urgent"]);
+        assert_eq!(notes[0].version, 2);
+        assert_eq!(notes[1].tags, vec!["base", "urgent"]);
+        assert_eq!(notes[1].version, 1);
+        assert_eq!(notes[3].tags, vec!["base"]);
+        assert_eq!(notes[3].version, 1);
+
+        // Only notes that had the tag change
+        let some = [notes[0].id, notes[3].id];
+        assert_eq!(remove_tag(&mut notes, &some, "urgent"), 1);
+        assert_eq!(notes[0].tags, vec!["base"]);
+        assert_eq!(notes[0].version, 3);
+        assert_eq!(notes[3].version, 1);
+        assert_eq!(notes[2].tags, vec!["base", "urgent"]);
+    }
+
+    #[test]
+    fn test_tag_diff() {
+        let with_tags = |tags: &[&str]| {
+            Note::new(
+                "Note".to_string(),
+                String::new(),
+                tags.iter().map(|t| t.to_string()).collect(),
+            )
+        };
+        let strings = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+        let old = with_tags(&["draft", "work"]);
+        assert_eq!(
+            tag_diff(&old, &with_tags(&["draft", "work", "urgent"])),
+            (strings(&["urgent"]), vec![])
+        );
+        assert_eq!(
+            tag_diff(&old, &with_tags(&["work"])),
+            (vec![], strings(&["draft"]))
+        );
+        assert_eq!(
+            tag_diff(&old, &with_tags(&["urgent", "work", "later"])),
+            (strings(&["urgent", "later"]), strings(&["draft"]))
+        );
+        assert_eq!(
+            tag_diff(&old, &with_tags(&["work", "draft"])),
+            (vec![], vec![])
+        );
+    }
+
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!(truncate_chars("short", 10), "short");
+        assert_eq!(truncate_chars("exact", 5), "exact");
+        assert_eq!(truncate_chars("truncated", 5), "trunc…");
+        assert_eq!(truncate_chars("", 0), "");
+        assert_eq!(truncate_chars("abc", 0), "…");
+
+        // Byte 4 falls inside "é"; slicing there would panic
+        let text = "café 日本語";
+        assert!(!text.is_char_boundary(4));
+        assert_eq!(truncate_chars(text, 4), "café…");
+        assert_eq!(truncate_chars(text, 6), "café 日…");
+    }
+
+    #[test]
+    fn test_tag_parse_flexible() {
+        let expected = Tag::from_path("work/project/urgent");
+        assert_eq!(
+            Tag::parse_flexible("work.project.urgent", DEFAULT_TAG_SEPARATORS),
+            expected
+        );
+        assert_eq!(
+            Tag::parse_flexible("work > project > urgent", DEFAULT_TAG_SEPARATORS),
+            expected
+        );
+        assert_eq!(
+            Tag::parse_flexible("work/project.urgent", DEFAULT_TAG_SEPARATORS),
+            expected
+        );
+        assert_eq!(
+            Tag::parse_flexible("work > project", DEFAULT_TAG_SEPARATORS).ancestors(),
+            vec!["work".to_string()]
+        );
+
+        // Only the requested separators are split on (plus `/`)
+        assert_eq!(Tag::parse_flexible("v1.2", &['>']).path, "v1.2");
+    }
+
+    #[test]
+    fn test_template_instantiate() {
+        let template = NoteTemplate::new(
+            "Meeting".to_string(),
+            "Meeting with {{person}} on {{date}}".to_string(),
+            "# {{person}}\n\nID: {{uuid}}\n{{unknown}}".to_string(),
+            vec!["meetings".to_string()],
+        );
+
+        let mut vars = HashMap::new();
+        vars.insert("person".to_string(), "Alice".to_string());
+        let note = template.instantiate(&vars);
+
+        let today = Utc::now().format("%Y-%m-%d").to_string();
+        assert_eq!(note.title, format!("Meeting with Alice on {}", today));
+        assert!(note.content.starts_with("# Alice"));
+        assert!(note.content.contains(&note.id.to_string()));
+        assert!(note.content.contains("{{unknown}}"));
+        assert_eq!(note.tags, vec!["meetings"]);
+        assert_eq!(note.version, 1);
+    }
+
+    #[test]
+    fn test_fuzzy_match_tags() {
+        let notes = vec![
+            Note::new("A".to_string(), String::new(), vec!["project".to_string()]),
+            Note::new(
+                "B".to_string(),
+                String::new(),
+                vec!["project".to_string(), "personal".to_string()],
+            ),
+        ];
+
+        assert_eq!(fuzzy_match_tags(&notes, "projet", 1), vec!["project"]);
+        assert_eq!(fuzzy_match_tags(&notes, "projetc", 2), vec!["project"]);
+        assert!(fuzzy_match_tags(&notes, "projetc", 1).is_empty());
+        assert!(fuzzy_match_tags(&notes, "garden", 1).is_empty());
+    }
+
+    #[test]
+    fn test_note_to_html() {
+        let note = Note::new(
+            "Shopping <List>".to_string(),
+            "## Groceries\n\n- milk\n- eggs\n\n[shop](https://example.com)\n\n```\nlet x = 1;\n```\n\n<script>alert(1)</script>\n".to_string(),
+            vec!["home".to_string()],
+        );
+
+        let html = note.to_html();
+        assert!(html.contains("<h1>Shopping &lt;List&gt;</h1>"));
+        assert!(html.contains("<h2>Groceries</h2>"));
+        assert!(html.contains("<ul>\n<li>milk</li>\n<li>eggs</li>\n</ul>"));
+        assert!(html.contains("<a href=\"https://example.com\">shop</a>"));
+        assert!(html.contains("<pre><code>let x = 1;\n</code></pre>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<script>"));
+    }
+
+    #[test]
+    fn test_note_to_html_drops_unsafe_links() {
+        let note = Note::new(
+            "Links".to_string(),
+            "[x](javascript:alert(1)) ![x](javascript:alert(2)) [y](JavaScript:alert(3))\n\n\
+             ![d](data:image/svg+xml;base64,PHN2Zz4=) [d](<java\tscript:alert(4)>)\n\n\
+             [ok](https://example.com) [mail](mailto:a@example.com) [rel](docs/guide.md) \
+             [n](note:1234) ![a](attachment:photo.png) [top](#top)\n"
+                .to_string(),
+            vec![],
+        );
+
+        let html = note.to_html();
+        assert!(!html.to_lowercase().contains("javascript"));
+        assert!(!html.contains("data:"));
+        assert!(html.contains("<a href=\"\">x</a>"));
+        assert!(html.contains("<img src=\"\" alt=\"x\" />"));
+        assert!(html.contains("<a href=\"https://example.com\">ok</a>"));
+        assert!(html.contains("<a href=\"mailto:a@example.com\">mail</a>"));
+        assert!(html.contains("<a href=\"docs/guide.md\">rel</a>"));
+        assert!(html.contains("<a href=\"note:1234\">n</a>"));
+        assert!(html.contains("<img src=\"attachment:photo.png\" alt=\"a\" />"));
+        assert!(html.contains("<a href=\"#top\">top</a>"));
+    }
+
+    #[test]
+    fn test_pinned_defaults_for_old_json() {
+        let note = Note::new("Old".to_string(), "Content".to_string(), vec![]);
+        let mut value = serde_json::to_value(&note).unwrap();
+        value.as_object_mut().unwrap().remove("pinned");
+
+        let parsed: Note = serde_json::from_value(value).unwrap();
+        assert!(!parsed.pinned);
+    }
+
+    #[test]
+    fn test_sort_pinned_then_updated() {
+        let base = Utc::now();
+        let mut notes: Vec<Note> = (0..4)
+            .map(|i| {
+                let mut note = Note::new(format!("Note {}", i), String::new(), vec![]);
+                note.updated_at = base + chrono::Duration::seconds(i);
+                note
+            })
+            .collect();
+        notes[0].pinned = true;
+        notes[2].pinned = true;
+
+        sort_notes(&mut notes, SortKey::PinnedThenUpdated);
+        let titles: Vec<&str> = notes.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, vec!["Note 2", "Note 0", "Note 3", "Note 1"]);
+
+        let mut note = Note::new("Pin me".to_string(), String::new(), vec![]);
+        let before = note.updated_at;
+        note.pin();
+        assert!(note.pinned);
+        assert!(note.updated_at >= before);
+        note.unpin();
+        assert!(!note.pinned);
+    }
+
+    #[test]
+    fn test_find_matches() {
+        let note = Note::new(String::new(), "aaaa Bar bar".to_string(), vec![]);
+        assert_eq!(note.find_matches("aa", true), vec![(0, 2), (2, 4)]);
+        assert_eq!(note.find_matches("bar", true), vec![(9, 12)]);
+        assert_eq!(note.find_matches("bar", false), vec![(5, 8), (9, 12)]);
+        assert!(note.find_matches("", false).is_empty());
+
+        let unicode = Note::new(String::new(), "Über café ÜBER Café".to_string(), vec![]);
+        let ranges = unicode.find_matches("über", false);
+        assert_eq!(ranges.len(), 2);
+        for (start, end) in &ranges {
+            assert_eq!(unicode.content[*start..*end].to_lowercase(), "über");
+        }
+        let cafe = unicode.find_matches("CAFÉ", false);
+        assert_eq!(cafe.len(), 2);
+        assert_eq!(&unicode.content[cafe[0].0..cafe[0].1], "café");
+        assert_eq!(unicode.find_matches("Café", true).len(), 1);
+    }
+
+    #[test]
+    fn test_deterministic_id() {
+        let namespace = Uuid::new_v4();
+        let a = Note::deterministic_id(&namespace, "journal/2024-01-01.md");
+        let b = Note::deterministic_id(&namespace, "journal/2024-01-01.md");
+        let c = Note::deterministic_id(&namespace, "journal/2024-01-02.md");
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.get_version_num(), 5);
+        assert_ne!(
+            a,
+            Note::deterministic_id(&Uuid::new_v4(), "journal/2024-01-01.md")
+        );
+    }
+
+    #[test]
+    fn test_to_canonical_json() {
+        let note = Note::new(
+            "Canonical".to_string(),
+            "Body with \"quotes\"\n".to_string(),
+            vec!["b".to_string(), "a".to_string()],
+        );
+
+        let canonical = note.to_canonical_json();
+        let reparsed: Note =
+            serde_json::from_str(&serde_json::to_string_pretty(&note).unwrap()).unwrap();
+        assert_eq!(reparsed.to_canonical_json(), canonical);
+        assert!(!canonical.contains(": "));
+        assert!(canonical.starts_with("{\"attachments\":[],\"content\":"));
+
+        let round_trip: Note = serde_json::from_str(&canonical).unwrap();
+        assert_eq!(round_trip.id, note.id);
+        assert_eq!(round_trip.tags, note.tags);
+        assert_eq!(round_trip.updated_at, note.updated_at);
+        assert_eq!(round_trip.to_canonical_json(), canonical);
+    }
+
+    #[test]
+    fn test_split_by_heading() {
+        let note = Note::new(
+            "Handbook".to_string(),
+            "Intro text\n\n## Setup\n\nInstall it.\n\n### Details\n\nMore.\n\n\
+             ## Usage\n\nRun it.\n\n## FAQ\nNone yet.\n\n# Appendix\n\nExtra."
+                .to_string(),
+            vec!["docs".to_string()],
+        );
+
+        let sections = note.split_by_heading(2);
+        let titles: Vec<&str> = sections.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, vec!["Setup", "Usage", "FAQ"]);
+        assert_eq!(
+            sections[0].content,
+            "[[Handbook]]\n\nInstall it.\n\n### Details\n\nMore."
+        );
+        assert_eq!(sections[2].content, "[[Handbook]]\n\nNone yet.");
+        for section in &sections {
+            assert_ne!(section.id, note.id);
+            assert_eq!(section.derived_from, Some(note.id));
+            assert_eq!(section.tags, note.tags);
+        }
+
+        assert_eq!(
+            Note::section_index(&sections),
+            "- [[Setup]]\n- [[Usage]]\n- [[FAQ]]\n"
+        );
+        assert!(note.split_by_heading(0).is_empty());
+        assert!(note.split_by_heading(4).is_empty());
+    }
+
+    #[test]
+    fn test_flatten_deep_tags() {
+        assert_eq!(Tag::truncate_depth("a/b/c/d/e", 3), "a/b/c");
+        assert_eq!(Tag::truncate_depth("a/b", 3), "a/b");
+        assert_eq!(Tag::truncate_depth("a/b", 0), "a");
+
+        let original = Tag::from_path("a/b/c/d/e");
+        let truncated = Tag::from_path(&Tag::truncate_depth(&original.path, 3));
+        assert!(original.ancestors().contains(&truncated.path));
+        assert_eq!(truncated.ancestors(), original.ancestors()[..2]);
+
+        let mut notes = vec![
+            Note::new(
+                "Deep".to_string(),
+                String::new(),
+                vec![
+                    "a/b/c/d/e".to_string(),
+                    "a/b/c/x".to_string(),
+                    "z".to_string(),
+                ],
+            ),
+            Note::new(
+                "Shallow".to_string(),
+                String::new(),
+                vec!["a/b".to_string()],
+            ),
+        ];
+        assert_eq!(flatten_deep_tags(&mut notes, 3), 1);
+        assert_eq!(notes[0].tags, vec!["a/b/c".to_string(), "z".to_string()]);
+        assert_eq!(notes[0].version, 2);
+        assert_eq!(notes[1].version, 1);
+    }
+
+    #[test]
+    fn test_redact() {
+        let note = Note::new(
+            "Contact alice@example.com".to_string(),
+            "Use key sk-abcdefghijklmnop1234 for the API, or ask bob@example.org".to_string(),
+            vec!["ops".to_string()],
+        );
+
+        let redacted = note.redact(&default_redaction_patterns(), "[redacted]");
+        assert_eq!(redacted.title, "Contact [redacted]");
+        assert_eq!(
+            redacted.content,
+            "Use key [redacted] for the API, or ask [redacted]"
+        );
+        assert_eq!(redacted.id, note.id);
+        assert_eq!(redacted.tags, note.tags);
+
+        assert!(note.title.contains("alice@example.com"));
+        assert!(note.content.contains("sk-abcdefghijklmnop1234"));
+
+        // Keywords and attachment names are redacted too
+        let mut note = note;
+        note.search_keywords = vec!["carol@example.net".to_string()];
+        note.attachments = vec![Attachment::new(
+            note.id,
+            "token_abcdefghijklmnop1234.txt".to_string(),
+            "text/plain".to_string(),
+            1,
+        )];
+        let redacted = note.redact(&default_redaction_patterns(), "[redacted]");
+        assert_eq!(redacted.search_keywords, vec!["[redacted]"]);
+        assert_eq!(redacted.attachments[0].filename, "[redacted].txt");
+
+        // Replacements are literal, not capture-group templates
+        let custom = vec![Regex::new(r"ops-\d+").unwrap()];
+        let ticket = Note::new("ops-42".to_string(), String::new(), vec![]);
+        assert_eq!(ticket.redact(&custom, "$0").title, "$0");
+    }
+
+    #[test]
+    fn test_update_timestamp_never_goes_backward() {
+        let mut note = Note::new("Skewed".to_string(), String::new(), vec![]);
+        // Last written on a device whose clock ran an hour fast
+        let ahead = Utc::now() + chrono::Duration::hours(1);
+        note.updated_at = ahead;
+
+        note.update("Skewed".to_string(), "first".to_string(), vec![]);
+        assert_eq!(note.updated_at, ahead + chrono::Duration::milliseconds(1));
+        let previous = note.updated_at;
+        note.add_tag("later");
+        assert!(note.updated_at > previous);
+        assert_eq!(note.version, 3);
+
+        let mut normal = Note::new("Normal".to_string(), String::new(), vec![]);
+        let before = Utc::now();
+        normal.update("Normal".to_string(), "edit".to_string(), vec![]);
+        assert!(normal.updated_at >= before);
+    }
+
+    #[test]
+    fn test_consolidate_tags() {
+        let content = "---\ntitle: Plan\ntags: [work/q3, \"Ideas\"]\naliases:\n  - plan\n---\n\
+            # Heading\nShip it #work/q3 and #release/\n```\n#not-a-tag\n```\nIssue #42 (#ideas)\n";
+        let mut note = Note::new(
+            "Plan".to_string(),
+            content.to_string(),
+            vec!["home".to_string(), "work//q3".to_string()],
+        );
+        assert_eq!(note.frontmatter_tags(), vec!["work/q3", "Ideas"]);
+        assert_eq!(note.inline_hashtags(), vec!["work/q3", "release", "ideas"]);
+
+        let version = note.version;
+        let mut folded = note.clone();
+        let ci = TagConfig {
+            case_insensitive: true,
+        };
+        assert!(folded.consolidate_tags_with(&ci));
+        assert_eq!(folded.tags, vec!["home", "work/q3", "Ideas", "release"]);
+
+        assert!(note.consolidate_tags());
+        assert_eq!(
+            note.tags,
+            vec!["home", "work/q3", "Ideas", "release", "ideas"]
+        );
+        assert_eq!(note.version, version + 1);
+
+        assert!(!note.consolidate_tags());
+        assert_eq!(note.version, version + 1);
+    }
+
+    #[test]
+    fn test_frontmatter_tag_list() {
+        let note = Note::new(
+            "List".to_string(),
+            "---\ntags:\n  - a\n  - '#b'\ntitle: x\n---\nbody".to_string(),
+            vec![],
+        );
+        assert_eq!(note.frontmatter_tags(), vec!["a", "b"]);
+
+        let unclosed = Note::new("U".to_string(), "---\ntags: a\n".to_string(), vec![]);
+        assert!(unclosed.frontmatter_tags().is_empty());
+    }
+
+    #[test]
+    fn test_word_count() {
+        let note = Note::new(
+            "W".to_string(),
+            "# Heading\n\nSome **bold** [link](https://example.com) text".to_string(),
+            vec![],
+        );
+        assert_eq!(note.word_count(), 5);
+    }
+
+    #[test]
+    fn test_diffable_round_trip() {
+        let mut note = Note::new(
+            "Plan".to_string(),
+            "line one\n---\nline three\n".to_string(),
+            vec!["work".to_string()],
+        );
+        let before = note.to_diffable();
+        assert!(before.lines().count() > 10);
+        assert!(before.ends_with("line one\n---\nline three\n"));
+
+        let parsed = Note::from_diffable(&before).unwrap();
+        assert_eq!(parsed.to_canonical_json(), note.to_canonical_json());
+
+        note.title = "Plan v2".to_string();
+        let after = note.to_diffable();
+        let changed = before
+            .lines()
+            .zip(after.lines())
+            .filter(|(a, b)| a != b)
+            .count();
+        assert_eq!(before.lines().count(), after.lines().count());
+        assert_eq!(changed, 1);
+
+        assert!(Note::from_diffable("{}").is_err());
+    }
+
+    #[test]
+    fn test_attachments() {
+        let mut note = Note::new("Pics".to_string(), String::new(), vec![]);
+        let attachment = Attachment::new(Uuid::new_v4(), "cat.png".into(), "image/png".into(), 42);
+        let id = attachment.id;
+        let missing = Uuid::new_v4();
+        note.content = format!(
+            "![cat](attachment:{}) and [gone](attachment:{})",
+            id, missing
+        );
+
+        assert!(note.add_attachment(attachment.clone()));
+        assert!(!note.add_attachment(attachment));
+        assert_eq!(note.attachments[0].note_id, note.id);
+        assert_eq!(note.attachment_refs(), vec![id, missing]);
+
+        let resolved = note.resolve_attachment_links(|a| format!("files/{}", a.filename));
+        assert_eq!(
+            resolved,
+            format!("![cat](files/cat.png) and [gone](attachment:{})", missing)
+        );
+
+        assert_eq!(note.remove_attachment(&id).unwrap().filename, "cat.png");
+        assert!(note.remove_attachment(&id).is_none());
+    }
+
+    #[test]
+    fn test_attachments_backward_compat() {
+        let mut value = serde_json::to_value(Note::new("Old".into(), "c".into(), vec![])).unwrap();
+        value.as_object_mut().unwrap().remove("attachments");
+        let note: Note = serde_json::from_value(value).unwrap();
+        assert!(note.attachments.is_empty());
+
+        let mut with = note.clone();
+        with.add_attachment(Attachment::new(
+            note.id,
+            "a.pdf".into(),
+            "application/pdf".into(),
+            7,
+        ));
+        let parsed: Note = serde_json::from_str(&serde_json::to_string(&with).unwrap()).unwrap();
+        assert_eq!(parsed.attachments, with.attachments);
+    }
+
+    #[test]
+    fn test_find_backlinks() {
+        let target = Note::new("Target".to_string(), "[[Target]]".to_string(), vec![]);
+        let by_title = Note::new("A".to_string(), "see [[ Target |here]]".to_string(), vec![]);
+        let by_id = Note::new(
+            "B".to_string(),
+            format!("see [that](note:{})", target.id),
+            vec![],
+        );
+        let unrelated = Note::new(
+            "C".to_string(),
+            "[[Targets]] [x](note:nope)".to_string(),
+            vec![],
+        );
+        let notes = vec![target.clone(), by_title.clone(), unrelated, by_id.clone()];
+
+        let ids: Vec<Uuid> = find_backlinks(&notes, &target)
+            .iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(ids, vec![by_title.id, by_id.id]);
+        assert!(find_backlinks(&notes, &by_id).is_empty());
+    }
+
+    #[test]
+    fn test_case_insensitive_tags() {
+        let ci = TagConfig {
+            case_insensitive: true,
+        };
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect();
+        let mut notes = vec![
+            Note::new("A".to_string(), String::new(), tags(&["Work/Q3"])),
+            Note::new("B".to_string(), String::new(), tags(&["work"])),
+            Note::new("C".to_string(), String::new(), tags(&["home"])),
+        ];
+
+        assert_eq!(filter_by_tag(&notes, "WORK", &ci).len(), 1);
+        assert!(filter_by_tag(&notes, "WORK", &TagConfig::default()).is_empty());
+
+        let tree = build_tag_tree(&notes, &ci);
+        assert_eq!(tree.len(), 2);
+        let work = tree.iter().find(|n| n.path == "Work").unwrap();
+        assert_eq!(work.count, 2);
+        assert_eq!(work.children[0].path, "Work/Q3");
+        assert_eq!(build_tag_tree(&notes, &TagConfig::default()).len(), 3);
+
+        assert!(!notes[1].add_tag_with("Work", &ci));
+        assert_eq!(notes[1].tags, vec!["work"]);
+
+        assert_eq!(rename_tag_with(&mut notes, "WORK", "job", &ci), 2);
+        assert_eq!(notes[0].tags, vec!["job/Q3"]);
+        assert_eq!(notes[1].tags, vec!["job"]);
+
+        let before = notes[1].clone();
+        notes[1].tags = tags(&["JOB", "home"]);
+        assert_eq!(
+            tag_diff_with(&before, &notes[1], &ci),
+            (tags(&["home"]), vec![])
+        );
+        assert_eq!(remove_tag_with(&mut notes, &[before.id], "Job", &ci), 1);
+        assert_eq!(notes[1].tags, vec!["home"]);
+
+        notes[2].tags = tags(&["Ideas", "ideas", "home"]);
+        assert!(notes[2].consolidate_tags_with(&ci));
+        assert_eq!(notes[2].tags, vec!["Ideas", "home"]);
+    }
+
+    #[test]
+    fn test_primary_tag() {
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect();
+        let many = Note::new(
+            "Many".to_string(),
+            String::new(),
+            tags(&["work/q3", "home", "a"]),
+        );
+        assert_eq!(many.primary_tag(), Some("work/q3"));
+
+        let one = Note::new("One".to_string(), String::new(), tags(&["", "solo"]));
+        assert_eq!(one.primary_tag(), Some("solo"));
+        assert_eq!(one.primary_tag_or("none"), "solo");
+
+        let none = Note::new("None".to_string(), String::new(), vec![]);
+        assert_eq!(none.primary_tag(), None);
+        assert_eq!(none.primary_tag_or("inbox"), "inbox");
+    }
+
+    #[test]
+    fn test_rename_tag() {
+        let mut notes = vec![
+            Note::new(
+                "A".to_string(),
+                String::new(),
+                vec!["work".to_string(), "work/urgent".to_string()],
+            ),
+            Note::new(
+                "B".to_string(),
+                String::new(),
+                vec!["job".to_string(), "work".to_string()],
+            ),
+            Note::new("C".to_string(), String::new(), vec!["home".to_string()]),
+        ];
+
+        assert_eq!(rename_tag(&mut notes, "work", "job"), 2);
+        assert_eq!(notes[0].tags, vec!["job", "job/urgent"]);
+        assert_eq!(notes[0].version, 2);
+        // Merged into the existing tag without duplicating it
+        assert_eq!(notes[1].tags, vec!["job"]);
+        assert_eq!(notes[2].tags, vec!["home"]);
+        assert_eq!(notes[2].version, 1);
+    }
+
+    #[test]
+    fn test_migrate_note() {
+        let note = Note::new("Title".to_string(), "Content".to_string(), vec![]);
+        assert_eq!(note.schema_version, NOTE_SCHEMA_VERSION);
+
+        // v0: the original layout without pinned or schema_version
+        let mut v0 = serde_json::to_value(&note).unwrap();
+        let object = v0.as_object_mut().unwrap();
+        object.remove("pinned");
+        object.remove("schema_version");
+        let migrated = migrate_note(v0).unwrap();
+        assert_eq!(migrated.id, note.id);
+        assert!(!migrated.pinned);
+        assert_eq!(migrated.schema_version, NOTE_SCHEMA_VERSION);
+
+        // v1: pinned, but none of the fields added since
+        let mut v1 = serde_json::to_value(&note).unwrap();
+        let object = v1.as_object_mut().unwrap();
+        object.insert("pinned".to_string(), true.into());
+        object.insert("schema_version".to_string(), 1.into());
+        for field in ["derived_from", "search_keywords", "attachments"] {
+            object.remove(field);
+        }
+        let migrated = migrate_note(v1).unwrap();
+        assert!(migrated.pinned);
+        assert_eq!(migrated.derived_from, None);
+        assert!(migrated.search_keywords.is_empty());
+        assert!(migrated.attachments.is_empty());
+        assert_eq!(migrated.schema_version, NOTE_SCHEMA_VERSION);
+
+        // Fields already present are kept
+        let mut v2 = serde_json::to_value(&note).unwrap();
+        let source = Uuid::new_v4();
+        v2["schema_version"] = 2.into();
+        v2["derived_from"] = source.to_string().into();
+        v2["search_keywords"] = serde_json::json!(["kept"]);
+        let migrated = migrate_note(v2).unwrap();
+        assert_eq!(migrated.derived_from, Some(source));
+        assert_eq!(migrated.search_keywords, vec!["kept"]);
+        assert_eq!(migrated.schema_version, NOTE_SCHEMA_VERSION);
+
+        // Future versions are rejected
+        let mut future = serde_json::to_value(&note).unwrap();
+        future["schema_version"] = 99.into();
+        assert!(matches!(
+            migrate_note(future),
+            Err(ModelError::UnsupportedSchemaVersion(99))
+        ));
+    }
+
+    #[test]
+    fn test_note_fingerprint() {
+        let a = Note::new(
+            "Title".to_string(),
+            "Content".to_string(),
+            vec!["b".to_string(), "a".to_string()],
+        );
+        let mut b = Note::new(
+            "Title ".to_string(),
+            "Content".to_string(),
+            vec!["a".to_string(), "b".to_string()],
+        );
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_eq!(a.fingerprint().len(), 64);
+
+        // Timestamp and version changes don't matter
+        b.updated_at += chrono::Duration::days(1);
+        b.version = 7;
+        assert_eq!(a.fingerprint(), b.fingerprint());
+
+        // Content changes do
+        b.content = "Changed".to_string();
+        assert_ne!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn test_lint_note() {
+        let clean = Note::new(
+            "Clean".to_string(),
+            "Body".to_string(),
+            vec!["work/project".to_string()],
+        );
+        assert!(lint_note(&clean).is_empty());
+
+        let messy = Note::new(
+            "  ".to_string(),
+            "".to_string(),
+            vec![
+                "a".to_string(),
+                "a".to_string(),
+                "work//x".to_string(),
+                "has space".to_string(),
+                "/".to_string(),
+            ],
+        );
+        assert_eq!(
+            lint_note(&messy),
+            vec![
+                LintWarning::EmptyTitle,
+                LintWarning::EmptyContent,
+                LintWarning::DuplicateTag("a".to_string()),
+                LintWarning::MalformedTag("work//x".to_string()),
+                LintWarning::MalformedTag("has space".to_string()),
+                LintWarning::MalformedTag("/".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_notes_broken_links() {
+        let target = Note::new("Target".to_string(), "Body".to_string(), vec![]);
+        let linker = Note::new(
+            "Linker".to_string(),
+            "See [[Target]], [[ Target | alias ]] and [[Missing]]".to_string(),
+            vec![],
+        );
+
+        // Without a title set, links aren't checked
+        assert!(lint_note(&linker).is_empty());
+
+        let report = lint_notes(&[target, linker.clone()]);
+        assert_eq!(
+            report,
+            vec![(
+                linker.id,
+                vec![LintWarning::BrokenLink("Missing".to_string())]
+            )]
+        );
+    }
 }
@@ -2,12 +2,24 @@
 //!
 //! Provides indexing and searching for notes with Markdown support.
 
+use crate::models::{markdown_to_text, primary_tag, Note, TagConfig};
+use serde::Serialize;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tantivy::{
-    collector::TopDocs, doc, query::QueryParser, schema::*, Index, IndexReader, IndexWriter,
-    TantivyDocument,
+    collector::DocSetCollector,
+    collector::{Count, TopDocs},
+    directory::{error::LockError, Directory, INDEX_WRITER_LOCK},
+    doc,
+    query::{AllQuery, BooleanQuery, Occur, Query, QueryParser, RegexQuery, TermQuery},
+    schema::*,
+    snippet::SnippetGenerator,
+    tokenizer::TextAnalyzer,
+    DocId, Index, IndexReader, IndexWriter, ReloadPolicy, Score, SegmentReader, TantivyDocument,
 };
 use thiserror::Error;
+use uuid::Uuid;
 
 #[derive(Error, Debug)]
 pub enum SearchError {
@@ -17,45 +29,687 @@ pub enum SearchError {
     SearchFailed(String),
     #[error("Parse error: {0}")]
     ParseError(String),
+    #[error("Index is locked for writing by another engine or process")]
+    IndexBusy,
+    #[error("Index was created with a different schema and must be rebuilt")]
+    SchemaMismatch,
+}
+
+/// Optional schema extensions, fixed when an index is created
+///
+/// Changing the configuration of an existing index requires rebuilding it;
+/// opening it with a different configuration fails with `SchemaMismatch`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexConfig {
+    /// Also index note bodies as `content_raw` (verbatim Markdown, for URLs and
+    /// code) and `content_text` (Markdown stripped, for prose)
+    pub separate_content_fields: bool,
+    /// Store note bodies in the index, which `snippets` needs. Off by default:
+    /// bodies are the bulk of a note, so storing them roughly doubles the
+    /// index, and in encrypted vaults it keeps plaintext on disk.
+    pub store_content: bool,
+    /// Record term positions in text fields, which phrase queries (`"exact
+    /// words"`) need. On by default; turning it off shrinks the index, and
+    /// phrase queries then fail with `ParseError`.
+    pub index_positions: bool,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            separate_content_fields: false,
+            store_content: false,
+            index_positions: true,
+        }
+    }
+}
+
+/// How `SearchEngine::new_with_options` opens an index
+///
+/// Unlike `IndexConfig`, these only affect the engine being opened, not the
+/// index on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenOptions {
+    /// When another engine or process holds the index's writer lock at open
+    /// time, open read-only instead of failing: searching works, writing
+    /// returns `IndexBusy`. On by default; when off, opening a locked index
+    /// fails with `IndexBusy`.
+    pub read_only_when_locked: bool,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self {
+            read_only_when_locked: true,
+        }
+    }
+}
+
+/// Largest field values `SearchEngine` indexes, in bytes
+///
+/// Longer values are cut at a character boundary before indexing so that
+/// pathological input can't exhaust the writer's memory budget. Only the index
+/// entry is affected; the stored note keeps its full text. The indexing calls
+/// return a `FieldTruncation` for each value cut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldLimits {
+    /// Maximum title length (default 4 KiB)
+    pub max_title_bytes: usize,
+    /// Maximum length of each tag (default 1 KiB)
+    pub max_tag_bytes: usize,
+    /// Maximum content length (default 4 MiB)
+    pub max_content_bytes: usize,
+}
+
+impl Default for FieldLimits {
+    fn default() -> Self {
+        Self {
+            max_title_bytes: 4 * 1024,
+            max_tag_bytes: 1024,
+            max_content_bytes: 4 * 1024 * 1024,
+        }
+    }
+}
+
+/// A field value cut to its `FieldLimits` maximum before indexing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldTruncation {
+    /// ID of the note the value belongs to
+    pub note_id: String,
+    /// Field that was cut: `title`, `content` or `tag`
+    pub field: &'static str,
+    /// Length of the stored value in bytes
+    pub original_bytes: usize,
+    /// Length of the indexed prefix in bytes
+    pub indexed_bytes: usize,
+}
+
+impl std::fmt::Display for FieldTruncation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Truncated {} of note {} from {} to {} bytes for indexing",
+            self.field, self.note_id, self.original_bytes, self.indexed_bytes
+        )
+    }
+}
+
+/// Cut `value` to at most `max_bytes`, backing off to a character boundary
+///
+/// Records each cut in `truncations` so the caller can report it.
+fn limit_field<'a>(
+    value: &'a str,
+    max_bytes: usize,
+    id: &str,
+    field: &'static str,
+    truncations: &mut Vec<FieldTruncation>,
+) -> &'a str {
+    if value.len() <= max_bytes {
+        return value;
+    }
+    let mut end = max_bytes;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    truncations.push(FieldTruncation {
+        note_id: id.to_string(),
+        field,
+        original_bytes: value.len(),
+        indexed_bytes: end,
+    });
+    &value[..end]
+}
+
+/// Regex matching `literal` in any letter case
+///
+/// Index terms are matched against the raw regex, so each cased character
+/// becomes a class such as `[wW]`.
+fn case_insensitive_regex(literal: &str) -> String {
+    literal
+        .chars()
+        .map(|c| {
+            let lower: Vec<char> = c.to_lowercase().collect();
+            let upper: Vec<char> = c.to_uppercase().collect();
+            match (lower.as_slice(), upper.as_slice()) {
+                ([lower], [upper]) if lower != upper => format!(
+                    "[{}{}]",
+                    regex::escape(&lower.to_string()),
+                    regex::escape(&upper.to_string())
+                ),
+                _ => regex::escape(&c.to_string()),
+            }
+        })
+        .collect()
+}
+
+/// Build the note schema, tokenizing text fields with the named tokenizer
+fn build_schema(tokenizer: &str, config: &IndexConfig) -> Schema {
+    let index_option = if config.index_positions {
+        IndexRecordOption::WithFreqsAndPositions
+    } else {
+        IndexRecordOption::WithFreqs
+    };
+    let text_indexing = TextFieldIndexing::default()
+        .set_tokenizer(tokenizer)
+        .set_index_option(index_option);
+    let text = TextOptions::default().set_indexing_options(text_indexing);
+    let content = if config.store_content {
+        text.clone().set_stored()
+    } else {
+        text.clone()
+    };
+
+    let mut schema_builder = Schema::builder();
+
+    // IDs are indexed untokenized so entries can be replaced or deleted by term
+    schema_builder.add_text_field("id", STRING | STORED);
+    schema_builder.add_text_field("title", text.clone().set_stored());
+    schema_builder.add_text_field("content", content);
+    schema_builder.add_text_field("tags", text.clone().set_stored());
+    // Each tag verbatim, one value per tag, for `notes_with_tag`
+    schema_builder.add_text_field("tag_path", STRING);
+    schema_builder.add_date_field("created_at", INDEXED | STORED);
+    // Fast so `search_recency_boosted` can read it per hit
+    schema_builder.add_date_field("updated_at", INDEXED | STORED | FAST);
+    // Lets the index be checked against note files
+    schema_builder.add_u64_field("version", STORED);
+    // Optional fields go last so they don't shift the IDs of the fields above
+    if config.separate_content_fields {
+        schema_builder.add_text_field("content_raw", text.clone());
+        schema_builder.add_text_field("content_text", text);
+    }
+
+    schema_builder.build()
+}
+
+/// Build the index document for a note, with any fields cut to `limits`
+#[allow(clippy::too_many_arguments)]
+fn build_document(
+    schema: &Schema,
+    limits: &FieldLimits,
+    id: &str,
+    title: &str,
+    content: &str,
+    tags: &[String],
+    created_at: i64,
+    updated_at: i64,
+    version: Option<u64>,
+) -> (TantivyDocument, Vec<FieldTruncation>) {
+    let id_field = schema.get_field("id").unwrap();
+    let title_field = schema.get_field("title").unwrap();
+    let content_field = schema.get_field("content").unwrap();
+    let tags_field = schema.get_field("tags").unwrap();
+    let created_field = schema.get_field("created_at").unwrap();
+    let updated_field = schema.get_field("updated_at").unwrap();
+
+    let mut truncations = Vec::new();
+    let title = limit_field(title, limits.max_title_bytes, id, "title", &mut truncations);
+    let content = limit_field(
+        content,
+        limits.max_content_bytes,
+        id,
+        "content",
+        &mut truncations,
+    );
+    let tags: Vec<&str> = tags
+        .iter()
+        .map(|tag| limit_field(tag, limits.max_tag_bytes, id, "tag", &mut truncations))
+        .collect();
+    let tags_str = tags.join(" ");
+
+    let mut doc = doc!(
+        id_field => id,
+        title_field => title,
+        content_field => content,
+        tags_field => tags_str,
+        created_field => tantivy::DateTime::from_timestamp_secs(created_at),
+        updated_field => tantivy::DateTime::from_timestamp_secs(updated_at),
+    );
+
+    if let (Ok(raw_field), Ok(text_field)) = (
+        schema.get_field("content_raw"),
+        schema.get_field("content_text"),
+    ) {
+        doc.add_text(raw_field, content);
+        doc.add_text(text_field, markdown_to_text(content));
+    }
+    if let Some(version) = version {
+        doc.add_u64(schema.get_field("version").unwrap(), version);
+    }
+    let tag_path_field = schema.get_field("tag_path").unwrap();
+    for tag in tags {
+        doc.add_text(tag_path_field, tag);
+    }
+
+    (doc, truncations)
+}
+
+/// Literal characters `search_wildcard` requires before the first wildcard
+pub const MIN_WILDCARD_PREFIX: usize = 2;
+
+/// Translate a glob-style term pattern (`*`, `?`) into a term regex
+///
+/// The pattern is lowercased to match the default tokenizer.
+fn wildcard_regex(pattern: &str) -> Result<String, SearchError> {
+    let pattern = pattern.trim().to_lowercase();
+    if pattern.chars().any(char::is_whitespace) {
+        return Err(SearchError::ParseError(
+            "Wildcard pattern must be a single term".to_string(),
+        ));
+    }
+    let prefix = pattern
+        .chars()
+        .take_while(|c| !matches!(c, '*' | '?'))
+        .count();
+    if prefix < MIN_WILDCARD_PREFIX {
+        return Err(SearchError::ParseError(format!(
+            "Wildcard pattern '{}' needs at least {} characters before the first wildcard",
+            pattern, MIN_WILDCARD_PREFIX
+        )));
+    }
+
+    let mut regex = String::with_capacity(pattern.len() * 2);
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '\\' | '.' | '+' | '(' | ')' | '|' | '[' | ']' | '{' | '}' | '^' | '$' => {
+                regex.push('\\');
+                regex.push(c);
+            }
+            _ => regex.push(c),
+        }
+    }
+    Ok(regex)
+}
+
+/// A single searchable note field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    /// Note titles
+    Title,
+    /// Note bodies
+    Content,
+    /// Note bodies as verbatim Markdown (needs `IndexConfig::separate_content_fields`)
+    ContentRaw,
+    /// Note bodies with Markdown stripped (needs `IndexConfig::separate_content_fields`)
+    ContentText,
+    /// Note tags
+    Tags,
+}
+
+impl SearchField {
+    /// Name of the field in the index schema
+    fn field_name(self) -> &'static str {
+        match self {
+            SearchField::Title => "title",
+            SearchField::Content => "content",
+            SearchField::ContentRaw => "content_raw",
+            SearchField::ContentText => "content_text",
+            SearchField::Tags => "tags",
+        }
+    }
+}
+
+/// A search hit with the note fields stored in the index
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchResult {
+    /// ID of the matching note
+    pub note_id: String,
+    /// Relevance score
+    pub score: f32,
+    /// Title of the note
+    pub title: String,
+    /// Tags of the note, in their original order
+    pub tags: Vec<String>,
+}
+
+/// The stored fields of one indexed note
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedNote {
+    pub id: String,
+    pub title: String,
+    pub tags: Vec<String>,
+    /// Creation time in Unix seconds
+    pub created_at: i64,
+    /// Last update time in Unix seconds
+    pub updated_at: i64,
+    /// Note version, recorded by `upsert_note` but not `index_note`
+    pub version: Option<u64>,
+}
+
+/// One page of results from `SearchEngine::search_page`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchPage {
+    /// Hits on this page, best first
+    pub results: Vec<SearchResult>,
+    /// Number of notes matching the query across all pages
+    pub total: usize,
+    /// Zero-based page number
+    pub page: usize,
+    /// Page size the page was computed with
+    pub page_size: usize,
+}
+
+/// Largest number of leading hits a search may skip, e.g. via `search_page`
+///
+/// Skipped hits still have to be ranked, so deeper pages are returned empty
+/// instead of costing memory proportional to the offset.
+pub const MAX_SEARCH_OFFSET: usize = 100_000;
+
+/// Group name used by `search_grouped` for hits without tags
+pub const UNTAGGED_GROUP: &str = "";
+
+/// One entry in a batch of index changes for `SearchEngine::apply_changes`
+#[derive(Debug, Clone)]
+pub enum IndexChange {
+    /// Index a note not yet in the index
+    Add(Note),
+    /// Replace a note's existing entry
+    Update(Note),
+    /// Remove a note's entry
+    Delete(Uuid),
 }
 
 /// Search engine for notes
+///
+/// Clones are cheap and share the index, the reader and the pooled writer, so
+/// one clone can index on a background thread while others search. A commit
+/// becomes visible to every clone after a reload (explicit, or automatic
+/// shortly afterwards). Tantivy allows a single writer per index: clones share
+/// `with_writer`'s writer safely, but only one `get_writer` result can exist at
+/// a time across all clones and processes.
+#[derive(Clone)]
 pub struct SearchEngine {
     index: Index,
+    index_path: PathBuf,
     schema: Schema,
+    reader: IndexReader,
+    max_results: Option<usize>,
+    field_limits: FieldLimits,
+    tag_config: TagConfig,
+    /// Set when the index was locked by another writer at open time
+    read_only: bool,
+    /// Long-lived writer lent out by `with_writer`, created on first use
+    pooled_writer: Arc<Mutex<Option<IndexWriter>>>,
+    /// Latest failed commit of a dropped `IndexSession`, see `take_session_error`
+    session_error: Arc<Mutex<Option<SearchError>>>,
 }
 
 impl SearchEngine {
     /// Create a new search engine with an index at the given path
     pub fn new(index_path: PathBuf) -> Result<Self, SearchError> {
-        let mut schema_builder = Schema::builder();
+        Self::new_with_config(index_path, &IndexConfig::default())
+    }
+
+    /// Create a search engine with optional schema extensions
+    pub fn new_with_config(index_path: PathBuf, config: &IndexConfig) -> Result<Self, SearchError> {
+        Self::new_with_options(index_path, config, &OpenOptions::default())
+    }
 
-        schema_builder.add_text_field("id", TEXT | STORED);
-        schema_builder.add_text_field("title", TEXT | STORED);
-        schema_builder.add_text_field("content", TEXT);
-        schema_builder.add_text_field("tags", TEXT | STORED);
-        schema_builder.add_date_field("created_at", INDEXED | STORED);
-        schema_builder.add_date_field("updated_at", INDEXED | STORED);
+    /// Create a search engine with optional schema extensions and open options
+    pub fn new_with_options(
+        index_path: PathBuf,
+        config: &IndexConfig,
+        options: &OpenOptions,
+    ) -> Result<Self, SearchError> {
+        Self::open(
+            index_path,
+            build_schema("default", config),
+            None,
+            options.read_only_when_locked,
+        )
+    }
 
-        let schema = schema_builder.build();
+    /// Create a search engine whose text fields use a caller-supplied tokenizer
+    ///
+    /// The analyzer is registered under `name` and applied to the title, content
+    /// and tags fields. Tokenizers aren't persisted with the index, so the same
+    /// analyzer must be registered under the same name every time the index is
+    /// reopened, or indexing and querying will fail.
+    pub fn new_with_tokenizer(
+        index_path: PathBuf,
+        name: &str,
+        tokenizer: TextAnalyzer,
+    ) -> Result<Self, SearchError> {
+        Self::open(
+            index_path,
+            build_schema(name, &IndexConfig::default()),
+            Some((name, tokenizer)),
+            OpenOptions::default().read_only_when_locked,
+        )
+    }
 
+    /// Open or create the index at `index_path`
+    ///
+    /// Fails with `SchemaMismatch` if an existing index was created with a
+    /// schema other than `expected_schema`.
+    fn open(
+        index_path: PathBuf,
+        expected_schema: Schema,
+        tokenizer: Option<(&str, TextAnalyzer)>,
+        read_only_when_locked: bool,
+    ) -> Result<Self, SearchError> {
         std::fs::create_dir_all(&index_path).map_err(|e| SearchError::IndexError(e.to_string()))?;
 
-        let index = Index::create_in_dir(&index_path, schema.clone())
+        let index = Index::create_in_dir(&index_path, expected_schema.clone())
             .or_else(|_| Index::open_in_dir(&index_path))
             .map_err(|e| SearchError::IndexError(e.to_string()))?;
+        // An existing index keeps the schema it was created with; documents
+        // built against a different one corrupt it
+        let schema = index.schema();
+        if schema != expected_schema {
+            return Err(SearchError::SchemaMismatch);
+        }
+
+        if let Some((name, analyzer)) = tokenizer {
+            index.tokenizers().register(name, analyzer);
+        }
+
+        // Probe the writer lock without holding it; a lock taken later by
+        // someone else surfaces as `IndexBusy` from `get_writer` instead
+        let read_only = match index.directory().acquire_lock(&INDEX_WRITER_LOCK) {
+            Ok(_lock) => false,
+            Err(LockError::LockBusy) if read_only_when_locked => true,
+            Err(LockError::LockBusy) => return Err(SearchError::IndexBusy),
+            Err(e) => return Err(SearchError::IndexError(e.to_string())),
+        };
+
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()
+            .map_err(|e: tantivy::TantivyError| SearchError::IndexError(e.to_string()))?;
+
+        Ok(Self {
+            index,
+            index_path,
+            schema,
+            reader,
+            max_results: None,
+            field_limits: FieldLimits::default(),
+            tag_config: TagConfig::default(),
+            read_only,
+            pooled_writer: Arc::new(Mutex::new(None)),
+            session_error: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// The schema `new` creates indexes with
+    ///
+    /// External tools can build compatible indexes from this without opening
+    /// one. Engines created with a custom tokenizer or `IndexConfig` differ;
+    /// use `schema_json` on such an engine instead.
+    pub fn default_schema() -> Schema {
+        build_schema("default", &IndexConfig::default())
+    }
+
+    /// This engine's schema, serialized in Tantivy's JSON format
+    pub fn schema_json(&self) -> String {
+        serde_json::to_string_pretty(&self.schema).expect("schemas always serialize")
+    }
+
+    /// Set a hard cap on how many results a single search may return
+    ///
+    /// Larger requested limits are silently clamped, bounding memory use.
+    pub fn set_max_results(&mut self, max_results: Option<usize>) {
+        self.max_results = max_results;
+    }
+
+    /// Set the maximum field lengths applied when indexing notes
+    pub fn set_field_limits(&mut self, limits: FieldLimits) {
+        self.field_limits = limits;
+    }
+
+    /// Set how `notes_with_tag` compares tags
+    pub fn set_tag_config(&mut self, config: TagConfig) {
+        self.tag_config = config;
+    }
+
+    /// Apply the configured cap to a requested limit
+    fn effective_limit(&self, limit: usize) -> usize {
+        match self.max_results {
+            Some(max) => limit.min(max),
+            None => limit,
+        }
+    }
+
+    /// Force the reader to pick up the latest commit
+    ///
+    /// The reader also reloads on its own shortly after a commit, but callers that
+    /// need to search immediately after writing should call this first.
+    pub fn reload(&self) -> Result<(), SearchError> {
+        self.reader
+            .reload()
+            .map_err(|e| SearchError::IndexError(e.to_string()))
+    }
 
-        Ok(Self { index, schema })
+    /// Total size in bytes of the files in the index directory
+    ///
+    /// Includes segments not yet merged or garbage collected, so the figure can
+    /// drop after Tantivy tidies up following a commit.
+    pub fn index_size_bytes(&self) -> Result<u64, SearchError> {
+        let mut total = 0;
+        for entry in walkdir::WalkDir::new(&self.index_path) {
+            let entry = entry.map_err(|e| SearchError::IndexError(e.to_string()))?;
+            if entry.file_type().is_file() {
+                total += entry
+                    .metadata()
+                    .map_err(|e| SearchError::IndexError(e.to_string()))?
+                    .len();
+            }
+        }
+        Ok(total)
     }
 
     /// Get an index writer
+    ///
+    /// Fails with `IndexBusy` on a read-only engine, or when another writer
+    /// currently holds the index lock.
     pub fn get_writer(&self) -> Result<IndexWriter, SearchError> {
-        self.index
-            .writer(50_000_000)
-            .map_err(|e| SearchError::IndexError(e.to_string()))
+        if self.read_only {
+            return Err(SearchError::IndexBusy);
+        }
+        self.index.writer(50_000_000).map_err(|e| match e {
+            tantivy::TantivyError::LockFailure(LockError::LockBusy, _) => SearchError::IndexBusy,
+            e => SearchError::IndexError(e.to_string()),
+        })
+    }
+
+    /// Open an `IndexSession` holding a fresh writer
+    ///
+    /// Fails like `get_writer`, so also with `IndexBusy` while `with_writer`'s
+    /// pooled writer exists; call `release_writer` first. The session rolls
+    /// back on drop unless `set_commit_on_drop` is turned on.
+    pub fn session(&self) -> Result<IndexSession<'_>, SearchError> {
+        Ok(IndexSession {
+            engine: self,
+            writer: self.get_writer()?,
+            pending: false,
+            commit_on_drop: false,
+        })
+    }
+
+    /// Take the error of the last commit-on-drop `IndexSession` that failed
+    ///
+    /// `Drop` can't return it, so it is kept here, shared by all clones, until
+    /// taken. Only the most recent failure is kept.
+    pub fn take_session_error(&self) -> Option<SearchError> {
+        self.session_error.lock().ok().and_then(|mut error| error.take())
+    }
+
+    /// Whether the engine opened read-only because the index was locked
+    ///
+    /// A read-only engine stays read-only; reopen it once the other writer is
+    /// gone to index again.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Run `f` with the engine's long-lived writer, committing afterwards
+    ///
+    /// The writer is created on first use and kept for later calls, so bursts
+    /// of indexing don't reallocate its 50MB heap each time, and concurrent
+    /// callers queue on the lock instead of racing for Tantivy's single writer.
+    /// If `f` fails, its uncommitted changes are rolled back. While the pooled
+    /// writer exists `get_writer` will fail; call `release_writer` first.
+    pub fn with_writer<T, F>(&self, f: F) -> Result<T, SearchError>
+    where
+        F: FnOnce(&mut IndexWriter) -> Result<T, SearchError>,
+    {
+        self.run_with_writer(true, f)
+    }
+
+    /// `with_writer` for one-off changes, which doesn't keep a writer around
+    ///
+    /// A pooled writer is used if there is one; otherwise the writer created
+    /// for `f` is dropped afterwards, releasing the index lock so `get_writer`,
+    /// `session` and other processes can write again.
+    pub fn with_writer_once<T, F>(&self, f: F) -> Result<T, SearchError>
+    where
+        F: FnOnce(&mut IndexWriter) -> Result<T, SearchError>,
+    {
+        self.run_with_writer(false, f)
+    }
+
+    fn run_with_writer<T, F>(&self, keep: bool, f: F) -> Result<T, SearchError>
+    where
+        F: FnOnce(&mut IndexWriter) -> Result<T, SearchError>,
+    {
+        let mut pooled = self
+            .pooled_writer
+            .lock()
+            .map_err(|_| SearchError::IndexError("Writer lock poisoned".to_string()))?;
+        let created = pooled.is_none();
+        if created {
+            *pooled = Some(self.get_writer()?);
+        }
+        let writer = pooled.as_mut().expect("writer was just created");
+
+        let result = match f(writer) {
+            Ok(value) => self.commit(writer).map(|_| value),
+            Err(e) => writer
+                .rollback()
+                .map_err(|e| SearchError::IndexError(e.to_string()))
+                .and(Err(e)),
+        };
+        if created && !keep {
+            *pooled = None;
+        }
+        result
+    }
+
+    /// Drop the writer kept by `with_writer`, freeing its memory and index lock
+    pub fn release_writer(&self) {
+        if let Ok(mut pooled) = self.pooled_writer.lock() {
+            *pooled = None;
+        }
     }
 
     /// Index a note
+    ///
+    /// Returns the fields cut to the engine's `FieldLimits`.
+    #[allow(clippy::too_many_arguments)]
     pub fn index_note(
         &self,
         writer: &mut IndexWriter,
@@ -65,84 +719,712 @@ impl SearchEngine {
         tags: &[String],
         created_at: i64,
         updated_at: i64,
-    ) -> Result<(), SearchError> {
-        let id_field = self.schema.get_field("id").unwrap();
-        let title_field = self.schema.get_field("title").unwrap();
-        let content_field = self.schema.get_field("content").unwrap();
-        let tags_field = self.schema.get_field("tags").unwrap();
-        let created_field = self.schema.get_field("created_at").unwrap();
-        let updated_field = self.schema.get_field("updated_at").unwrap();
-
-        let tags_str = tags.join(" ");
-
-        let doc = doc!(
-            id_field => id,
-            title_field => title,
-            content_field => content,
-            tags_field => tags_str,
-            created_field => tantivy::DateTime::from_timestamp_secs(created_at),
-            updated_field => tantivy::DateTime::from_timestamp_secs(updated_at),
+    ) -> Result<Vec<FieldTruncation>, SearchError> {
+        let (doc, truncations) = build_document(
+            &self.schema,
+            &self.field_limits,
+            id,
+            title,
+            content,
+            tags,
+            created_at,
+            updated_at,
+            None,
         );
 
         writer
             .add_document(doc)
             .map_err(|e| SearchError::IndexError(e.to_string()))?;
 
-        Ok(())
+        Ok(truncations)
+    }
+
+    /// Index a batch of notes, preparing their documents in parallel
+    ///
+    /// Documents (tokenizable text, plus Markdown stripping when
+    /// `IndexConfig::separate_content_fields` is on) are built across rayon's
+    /// thread pool, then added in input order through `with_writer`, which
+    /// commits once at the end. Existing entries with the same IDs are
+    /// replaced, so the result is the same as upserting the notes one by one.
+    /// Only document preparation is spread out, so the gain depends on spare
+    /// cores; the ignored `bench_index_notes_parallel` test times it against
+    /// sequential upserts (`cargo test --release --features parallel --
+    /// --ignored --nocapture bench_index_notes_parallel`).
+    /// Returns the fields cut to the engine's `FieldLimits`, in input order.
+    #[cfg(feature = "parallel")]
+    pub fn index_notes_parallel(
+        &self,
+        notes: &[Note],
+    ) -> Result<Vec<FieldTruncation>, SearchError> {
+        use rayon::prelude::*;
+
+        let docs: Vec<(String, (TantivyDocument, Vec<FieldTruncation>))> = notes
+            .par_iter()
+            .map(|note| {
+                let id = note.id.to_string();
+                let doc = build_document(
+                    &self.schema,
+                    &self.field_limits,
+                    &id,
+                    &note.title,
+                    &note.indexed_content(),
+                    &note.tags,
+                    note.created_at.timestamp(),
+                    note.updated_at.timestamp(),
+                    Some(note.version),
+                );
+                (id, doc)
+            })
+            .collect();
+
+        self.with_writer(|writer| {
+            let mut truncations = Vec::new();
+            for (id, (doc, cut)) in docs {
+                self.delete_note(writer, &id);
+                writer
+                    .add_document(doc)
+                    .map_err(|e| SearchError::IndexError(e.to_string()))?;
+                truncations.extend(cut);
+            }
+            Ok(truncations)
+        })
+    }
+
+    /// Index a note, replacing any existing entry with the same ID
+    ///
+    /// Returns the fields cut to the engine's `FieldLimits`.
+    pub fn upsert_note(
+        &self,
+        writer: &mut IndexWriter,
+        note: &Note,
+    ) -> Result<Vec<FieldTruncation>, SearchError> {
+        self.delete_note(writer, &note.id.to_string());
+        self.add_note(writer, note)
+    }
+
+    /// Add a note's entry without removing any existing one
+    fn add_note(
+        &self,
+        writer: &mut IndexWriter,
+        note: &Note,
+    ) -> Result<Vec<FieldTruncation>, SearchError> {
+        let id = note.id.to_string();
+        let (doc, truncations) = build_document(
+            &self.schema,
+            &self.field_limits,
+            &id,
+            &note.title,
+            &note.indexed_content(),
+            &note.tags,
+            note.created_at.timestamp(),
+            note.updated_at.timestamp(),
+            Some(note.version),
+        );
+
+        writer
+            .add_document(doc)
+            .map_err(|e| SearchError::IndexError(e.to_string()))?;
+
+        Ok(truncations)
+    }
+
+    /// Apply a batch of changes in one transaction
+    ///
+    /// Changes run in order through the pooled writer (see `with_writer`) and
+    /// are committed together, or not at all if one fails. Updates and deletes
+    /// remove entries by ID term; adds don't, so adding an ID that is already
+    /// indexed leaves two entries. Returns the fields cut to the engine's
+    /// `FieldLimits`.
+    pub fn apply_changes(
+        &self,
+        changes: &[IndexChange],
+    ) -> Result<Vec<FieldTruncation>, SearchError> {
+        self.with_writer(|writer| {
+            let mut truncations = Vec::new();
+            for change in changes {
+                match change {
+                    IndexChange::Add(note) => truncations.extend(self.add_note(writer, note)?),
+                    IndexChange::Update(note) => {
+                        truncations.extend(self.upsert_note(writer, note)?)
+                    }
+                    IndexChange::Delete(id) => self.delete_note(writer, &id.to_string()),
+                }
+            }
+            Ok(truncations)
+        })
+    }
+
+    /// Look up a note's stored fields by ID, as of the last reload
+    pub fn get_by_id(&self, id: &str) -> Result<Option<IndexedNote>, SearchError> {
+        let id_field = self.schema.get_field("id").unwrap();
+        let query = TermQuery::new(
+            Term::from_field_text(id_field, id),
+            IndexRecordOption::Basic,
+        );
+        let searcher = self.reader.searcher();
+        let top_docs = searcher
+            .search(&query, &TopDocs::with_limit(1))
+            .map_err(|e| SearchError::SearchFailed(e.to_string()))?;
+
+        match top_docs.first() {
+            Some((_, address)) => self.indexed_note(&searcher, *address),
+            None => Ok(None),
+        }
+    }
+
+    /// The stored fields of every note in the index, as of the last reload
+    pub fn indexed_notes(&self) -> Result<Vec<IndexedNote>, SearchError> {
+        let searcher = self.reader.searcher();
+        let addresses = searcher
+            .search(&AllQuery, &DocSetCollector)
+            .map_err(|e| SearchError::SearchFailed(e.to_string()))?;
+
+        let mut notes = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            if let Some(note) = self.indexed_note(&searcher, address)? {
+                notes.push(note);
+            }
+        }
+        Ok(notes)
+    }
+
+    /// Read the stored fields of one document
+    fn indexed_note(
+        &self,
+        searcher: &tantivy::Searcher,
+        address: tantivy::DocAddress,
+    ) -> Result<Option<IndexedNote>, SearchError> {
+        let doc: TantivyDocument = searcher
+            .doc(address)
+            .map_err(|e| SearchError::SearchFailed(e.to_string()))?;
+        let field = |name| self.schema.get_field(name).unwrap();
+        let text = |name| {
+            doc.get_first(field(name))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        let date = |name| {
+            doc.get_first(field(name))
+                .and_then(|v| v.as_datetime())
+                .map(|d| d.into_timestamp_secs())
+                .unwrap_or_default()
+        };
+
+        Ok(text("id").map(|id| IndexedNote {
+            id,
+            title: text("title").unwrap_or_default(),
+            tags: text("tags")
+                .map(|tags| tags.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+            created_at: date("created_at"),
+            updated_at: date("updated_at"),
+            version: doc.get_first(field("version")).and_then(|v| v.as_u64()),
+        }))
+    }
+
+    /// Remove a note's entry from the index (takes effect on commit)
+    pub fn delete_note(&self, writer: &mut IndexWriter, id: &str) {
+        let id_field = self.schema.get_field("id").unwrap();
+        writer.delete_term(Term::from_field_text(id_field, id));
     }
 
-    /// Commit changes to the index
+    /// Commit changes to the index and reload the reader
     pub fn commit(&self, writer: &mut IndexWriter) -> Result<(), SearchError> {
+        #[cfg(feature = "metrics")]
+        let _timer = crate::metrics::Timer::start(crate::metrics::Operation::IndexCommit);
         writer
             .commit()
             .map_err(|e| SearchError::IndexError(e.to_string()))?;
-        Ok(())
+        self.reload()
     }
 
     /// Search for notes
     pub fn search(&self, query_str: &str, limit: usize) -> Result<Vec<(f32, String)>, SearchError> {
-        let reader: IndexReader = self
-            .index
-            .reader()
-            .map_err(|e| SearchError::IndexError(e.to_string()))?;
+        Ok(self
+            .search_detailed(query_str, limit)?
+            .into_iter()
+            .map(|r| (r.score, r.note_id))
+            .collect())
+    }
+
+    /// Search for notes, returning the stored title and tags alongside each hit
+    pub fn search_detailed(
+        &self,
+        query_str: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>, SearchError> {
+        let title_field = self.schema.get_field("title").unwrap();
+        let content_field = self.schema.get_field("content").unwrap();
+        let tags_field = self.schema.get_field("tags").unwrap();
+
+        Ok(self
+            .search_in_fields(
+                vec![title_field, content_field, tags_field],
+                query_str,
+                0,
+                limit,
+            )?
+            .0)
+    }
+
+    /// Search for notes, ranking recently updated ones higher
+    ///
+    /// Each hit's text relevance is multiplied by `1 + 0.5^(age / half_life)`,
+    /// where `age` is the time since its `updated_at`: a note updated just now
+    /// scores up to twice its plain relevance, one `half_life` old 1.5 times,
+    /// and a very old one close to its plain relevance. Hits therefore still
+    /// need to match the query, and a much better text match outranks a merely
+    /// fresher one. A zero `half_life` disables the boost.
+    pub fn search_recency_boosted(
+        &self,
+        query_str: &str,
+        limit: usize,
+        half_life: Duration,
+    ) -> Result<Vec<(f32, String)>, SearchError> {
+        let fields = ["title", "content", "tags"]
+            .iter()
+            .map(|name| self.schema.get_field(name).unwrap())
+            .collect();
+        let query = QueryParser::for_index(&self.index, fields)
+            .parse_query(query_str)
+            .map_err(|e| SearchError::ParseError(e.to_string()))?;
+        let limit = self.effective_limit(limit);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let half_life_secs = half_life.as_secs_f64();
+        let collector = TopDocs::with_limit(limit).tweak_score(move |segment: &SegmentReader| {
+            let updated_at = segment.fast_fields().date("updated_at").ok();
+            move |doc: DocId, score: Score| {
+                let updated = updated_at.as_ref().and_then(|column| column.first(doc));
+                let decay = match updated {
+                    Some(updated) if half_life_secs > 0.0 => {
+                        let age = (now - updated.into_timestamp_secs()).max(0) as f64;
+                        0.5f64.powf(age / half_life_secs)
+                    }
+                    _ => 0.0,
+                };
+                score * (1.0 + decay as f32)
+            }
+        });
+
+        let searcher = self.reader.searcher();
+        let top_docs = searcher
+            .search(&query, &collector)
+            .map_err(|e| SearchError::SearchFailed(e.to_string()))?;
 
-        let searcher = reader.searcher();
+        let id_field = self.schema.get_field("id").unwrap();
+        let mut results = Vec::with_capacity(top_docs.len());
+        for (score, address) in top_docs {
+            let doc: TantivyDocument = searcher
+                .doc(address)
+                .map_err(|e| SearchError::SearchFailed(e.to_string()))?;
+            if let Some(id) = doc.get_first(id_field).and_then(|v| v.as_str()) {
+                results.push((score, id.to_string()));
+            }
+        }
+        Ok(results)
+    }
+
+    /// List the notes carrying a tag, straight from the index
+    ///
+    /// Tags match exactly, or ignoring case if `set_tag_config` asked for it.
+    /// With `include_descendants`, notes tagged with a nested tag such as
+    /// `work/q3` also match `work`. Results come back in index order, with the
+    /// stored title and tags.
+    pub fn notes_with_tag(
+        &self,
+        tag: &str,
+        include_descendants: bool,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>, SearchError> {
+        let field = self.schema.get_field("tag_path").unwrap();
+        let query: Box<dyn Query> = if include_descendants || self.tag_config.case_insensitive {
+            let literal = if self.tag_config.case_insensitive {
+                case_insensitive_regex(tag)
+            } else {
+                regex::escape(tag)
+            };
+            let descendants = if include_descendants { "(/.*)?" } else { "" };
+            let pattern = format!("{}{}", literal, descendants);
+            Box::new(
+                RegexQuery::from_pattern(&pattern, field)
+                    .map_err(|e| SearchError::ParseError(e.to_string()))?,
+            )
+        } else {
+            Box::new(TermQuery::new(
+                Term::from_field_text(field, tag),
+                IndexRecordOption::Basic,
+            ))
+        };
+
+        Ok(self.collect_results(query.as_ref(), 0, limit)?.0)
+    }
+
+    /// Search for notes with a term matching a glob-style pattern
+    ///
+    /// `*` matches any run of characters and `?` a single character, so `proj*`
+    /// finds "project" and "projects". Titles, content and tags are searched.
+    /// Patterns must be one term with at least `MIN_WILDCARD_PREFIX` literal
+    /// characters before the first wildcard; broader patterns such as a bare
+    /// `*` would enumerate every term in the index and are rejected.
+    pub fn search_wildcard(
+        &self,
+        pattern: &str,
+        limit: usize,
+    ) -> Result<Vec<(f32, String)>, SearchError> {
+        let regex = wildcard_regex(pattern)?;
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for name in ["title", "content", "tags"] {
+            let field = self.schema.get_field(name).unwrap();
+            let query = RegexQuery::from_pattern(&regex, field)
+                .map_err(|e| SearchError::ParseError(e.to_string()))?;
+            clauses.push((Occur::Should, Box::new(query)));
+        }
+
+        Ok(self
+            .collect_results(&BooleanQuery::new(clauses), 0, limit)?
+            .0
+            .into_iter()
+            .map(|result| (result.score, result.note_id))
+            .collect())
+    }
+
+    /// Search and return an HTML snippet of each hit's content
+    ///
+    /// Snippets are excerpts with matched terms wrapped in `<b>` tags. Hits
+    /// whose terms only matched the title or tags get an empty snippet. Requires
+    /// an index created with `IndexConfig::store_content`; otherwise fails with
+    /// `SearchFailed`.
+    pub fn snippets(
+        &self,
+        query_str: &str,
+        limit: usize,
+    ) -> Result<Vec<(String, String)>, SearchError> {
+        let content_field = self.schema.get_field("content").unwrap();
+        if !self.schema.get_field_entry(content_field).is_stored() {
+            return Err(SearchError::SearchFailed(
+                "Content is not stored in this index".to_string(),
+            ));
+        }
+
+        let fields = ["title", "content", "tags"].map(|name| self.schema.get_field(name).unwrap());
+        let query = QueryParser::for_index(&self.index, fields.to_vec())
+            .parse_query(query_str)
+            .map_err(|e| SearchError::ParseError(e.to_string()))?;
+
+        let limit = self.effective_limit(limit);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let searcher = self.reader.searcher();
+        let generator = SnippetGenerator::create(&searcher, query.as_ref(), content_field)
+            .map_err(|e| SearchError::SearchFailed(e.to_string()))?;
+        let top_docs = searcher
+            .search(&query, &TopDocs::with_limit(limit))
+            .map_err(|e| SearchError::SearchFailed(e.to_string()))?;
+
+        let id_field = self.schema.get_field("id").unwrap();
+        let mut snippets = Vec::new();
+        for (_, address) in top_docs {
+            let doc: TantivyDocument = searcher
+                .doc(address)
+                .map_err(|e| SearchError::SearchFailed(e.to_string()))?;
+            if let Some(id) = doc.get_first(id_field).and_then(|v| v.as_str()) {
+                snippets.push((id.to_string(), generator.snippet_from_doc(&doc).to_html()));
+            }
+        }
+        Ok(snippets)
+    }
 
+    /// Fetch one page of search results along with the total match count
+    ///
+    /// Pages are zero-based: page `n` holds hits `n * page_size` up to
+    /// `(n + 1) * page_size` in score order. A page past the end is empty but
+    /// still reports `total`, as is any page starting beyond
+    /// `MAX_SEARCH_OFFSET`. `page_size` is subject to `set_max_results`.
+    pub fn search_page(
+        &self,
+        query_str: &str,
+        page: usize,
+        page_size: usize,
+    ) -> Result<SearchPage, SearchError> {
         let title_field = self.schema.get_field("title").unwrap();
         let content_field = self.schema.get_field("content").unwrap();
         let tags_field = self.schema.get_field("tags").unwrap();
 
-        let query_parser =
-            QueryParser::for_index(&self.index, vec![title_field, content_field, tags_field]);
+        let page_size = self.effective_limit(page_size);
+        let (results, total) = self.search_in_fields(
+            vec![title_field, content_field, tags_field],
+            query_str,
+            page.saturating_mul(page_size),
+            page_size,
+        )?;
+
+        Ok(SearchPage {
+            results,
+            total,
+            page,
+            page_size,
+        })
+    }
+
+    /// Search, then group hits by their primary tag (see `Note::primary_tag`)
+    ///
+    /// Groups are ordered by their best hit and keep score order within; hits
+    /// without tags go under `UNTAGGED_GROUP`.
+    pub fn search_grouped(
+        &self,
+        query_str: &str,
+        limit: usize,
+    ) -> Result<Vec<(String, Vec<SearchResult>)>, SearchError> {
+        let mut groups: Vec<(String, Vec<SearchResult>)> = Vec::new();
+        for result in self.search_detailed(query_str, limit)? {
+            let key = primary_tag(&result.tags)
+                .unwrap_or(UNTAGGED_GROUP)
+                .to_string();
+            match groups.iter_mut().find(|(name, _)| *name == key) {
+                Some((_, hits)) => hits.push(result),
+                None => groups.push((key, vec![result])),
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Search a single field only (e.g. titles)
+    pub fn search_field(
+        &self,
+        field: SearchField,
+        query_str: &str,
+        limit: usize,
+    ) -> Result<Vec<(f32, String)>, SearchError> {
+        let field = self.schema.get_field(field.field_name()).map_err(|_| {
+            SearchError::SearchFailed(format!(
+                "Field '{}' is not in this index",
+                field.field_name()
+            ))
+        })?;
+        Ok(self
+            .search_in_fields(vec![field], query_str, 0, limit)?
+            .0
+            .into_iter()
+            .map(|r| (r.score, r.note_id))
+            .collect())
+    }
+
+    /// Parse a query over the given default fields and collect the matching notes
+    ///
+    /// Skips the first `offset` hits; also returns the total number of matches.
+    fn search_in_fields(
+        &self,
+        fields: Vec<Field>,
+        query_str: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<SearchResult>, usize), SearchError> {
+        let query_parser = QueryParser::for_index(&self.index, fields);
 
         let query = query_parser
             .parse_query(query_str)
             .map_err(|e| SearchError::ParseError(e.to_string()))?;
 
+        self.collect_results(query.as_ref(), offset, limit)
+    }
+
+    /// Run a query and load the stored fields of the matching notes
+    fn collect_results(
+        &self,
+        query: &dyn Query,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<SearchResult>, usize), SearchError> {
+        #[cfg(feature = "metrics")]
+        let _timer = crate::metrics::Timer::start(crate::metrics::Operation::Search);
+        let limit = self.effective_limit(limit);
+
+        let searcher = self.reader.searcher();
+
+        // Tantivy sizes its buffer by `offset + limit` up front, so both are
+        // bounded by the actual hits before collecting any
+        let total = searcher
+            .search(query, &Count)
+            .map_err(|e| SearchError::SearchFailed(e.to_string()))?;
+        if offset >= total || offset > MAX_SEARCH_OFFSET {
+            return Ok((Vec::new(), total));
+        }
+        let limit = limit.min(total - offset);
+        if limit == 0 {
+            return Ok((Vec::new(), total));
+        }
+
         let top_docs = searcher
-            .search(&query, &TopDocs::with_limit(limit))
+            .search(query, &TopDocs::with_limit(limit).and_offset(offset))
             .map_err(|e| SearchError::SearchFailed(e.to_string()))?;
 
         let id_field = self.schema.get_field("id").unwrap();
+        let title_field = self.schema.get_field("title").unwrap();
+        let tags_field = self.schema.get_field("tags").unwrap();
         let mut results = Vec::new();
 
         for (score, doc_address) in top_docs {
             let retrieved_doc: TantivyDocument = searcher
                 .doc(doc_address)
                 .map_err(|e| SearchError::SearchFailed(e.to_string()))?;
+            let stored_text = |field| {
+                retrieved_doc
+                    .get_first(field)
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+            };
 
-            if let Some(id_value) = retrieved_doc.get_first(id_field) {
-                if let Some(id_str) = id_value.as_str() {
-                    results.push((score, id_str.to_string()));
-                }
+            if let Some(note_id) = stored_text(id_field) {
+                results.push(SearchResult {
+                    note_id,
+                    score,
+                    title: stored_text(title_field).unwrap_or_default(),
+                    tags: stored_text(tags_field)
+                        .map(|tags| tags.split_whitespace().map(str::to_string).collect())
+                        .unwrap_or_default(),
+                });
             }
         }
 
-        Ok(results)
+        Ok((results, total))
     }
 }
 
-#[cfg(test)]
+/// An index writer bound to its engine, with an optional commit on drop
+///
+/// Like a bare `IndexWriter`, a session discards uncommitted changes when
+/// dropped. With `set_commit_on_drop(true)` it commits them instead, for
+/// fire-and-forget indexing, unless it is dropped while unwinding from a
+/// panic, when the batch may be half-finished. Since `Drop` can't return an
+/// error, a failed commit is kept for `SearchEngine::take_session_error`.
+pub struct IndexSession<'a> {
+    engine: &'a SearchEngine,
+    writer: IndexWriter,
+    /// Changes made since the last commit
+    pending: bool,
+    commit_on_drop: bool,
+}
+
+impl IndexSession<'_> {
+    /// Commit pending changes when the session is dropped (off by default)
+    pub fn set_commit_on_drop(&mut self, commit_on_drop: bool) {
+        self.commit_on_drop = commit_on_drop;
+    }
+
+    /// Index a note, replacing any existing entry with the same ID
+    ///
+    /// Returns the fields cut to the engine's `FieldLimits`.
+    pub fn upsert_note(&mut self, note: &Note) -> Result<Vec<FieldTruncation>, SearchError> {
+        self.pending = true;
+        self.engine.upsert_note(&mut self.writer, note)
+    }
+
+    /// Remove a note's entry from the index (takes effect on commit)
+    pub fn delete_note(&mut self, id: &str) {
+        self.pending = true;
+        self.engine.delete_note(&mut self.writer, id);
+    }
+
+    /// Commit changes to the index and reload the engine's reader
+    pub fn commit(&mut self) -> Result<(), SearchError> {
+        self.engine.commit(&mut self.writer)?;
+        self.pending = false;
+        Ok(())
+    }
+}
+
+impl Drop for IndexSession<'_> {
+    fn drop(&mut self) {
+        if self.commit_on_drop && self.pending && !std::thread::panicking() {
+            if let Err(e) = self.commit() {
+                if let Ok(mut error) = self.engine.session_error.lock() {
+                    *error = Some(e);
+                }
+            }
+        }
+    }
+}
+
+/// A search hit labelled with the vault it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiSearchResult {
+    /// Vault whose index produced the hit
+    pub vault_id: Uuid,
+    /// Relevance score
+    pub score: f32,
+    /// ID of the matching note
+    pub note_id: String,
+}
+
+/// Combined results of a search across several vaults
+#[derive(Debug, Default)]
+pub struct MultiSearchOutcome {
+    /// Hits from all vaults, highest score first
+    pub results: Vec<MultiSearchResult>,
+    /// Vaults whose index could not be searched
+    pub errors: Vec<(Uuid, SearchError)>,
+}
+
+/// Searches several vault indexes at once
+#[derive(Default)]
+pub struct MultiSearchEngine {
+    engines: Vec<(Uuid, SearchEngine)>,
+}
+
+impl MultiSearchEngine {
+    /// Create an empty multi-vault search engine
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a vault's search engine
+    pub fn add_engine(&mut self, vault_id: Uuid, engine: SearchEngine) {
+        self.engines.push((vault_id, engine));
+    }
+
+    /// Number of vault indexes being searched
+    pub fn len(&self) -> usize {
+        self.engines.len()
+    }
+
+    /// Whether no vault indexes have been added
+    pub fn is_empty(&self) -> bool {
+        self.engines.is_empty()
+    }
+
+    /// Search every vault and merge the hits by score
+    ///
+    /// A failing index does not abort the search; its error is collected in the
+    /// outcome and the remaining vaults are still searched.
+    pub fn search_all(&self, query_str: &str, limit: usize) -> MultiSearchOutcome {
+        let mut outcome = MultiSearchOutcome::default();
+
+        for (vault_id, engine) in &self.engines {
+            match engine.search(query_str, limit) {
+                Ok(hits) => outcome
+                    .results
+                    .extend(hits.into_iter().map(|(score, note_id)| MultiSearchResult {
+                        vault_id: *vault_id,
+                        score,
+                        note_id,
+                    })),
+                Err(e) => outcome.errors.push((*vault_id, e)),
+            }
+        }
+
+        outcome.results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        outcome.results.truncate(limit);
+        outcome
+    }
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::tempdir;
@@ -173,4 +1455,939 @@ mod tests {
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].1, "note-1");
     }
+
+    #[test]
+    fn test_reload_after_commit() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+
+        assert!(engine.search("fresh", 10).unwrap().is_empty());
+
+        let mut writer = engine.get_writer().unwrap();
+        engine
+            .index_note(
+                &mut writer,
+                "note-2",
+                "Fresh Note",
+                "Just saved",
+                &[],
+                1640000000,
+                1640000000,
+            )
+            .unwrap();
+        writer.commit().unwrap();
+
+        engine.reload().unwrap();
+        let results = engine.search("fresh", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1, "note-2");
+    }
+
+    #[test]
+    fn test_multi_search_engine() {
+        let dir_a = tempdir().unwrap();
+        let dir_b = tempdir().unwrap();
+        let vault_a = Uuid::new_v4();
+        let vault_b = Uuid::new_v4();
+
+        let engine_a = SearchEngine::new(dir_a.path().to_path_buf()).unwrap();
+        let mut writer = engine_a.get_writer().unwrap();
+        engine_a
+            .index_note(&mut writer, "a-1", "Shared Topic", "alpha", &[], 0, 0)
+            .unwrap();
+        engine_a.commit(&mut writer).unwrap();
+
+        let engine_b = SearchEngine::new(dir_b.path().to_path_buf()).unwrap();
+        let mut writer = engine_b.get_writer().unwrap();
+        engine_b
+            .index_note(&mut writer, "b-1", "Shared Topic", "beta", &[], 0, 0)
+            .unwrap();
+        engine_b.commit(&mut writer).unwrap();
+
+        let mut multi = MultiSearchEngine::new();
+        multi.add_engine(vault_a, engine_a);
+        multi.add_engine(vault_b, engine_b);
+
+        let outcome = multi.search_all("shared", 10);
+        assert!(outcome.errors.is_empty());
+        assert_eq!(outcome.results.len(), 2);
+        assert!(outcome
+            .results
+            .iter()
+            .any(|r| r.vault_id == vault_a && r.note_id == "a-1"));
+        assert!(outcome
+            .results
+            .iter()
+            .any(|r| r.vault_id == vault_b && r.note_id == "b-1"));
+
+        // A query error is collected per vault rather than aborting
+        let outcome = multi.search_all("title:(", 10);
+        assert_eq!(outcome.errors.len(), 2);
+    }
+
+    #[test]
+    fn test_oversized_title_truncated_in_index() {
+        let temp_dir = tempdir().unwrap();
+        let mut engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        engine.set_field_limits(FieldLimits {
+            max_title_bytes: 64,
+            ..FieldLimits::default()
+        });
+        let title = format!("Kickoff agenda {} tailword", "filler ".repeat(200_000));
+        let note = Note::new(title.clone(), "body".to_string(), vec!["é".repeat(600)]);
+
+        let mut writer = engine.get_writer().unwrap();
+        let truncations = engine.upsert_note(&mut writer, &note).unwrap();
+        engine.commit(&mut writer).unwrap();
+
+        let fields: Vec<&str> = truncations.iter().map(|t| t.field).collect();
+        assert_eq!(fields, vec!["title", "tag"]);
+        assert_eq!(truncations[0].original_bytes, title.len());
+        assert_eq!(truncations[0].indexed_bytes, 64);
+        assert!(truncations[1].to_string().starts_with("Truncated tag"));
+
+        let indexed = engine.get_by_id(&note.id.to_string()).unwrap().unwrap();
+        assert_eq!(indexed.title.len(), 64);
+        assert!(title.starts_with(&indexed.title));
+        // 1200-byte tag cut to the 1024-byte default on a character boundary
+        assert_eq!(indexed.tags[0].len(), 1024);
+        assert_eq!(note.title, title);
+
+        assert_eq!(engine.search("kickoff", 10).unwrap().len(), 1);
+        assert!(engine.search("tailword", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_index_session_commit_on_drop() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let kept = Note::new("Kept".to_string(), "body".to_string(), vec![]);
+        let lost = Note::new("Lost".to_string(), "body".to_string(), vec![]);
+
+        engine.session().unwrap().upsert_note(&lost).unwrap();
+        assert!(engine.get_by_id(&lost.id.to_string()).unwrap().is_none());
+
+        {
+            let mut session = engine.session().unwrap();
+            session.set_commit_on_drop(true);
+            session.upsert_note(&kept).unwrap();
+        }
+        assert!(engine.get_by_id(&kept.id.to_string()).unwrap().is_some());
+        assert!(engine.take_session_error().is_none());
+
+        // A batch interrupted by a panic is discarded, not committed
+        let interrupted = Note::new("Interrupted".to_string(), "body".to_string(), vec![]);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut session = engine.session().unwrap();
+            session.set_commit_on_drop(true);
+            session.upsert_note(&interrupted).unwrap();
+            panic!("indexing failed halfway");
+        }));
+        assert!(result.is_err());
+        assert!(engine
+            .get_by_id(&interrupted.id.to_string())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_notes_with_tag_case_insensitive() {
+        let temp_dir = tempdir().unwrap();
+        let mut engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        for (id, tag) in [("a", "Work"), ("b", "work/Q3"), ("c", "home")] {
+            engine
+                .index_note(&mut writer, id, id, "body", &[tag.to_string()], 0, 0)
+                .unwrap();
+        }
+        engine.commit(&mut writer).unwrap();
+
+        assert_eq!(engine.notes_with_tag("work", false, 10).unwrap().len(), 0);
+        engine.set_tag_config(TagConfig {
+            case_insensitive: true,
+        });
+        let exact = engine.notes_with_tag("WORK", false, 10).unwrap();
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact[0].tags, vec!["Work"]);
+        assert_eq!(engine.notes_with_tag("work", true, 10).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_max_results_cap() {
+        let temp_dir = tempdir().unwrap();
+        let mut engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        for i in 0..5 {
+            engine
+                .index_note(&mut writer, &format!("note-{}", i), "Capped", "", &[], 0, 0)
+                .unwrap();
+        }
+        engine.commit(&mut writer).unwrap();
+
+        assert_eq!(engine.search("capped", 100).unwrap().len(), 5);
+        assert!(engine.search("capped", 0).unwrap().is_empty());
+
+        engine.set_max_results(Some(2));
+        assert_eq!(engine.search("capped", 100).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_search_field() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        engine
+            .index_note(
+                &mut writer,
+                "note-1",
+                "Groceries",
+                "Remember the artichokes",
+                &["errands".to_string()],
+                0,
+                0,
+            )
+            .unwrap();
+        engine.commit(&mut writer).unwrap();
+
+        assert_eq!(
+            engine
+                .search_field(SearchField::Content, "artichokes", 10)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(engine
+            .search_field(SearchField::Title, "artichokes", 10)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            engine
+                .search_field(SearchField::Tags, "errands", 10)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_separate_content_fields() {
+        let temp_dir = tempdir().unwrap();
+        let config = IndexConfig {
+            separate_content_fields: true,
+            ..IndexConfig::default()
+        };
+        let engine = SearchEngine::new_with_config(temp_dir.path().to_path_buf(), &config).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        engine
+            .index_note(
+                &mut writer,
+                "note-1",
+                "Links",
+                "Read [the guide](https://example.org/handbook) today",
+                &[],
+                0,
+                0,
+            )
+            .unwrap();
+        engine.commit(&mut writer).unwrap();
+
+        let count = |field, query| engine.search_field(field, query, 10).unwrap().len();
+        assert_eq!(count(SearchField::ContentRaw, "handbook"), 1);
+        assert_eq!(count(SearchField::ContentText, "handbook"), 0);
+        assert_eq!(count(SearchField::ContentText, "guide"), 1);
+
+        // Indexes without the extension report the missing field
+        let plain_dir = tempdir().unwrap();
+        let plain = SearchEngine::new(plain_dir.path().to_path_buf()).unwrap();
+        assert!(plain
+            .search_field(SearchField::ContentRaw, "handbook", 10)
+            .is_err());
+    }
+
+    #[test]
+    fn test_reopen_with_different_config_is_rejected() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        let note = Note::new("Dated".to_string(), "body".to_string(), vec![]);
+        engine.upsert_note(&mut writer, &note).unwrap();
+        engine.commit(&mut writer).unwrap();
+        drop(writer);
+        drop(engine);
+
+        let config = IndexConfig {
+            separate_content_fields: true,
+            ..IndexConfig::default()
+        };
+        assert!(matches!(
+            SearchEngine::new_with_config(temp_dir.path().to_path_buf(), &config),
+            Err(SearchError::SchemaMismatch)
+        ));
+
+        // The original configuration still opens and searches the index
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        assert_eq!(engine.search("body", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_with_writer_batches() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+
+        for batch in 0..3 {
+            engine
+                .with_writer(|writer| {
+                    for i in 0..4 {
+                        let note = Note::new(
+                            format!("Batch {} note {}", batch, i),
+                            "pooled".to_string(),
+                            vec![],
+                        );
+                        engine.upsert_note(writer, &note)?;
+                    }
+                    Ok(())
+                })
+                .unwrap();
+        }
+        assert_eq!(engine.search("pooled", 100).unwrap().len(), 12);
+
+        // A failing batch leaves nothing behind
+        let result: Result<(), SearchError> = engine.with_writer(|writer| {
+            let note = Note::new("Doomed".to_string(), "pooled".to_string(), vec![]);
+            engine.upsert_note(writer, &note)?;
+            Err(SearchError::IndexError("abort".to_string()))
+        });
+        assert!(result.is_err());
+        engine.with_writer(|_| Ok(())).unwrap();
+        assert_eq!(engine.search("pooled", 100).unwrap().len(), 12);
+
+        // The pooled writer holds the index lock until released
+        assert!(engine.get_writer().is_err());
+        engine.release_writer();
+        assert!(engine.get_writer().is_ok());
+    }
+
+    #[test]
+    fn test_search_grouped() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        let notes = [
+            ("w1", "rust rust rust", vec!["work".to_string()]),
+            (
+                "h1",
+                "rust rust",
+                vec!["home".to_string(), "work".to_string()],
+            ),
+            (
+                "w2",
+                "rust and other words to dilute the score",
+                vec!["work".to_string()],
+            ),
+            ("u1", "rust", vec![]),
+            ("b1", "rust", vec![" ".to_string(), "home".to_string()]),
+        ];
+        for (id, content, tags) in &notes {
+            engine
+                .index_note(&mut writer, id, id, content, tags, 0, 0)
+                .unwrap();
+        }
+        engine.commit(&mut writer).unwrap();
+
+        let groups = engine.search_grouped("rust", 10).unwrap();
+        let names: Vec<&str> = groups.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names.len(), 3);
+        assert!(names.contains(&"work") && names.contains(&"home"));
+        assert!(names.contains(&UNTAGGED_GROUP));
+
+        let work = &groups.iter().find(|(name, _)| name == "work").unwrap().1;
+        let work_ids: Vec<&str> = work.iter().map(|r| r.note_id.as_str()).collect();
+        assert_eq!(work_ids, vec!["w1", "w2"]);
+        assert!(work[0].score >= work[1].score);
+
+        let home = &groups.iter().find(|(name, _)| name == "home").unwrap().1;
+        assert_eq!(home[0].tags, vec!["home".to_string(), "work".to_string()]);
+        assert_eq!(home[0].title, "h1");
+        // A blank first tag is skipped, as in `Note::primary_tag`
+        assert!(home.iter().any(|r| r.note_id == "b1"));
+    }
+
+    #[test]
+    fn test_notes_with_tag() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        let notes = [
+            ("a", vec!["work"]),
+            ("b", vec!["work/q3", "home"]),
+            ("c", vec!["home"]),
+            ("d", vec!["workshop"]),
+            ("e", vec!["Work"]),
+        ];
+        for (id, tags) in &notes {
+            let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+            engine
+                .index_note(&mut writer, id, id, "body", &tags, 0, 0)
+                .unwrap();
+        }
+        engine.commit(&mut writer).unwrap();
+
+        let ids = |tag: &str, descendants: bool| {
+            let mut ids: Vec<String> = engine
+                .notes_with_tag(tag, descendants, 10)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.note_id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids("work", false), vec!["a"]);
+        assert_eq!(ids("work", true), vec!["a", "b"]);
+        assert_eq!(ids("home", false), vec!["b", "c"]);
+        assert_eq!(ids("work/q3", true), vec!["b"]);
+        assert!(ids("missing", true).is_empty());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_index_notes_parallel() {
+        let temp_dir = tempdir().unwrap();
+        let config = IndexConfig {
+            separate_content_fields: true,
+            ..IndexConfig::default()
+        };
+        let engine = SearchEngine::new_with_config(temp_dir.path().to_path_buf(), &config).unwrap();
+
+        let notes: Vec<Note> = (0..2000)
+            .map(|i| {
+                Note::new(
+                    format!("Note {}", i),
+                    format!("# Batch\n\nmarker{} shared **body**", i),
+                    vec![format!("group{}", i % 7)],
+                )
+            })
+            .collect();
+        engine.index_notes_parallel(&notes).unwrap();
+
+        assert_eq!(engine.search("shared", 5000).unwrap().len(), 2000);
+        for i in [0, 999, 1999] {
+            let hits = engine.search(&format!("marker{}", i), 10).unwrap();
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].1, notes[i].id.to_string());
+        }
+
+        // Re-indexing replaces rather than duplicates
+        engine.index_notes_parallel(&notes[..10]).unwrap();
+        assert_eq!(engine.search("shared", 5000).unwrap().len(), 2000);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    #[ignore = "timing benchmark, run in release mode on a multi-core machine"]
+    fn bench_index_notes_parallel() {
+        let config = IndexConfig {
+            separate_content_fields: true,
+            ..IndexConfig::default()
+        };
+        let body: String = (0..800)
+            .map(|i| format!("**word{}** [link](https://example.com/{}) text ", i % 97, i))
+            .collect();
+        let notes: Vec<Note> = (0..2000)
+            .map(|i| Note::new(format!("Note {}", i), body.clone(), vec!["bench".to_string()]))
+            .collect();
+
+        let time = |parallel: bool| {
+            let temp_dir = tempdir().unwrap();
+            let engine =
+                SearchEngine::new_with_config(temp_dir.path().to_path_buf(), &config).unwrap();
+            let start = std::time::Instant::now();
+            if parallel {
+                engine.index_notes_parallel(&notes).unwrap();
+            } else {
+                engine
+                    .with_writer(|writer| {
+                        for note in &notes {
+                            engine.upsert_note(writer, note)?;
+                        }
+                        Ok(())
+                    })
+                    .unwrap();
+            }
+            start.elapsed()
+        };
+        let sequential = time(false);
+        let parallel = time(true);
+        println!(
+            "{} threads: sequential {:?}, parallel {:?}, speedup {:.2}x",
+            rayon::current_num_threads(),
+            sequential,
+            parallel,
+            sequential.as_secs_f64() / parallel.as_secs_f64()
+        );
+    }
+
+    #[test]
+    fn test_schema_json() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+
+        let fields: serde_json::Value = serde_json::from_str(&engine.schema_json()).unwrap();
+        let field_type = |name: &str| {
+            fields
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|f| f["name"] == name)
+                .map(|f| f["type"].as_str().unwrap().to_string())
+        };
+        for name in ["id", "title", "content", "tags"] {
+            assert_eq!(field_type(name).as_deref(), Some("text"), "{}", name);
+        }
+        for name in ["created_at", "updated_at"] {
+            assert_eq!(field_type(name).as_deref(), Some("date"), "{}", name);
+        }
+        assert_eq!(field_type("content_raw"), None);
+
+        assert_eq!(
+            serde_json::to_string_pretty(&SearchEngine::default_schema()).unwrap(),
+            engine.schema_json()
+        );
+    }
+
+    #[test]
+    fn test_search_recency_boosted() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let year = 365 * 24 * 3600;
+        // Identical text, so plain relevance ties
+        for (id, updated_at) in [("stale", now - 2 * year), ("fresh", now - 60)] {
+            engine
+                .index_note(
+                    &mut writer,
+                    id,
+                    "Roadmap",
+                    "quarterly roadmap",
+                    &[],
+                    0,
+                    updated_at,
+                )
+                .unwrap();
+        }
+        engine
+            .index_note(&mut writer, "other", "Groceries", "milk", &[], 0, now)
+            .unwrap();
+        engine.commit(&mut writer).unwrap();
+
+        let day = Duration::from_secs(24 * 3600);
+        let results = engine
+            .search_recency_boosted("roadmap", 10, day * 30)
+            .unwrap();
+        let ids: Vec<_> = results.iter().map(|(_, id)| id.as_str()).collect();
+        assert_eq!(ids, vec!["fresh", "stale"]);
+        assert!(results[0].0 > results[1].0);
+
+        let plain = engine
+            .search_recency_boosted("roadmap", 10, Duration::ZERO)
+            .unwrap();
+        assert_eq!(plain[0].0, plain[1].0);
+    }
+
+    #[test]
+    fn test_search_page() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        for i in 0..23 {
+            let note = Note::new(format!("Note {}", i), "paged".to_string(), vec![]);
+            engine.upsert_note(&mut writer, &note).unwrap();
+        }
+        engine.commit(&mut writer).unwrap();
+
+        let mut seen = Vec::new();
+        for page in 0..3 {
+            let result = engine.search_page("paged", page, 10).unwrap();
+            assert_eq!(result.total, 23);
+            assert_eq!(result.results.len(), if page < 2 { 10 } else { 3 });
+            seen.extend(result.results.into_iter().map(|r| r.note_id));
+        }
+        let mut all: Vec<String> = engine
+            .search("paged", 100)
+            .unwrap()
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect();
+        let mut unique = seen.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), seen.len());
+        all.sort();
+        assert_eq!(unique, all);
+
+        let past_end = engine.search_page("paged", 5, 10).unwrap();
+        assert!(past_end.results.is_empty());
+        assert_eq!(past_end.total, 23);
+        let overflow = engine.search_page("paged", usize::MAX, 10).unwrap();
+        assert!(overflow.results.is_empty());
+        assert_eq!(overflow.total, 23);
+        // Deep pages and huge sizes are bounded by the hits, not allocated up front
+        let deep = engine.search_page("paged", i32::MAX as usize, 1000).unwrap();
+        assert!(deep.results.is_empty());
+        assert_eq!(deep.total, 23);
+        let huge = engine.search_page("paged", 0, usize::MAX).unwrap();
+        assert_eq!(huge.results.len(), 23);
+    }
+
+    #[test]
+    fn test_search_wildcard() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        for (title, content) in [
+            ("Project plan", "milestones"),
+            ("Side projects", "ideas"),
+            ("Groceries", "proper bread"),
+            ("Travel", "passport"),
+        ] {
+            let note = Note::new(title.to_string(), content.to_string(), vec![]);
+            engine.upsert_note(&mut writer, &note).unwrap();
+        }
+        engine.commit(&mut writer).unwrap();
+
+        assert_eq!(engine.search_wildcard("proj*", 10).unwrap().len(), 2);
+        assert_eq!(engine.search_wildcard("Pro*", 10).unwrap().len(), 3);
+        assert_eq!(engine.search_wildcard("pass?ort", 10).unwrap().len(), 1);
+        assert!(engine.search_wildcard("proj", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_wildcard_rejects_broad_patterns() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        for pattern in ["*", "**", "p*", "?roject", "two words*"] {
+            assert!(
+                matches!(
+                    engine.search_wildcard(pattern, 10),
+                    Err(SearchError::ParseError(_))
+                ),
+                "{} was accepted",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_by_id() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        let mut note = Note::new("Lookup".to_string(), "body".to_string(), vec!["a".into()]);
+        note.version = 4;
+        engine.upsert_note(&mut writer, &note).unwrap();
+        engine
+            .index_note(&mut writer, "plain", "Plain", "", &[], 10, 20)
+            .unwrap();
+        engine.commit(&mut writer).unwrap();
+
+        let indexed = engine.get_by_id(&note.id.to_string()).unwrap().unwrap();
+        assert_eq!(indexed.title, "Lookup");
+        assert_eq!(indexed.tags, vec!["a".to_string()]);
+        assert_eq!(indexed.updated_at, note.updated_at.timestamp());
+        assert_eq!(indexed.version, Some(4));
+
+        let plain = engine.get_by_id("plain").unwrap().unwrap();
+        assert_eq!((plain.created_at, plain.version), (10, None));
+        assert!(engine.get_by_id("missing").unwrap().is_none());
+        assert_eq!(engine.indexed_notes().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_store_content_enables_snippets() {
+        let open = |store_content: bool| {
+            let temp_dir = tempdir().unwrap();
+            let config = IndexConfig {
+                store_content,
+                ..IndexConfig::default()
+            };
+            let engine =
+                SearchEngine::new_with_config(temp_dir.path().to_path_buf(), &config).unwrap();
+            let mut writer = engine.get_writer().unwrap();
+            let note = Note::new(
+                "Recipe".to_string(),
+                "Knead the dough for ten minutes".to_string(),
+                vec![],
+            );
+            engine.upsert_note(&mut writer, &note).unwrap();
+            engine.commit(&mut writer).unwrap();
+            (temp_dir, engine, note.id.to_string())
+        };
+
+        let (_dir, stored, id) = open(true);
+        let snippets = stored.snippets("dough", 10).unwrap();
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].0, id);
+        assert!(snippets[0].1.contains("<b>dough</b>"));
+
+        let (_dir, unstored, _) = open(false);
+        assert!(matches!(
+            unstored.snippets("dough", 10),
+            Err(SearchError::SearchFailed(_))
+        ));
+        assert_eq!(unstored.search("dough", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_index_positions_toggle() {
+        let temp_dir = tempdir().unwrap();
+        let config = IndexConfig {
+            index_positions: false,
+            ..IndexConfig::default()
+        };
+        let engine = SearchEngine::new_with_config(temp_dir.path().to_path_buf(), &config).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        let note = Note::new("Phrase".to_string(), "red green blue".to_string(), vec![]);
+        engine.upsert_note(&mut writer, &note).unwrap();
+        engine.commit(&mut writer).unwrap();
+
+        assert_eq!(engine.search("green", 10).unwrap().len(), 1);
+        assert!(matches!(
+            engine.search("\"red green\"", 10),
+            Err(SearchError::ParseError(_))
+        ));
+
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+        engine.upsert_note(&mut writer, &note).unwrap();
+        engine.commit(&mut writer).unwrap();
+        assert_eq!(engine.search("\"red green\"", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_apply_changes() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let kept = Note::new("Kept".to_string(), "alpha".to_string(), vec![]);
+        let mut edited = Note::new("Edited".to_string(), "alpha".to_string(), vec![]);
+        let removed = Note::new("Removed".to_string(), "alpha".to_string(), vec![]);
+        engine
+            .apply_changes(&[
+                IndexChange::Add(kept.clone()),
+                IndexChange::Add(edited.clone()),
+                IndexChange::Add(removed.clone()),
+            ])
+            .unwrap();
+        assert_eq!(engine.search("alpha", 10).unwrap().len(), 3);
+
+        edited.update(edited.title.clone(), "beta".to_string(), vec![]);
+        let added = Note::new("Added".to_string(), "beta".to_string(), vec![]);
+        engine
+            .apply_changes(&[
+                IndexChange::Update(edited.clone()),
+                IndexChange::Delete(removed.id),
+                IndexChange::Add(added.clone()),
+            ])
+            .unwrap();
+
+        let ids = |query| {
+            let mut ids: Vec<String> = engine
+                .search(query, 10)
+                .unwrap()
+                .into_iter()
+                .map(|(_, id)| id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids("alpha"), vec![kept.id.to_string()]);
+        let mut expected = vec![edited.id.to_string(), added.id.to_string()];
+        expected.sort();
+        assert_eq!(ids("beta"), expected);
+        assert_eq!(engine.indexed_notes().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_search_keywords_replace_content() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut note = Note::new(
+            "Medical".to_string(),
+            "diagnosis details".to_string(),
+            vec![],
+        );
+        note.search_keywords = vec!["checkup".to_string(), "clinic".to_string()];
+        let plain = Note::new("Plain".to_string(), "diagnosis".to_string(), vec![]);
+        let mut writer = engine.get_writer().unwrap();
+        engine.upsert_note(&mut writer, &note).unwrap();
+        engine.upsert_note(&mut writer, &plain).unwrap();
+        engine.commit(&mut writer).unwrap();
+
+        assert_eq!(
+            engine.search("clinic", 10).unwrap()[0].1,
+            note.id.to_string()
+        );
+        let hits = engine.search("diagnosis", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].1, plain.id.to_string());
+    }
+
+    #[test]
+    fn test_clones_index_and_search_concurrently() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let indexer = engine.clone();
+        let searcher = engine.clone();
+
+        let writer_thread = std::thread::spawn(move || {
+            for batch in 0..5 {
+                let notes: Vec<IndexChange> = (0..10)
+                    .map(|i| {
+                        let title = format!("Concurrent {}-{}", batch, i);
+                        IndexChange::Add(Note::new(title, String::new(), vec![]))
+                    })
+                    .collect();
+                indexer.apply_changes(&notes).unwrap();
+            }
+        });
+
+        let search_thread = std::thread::spawn(move || {
+            let mut seen = 0;
+            for _ in 0..1000 {
+                searcher.reload().unwrap();
+                seen = searcher.search("concurrent", 100).unwrap().len();
+                // Commits land in whole batches
+                assert_eq!(seen % 10, 0);
+                if seen == 50 {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            seen
+        });
+
+        writer_thread.join().unwrap();
+        let seen = search_thread.join().unwrap();
+        engine.reload().unwrap();
+        assert_eq!(engine.search("concurrent", 100).unwrap().len(), 50);
+        assert_eq!(seen, 50);
+    }
+
+    #[test]
+    fn test_index_size_bytes() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let changes: Vec<IndexChange> = (0..20)
+            .map(|i| IndexChange::Add(Note::new(format!("Sized {}", i), "x".repeat(500), vec![])))
+            .collect();
+        engine.apply_changes(&changes).unwrap();
+        engine.release_writer();
+
+        let size = engine.index_size_bytes().unwrap();
+        let manual: u64 = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().metadata().unwrap())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum();
+        assert!(size > 0);
+        assert_eq!(size, manual);
+    }
+
+    #[test]
+    fn test_locked_index_opens_read_only() {
+        let temp_dir = tempdir().unwrap();
+        let primary = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = primary.get_writer().unwrap();
+        let note = Note::new("Shared".to_string(), "window".to_string(), vec![]);
+        primary.upsert_note(&mut writer, &note).unwrap();
+        primary.commit(&mut writer).unwrap();
+
+        // The primary still holds its writer, as a long-running window would
+        let secondary = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        assert!(secondary.is_read_only());
+        assert_eq!(secondary.search("window", 10).unwrap().len(), 1);
+        assert!(matches!(
+            secondary.get_writer(),
+            Err(SearchError::IndexBusy)
+        ));
+        assert!(matches!(
+            secondary.with_writer(|_| Ok(())),
+            Err(SearchError::IndexBusy)
+        ));
+
+        let options = OpenOptions {
+            read_only_when_locked: false,
+        };
+        assert!(matches!(
+            SearchEngine::new_with_options(
+                temp_dir.path().to_path_buf(),
+                &IndexConfig::default(),
+                &options
+            ),
+            Err(SearchError::IndexBusy)
+        ));
+
+        drop(writer);
+        assert!(!SearchEngine::new(temp_dir.path().to_path_buf())
+            .unwrap()
+            .is_read_only());
+    }
+
+    #[test]
+    fn test_upsert_and_delete_note() {
+        let temp_dir = tempdir().unwrap();
+        let engine = SearchEngine::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut writer = engine.get_writer().unwrap();
+
+        let mut note = Note::new("Original".to_string(), "walrus".to_string(), vec![]);
+        engine.upsert_note(&mut writer, &note).unwrap();
+        engine.commit(&mut writer).unwrap();
+
+        note.update("Original".to_string(), "narwhal".to_string(), vec![]);
+        engine.upsert_note(&mut writer, &note).unwrap();
+        engine.commit(&mut writer).unwrap();
+
+        assert!(engine.search("walrus", 10).unwrap().is_empty());
+        let results = engine.search("narwhal", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1, note.id.to_string());
+
+        engine.delete_note(&mut writer, &note.id.to_string());
+        engine.commit(&mut writer).unwrap();
+        assert!(engine.search("narwhal", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_custom_tokenizer() {
+        use tantivy::tokenizer::{LowerCaser, WhitespaceTokenizer};
+
+        let temp_dir = tempdir().unwrap();
+        let analyzer = TextAnalyzer::builder(WhitespaceTokenizer::default())
+            .filter(LowerCaser)
+            .build();
+        let engine =
+            SearchEngine::new_with_tokenizer(temp_dir.path().to_path_buf(), "ws", analyzer)
+                .unwrap();
+
+        let mut writer = engine.get_writer().unwrap();
+        engine
+            .index_note(
+                &mut writer,
+                "note-1",
+                "Hello-World",
+                "plain words",
+                &[],
+                0,
+                0,
+            )
+            .unwrap();
+        engine.commit(&mut writer).unwrap();
+
+        // Whitespace tokenization keeps the hyphenated word whole
+        assert_eq!(engine.search("hello-world", 10).unwrap().len(), 1);
+        assert!(engine.search("hello", 10).unwrap().is_empty());
+        assert_eq!(engine.search("words", 10).unwrap().len(), 1);
+    }
 }
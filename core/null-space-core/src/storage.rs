@@ -2,8 +2,11 @@
 //!
 //! Handles reading and writing notes to the filesystem.
 
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use std::fs;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use thiserror::Error;
 use walkdir::WalkDir;
 
@@ -17,6 +20,39 @@ pub enum StorageError {
     NotFound(String),
 }
 
+/// Payloads smaller than this are stored uncompressed by `write_file_compressed`
+pub const COMPRESSION_THRESHOLD: usize = 4 * 1024;
+
+/// Leading bytes of every gzip stream, used to detect compressed files
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Gzip `data` if it is at least `COMPRESSION_THRESHOLD` bytes long
+///
+/// Smaller payloads are returned unchanged; `decompress` undoes either.
+pub fn compress(data: &[u8]) -> Result<Vec<u8>, StorageError> {
+    if data.len() < COMPRESSION_THRESHOLD {
+        return Ok(data.to_vec());
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Gunzip `data` if it starts with the gzip magic bytes, else return it as-is
+///
+/// Only meaningful for plaintext formats that can't start with those bytes,
+/// such as JSON.
+pub fn decompress(data: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+    if !data.starts_with(&GZIP_MAGIC) {
+        return Ok(data);
+    }
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(data.as_slice()).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
 /// File storage manager
 pub struct FileStorage {
     base_path: PathBuf,
@@ -46,6 +82,31 @@ impl FileStorage {
         Ok(())
     }
 
+    /// Write data to a file and flush it to stable storage
+    ///
+    /// Both the file and its parent directory are fsynced, so a successful return
+    /// means the data survives a crash or power loss. This is noticeably slower
+    /// than `write_file`.
+    pub fn write_file_durable(&self, relative_path: &str, data: &[u8]) -> Result<(), StorageError> {
+        let full_path = self.get_path(relative_path);
+
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = fs::File::create(&full_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+
+        // Persist the directory entry itself
+        #[cfg(unix)]
+        if let Some(parent) = full_path.parent() {
+            fs::File::open(parent)?.sync_all()?;
+        }
+
+        Ok(())
+    }
+
     /// Read data from a file
     pub fn read_file(&self, relative_path: &str) -> Result<Vec<u8>, StorageError> {
         let full_path = self.get_path(relative_path);
@@ -57,6 +118,30 @@ impl FileStorage {
         Ok(fs::read(full_path)?)
     }
 
+    /// Write data to a file, gzip-compressing it if it is large enough to benefit
+    ///
+    /// Payloads under `COMPRESSION_THRESHOLD` are written as-is. Read the file
+    /// back with `read_file_compressed`, which tells the two apart by the gzip
+    /// magic bytes. Don't use this for encrypted data: ciphertext doesn't
+    /// compress, so it would only cost time. Compress the plaintext with
+    /// `compress` before encrypting it instead.
+    pub fn write_file_compressed(
+        &self,
+        relative_path: &str,
+        data: &[u8],
+    ) -> Result<(), StorageError> {
+        self.write_file(relative_path, &compress(data)?)
+    }
+
+    /// Read a file written by `write_file_compressed`, decompressing if needed
+    ///
+    /// Files without the gzip magic bytes, including any written with
+    /// `write_file`, are returned unchanged. Only use this for plaintext:
+    /// ciphertext can start with the magic bytes by chance.
+    pub fn read_file_compressed(&self, relative_path: &str) -> Result<Vec<u8>, StorageError> {
+        decompress(self.read_file(relative_path)?)
+    }
+
     /// Delete a file
     pub fn delete_file(&self, relative_path: &str) -> Result<(), StorageError> {
         let full_path = self.get_path(relative_path);
@@ -69,6 +154,31 @@ impl FileStorage {
         Ok(())
     }
 
+    /// Move a file, replacing any file already at `to`
+    ///
+    /// Both paths must be on the same filesystem, where the rename is atomic.
+    pub fn rename_file(&self, from: &str, to: &str) -> Result<(), StorageError> {
+        let from_path = self.get_path(from);
+
+        if !from_path.exists() {
+            return Err(StorageError::NotFound(from.to_string()));
+        }
+
+        fs::rename(from_path, self.get_path(to))?;
+        Ok(())
+    }
+
+    /// Get a file's last modification time
+    pub fn modified_time(&self, relative_path: &str) -> Result<SystemTime, StorageError> {
+        let full_path = self.get_path(relative_path);
+
+        if !full_path.exists() {
+            return Err(StorageError::NotFound(relative_path.to_string()));
+        }
+
+        Ok(fs::metadata(full_path)?.modified()?)
+    }
+
     /// Check if a file exists
     pub fn exists(&self, relative_path: &str) -> bool {
         self.get_path(relative_path).exists()
@@ -147,4 +257,67 @@ mod tests {
         assert_eq!(files.len(), 1);
         assert!(files[0].contains("file.txt"));
     }
+
+    #[test]
+    fn test_modified_time() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+
+        let before = SystemTime::now() - std::time::Duration::from_secs(1);
+        storage.write_file("file.txt", b"data").unwrap();
+        assert!(storage.modified_time("file.txt").unwrap() >= before);
+        assert!(matches!(
+            storage.modified_time("missing.txt"),
+            Err(StorageError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_write_file_durable() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+
+        storage
+            .write_file_durable("notes/durable.json", b"durable")
+            .unwrap();
+        assert_eq!(storage.read_file("notes/durable.json").unwrap(), b"durable");
+
+        // Overwriting replaces the previous content entirely
+        storage
+            .write_file_durable("notes/durable.json", b"v2")
+            .unwrap();
+        assert_eq!(storage.read_file("notes/durable.json").unwrap(), b"v2");
+    }
+
+    #[test]
+    fn test_compressed_files() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+
+        let payload = "a very compressible line of note text\n".repeat(1000);
+        storage
+            .write_file_compressed("notes/big.json", payload.as_bytes())
+            .unwrap();
+        let on_disk = fs::metadata(storage.get_path("notes/big.json"))
+            .unwrap()
+            .len();
+        assert!(
+            on_disk < payload.len() as u64 / 10,
+            "{} bytes on disk",
+            on_disk
+        );
+        assert_eq!(
+            storage.read_file_compressed("notes/big.json").unwrap(),
+            payload.as_bytes()
+        );
+
+        // Small payloads and plain files read back unchanged
+        storage.write_file_compressed("small.json", b"{}").unwrap();
+        assert_eq!(storage.read_file("small.json").unwrap(), b"{}");
+        storage.write_file("plain.json", b"{\"a\":1}").unwrap();
+        assert_eq!(
+            storage.read_file_compressed("plain.json").unwrap(),
+            b"{\"a\":1}"
+        );
+    }
 }
@@ -2,16 +2,40 @@
 //!
 //! Handles zip-based vault export/import with UUID-based conflict resolution.
 
-use crate::crypto::EncryptionManager;
-use crate::models::{ConflictResolution, Note, Vault, VaultMetadata};
-use crate::storage::FileStorage;
-use chrono::Utc;
-use std::io::{Read, Write};
-use std::path::Path;
+use crate::crypto::{normalize_salt, to_hex, EncryptionError, EncryptionManager};
+use crate::models::{
+    migrate_note, wiki_links, ConflictResolution, ConflictSensitivity, ModelError, Note, TagConfig,
+    Vault, VaultMetadata,
+};
+use crate::search::{SearchEngine, SearchError};
+use crate::storage::{FileStorage, StorageError};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
 use thiserror::Error;
 use uuid::Uuid;
 use zip::{write::FileOptions, ZipArchive, ZipWriter};
 
+/// Magic bytes at the start of a single-note share file
+const NOTE_FILE_MAGIC: &[u8; 6] = b"NSNOTE";
+
+/// Archive format version written to `metadata.json`
+///
+/// Imports accept this version and any older one; newer versions are rejected.
+pub const VAULT_FORMAT_VERSION: &str = "1.0";
+
+/// Current single-note share file format version
+const NOTE_FILE_VERSION: u8 = 1;
+
+/// Suffix of the re-encrypted copies written by `stage_password_change`
+const REKEY_SUFFIX: &str = ".rekey";
+
 #[derive(Error, Debug)]
 pub enum VaultError {
     #[error("IO error: {0}")]
@@ -26,197 +50,3570 @@ pub enum VaultError {
     VaultNotFound(String),
     #[error("Invalid vault format")]
     InvalidFormat,
+    #[error("Operation cancelled")]
+    Cancelled,
+    #[error("Archive too large: {0}")]
+    TooLarge(String),
+    #[error("Unsupported vault format version: {0}")]
+    UnsupportedVaultVersion(String),
+    #[error("Note already exists: {0}")]
+    NoteExists(Uuid),
+    #[error("Model error: {0}")]
+    ModelError(#[from] ModelError),
+    #[error("Storage error: {0}")]
+    StorageError(#[from] StorageError),
+    #[error("Search error: {0}")]
+    SearchError(#[from] SearchError),
+    /// Some re-encrypted notes were moved into place and some weren't
+    ///
+    /// `vault` carries the new salt and auth tag, which the moved notes need;
+    /// persist it, then finish with `VaultManager::commit_password_change`.
+    #[error("Password change incomplete: {source}")]
+    PasswordChangeIncomplete {
+        vault: Box<Vault>,
+        source: Box<VaultError>,
+    },
+}
+
+/// Summary of a completed vault export
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportStats {
+    /// Number of notes written to the archive
+    pub notes_written: usize,
+    /// Total size of all entries before compression (after encryption, if any)
+    pub bytes_uncompressed: u64,
+    /// Size of the finished archive on disk
+    pub bytes_compressed: u64,
+    /// Where the archive was written
+    pub output_path: PathBuf,
+    /// Whether note entries were encrypted
+    pub encrypted: bool,
+}
+
+impl ExportStats {
+    /// Archive size relative to the uncompressed entries (lower is better)
+    ///
+    /// Encrypted data is essentially random and doesn't compress, so encrypted
+    /// exports sit at roughly 1.0 (or slightly above, due to zip headers),
+    /// while plaintext exports of prose typically shrink well below that.
+    pub fn compression_ratio(&self) -> f64 {
+        if self.bytes_uncompressed == 0 {
+            return 1.0;
+        }
+        self.bytes_compressed as f64 / self.bytes_uncompressed as f64
+    }
+
+    /// Bytes saved by compression (negative when the archive grew)
+    pub fn bytes_saved(&self) -> i64 {
+        self.bytes_uncompressed as i64 - self.bytes_compressed as i64
+    }
+}
+
+/// Decompressed size caps enforced while reading vault archives
+///
+/// Guards against zip bombs: entries are read through a capped reader, so an
+/// oversized entry is rejected after at most `max_entry_bytes + 1` bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportLimits {
+    /// Maximum decompressed size of any single entry
+    pub max_entry_bytes: u64,
+    /// Maximum decompressed size of all entries read in one import
+    pub max_total_bytes: u64,
+}
+
+impl Default for ImportLimits {
+    fn default() -> Self {
+        Self {
+            max_entry_bytes: 64 * 1024 * 1024,
+            max_total_bytes: 2 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// Optional extras written by `export_vault_with_options`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Write `manifest.json` with a SHA-256 of every note entry, for `verify_vault`
+    pub include_manifest: bool,
+    /// Place notes under `notes/<first tag path>/<id>.json` (untagged notes under
+    /// `notes/_untagged/`) so the archive is browsable by hand. Imports read both
+    /// layouts.
+    pub group_by_tag: bool,
+}
+
+/// One note entry listed in an archive's `manifest.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub id: Uuid,
+    pub filename: String,
+    /// Hex SHA-256 of the entry's bytes as stored (i.e. after encryption)
+    pub sha256: String,
+}
+
+/// Contents of an archive's `manifest.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultManifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Outcome of `verify_vault`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Notes whose bytes no longer match the manifest hash
+    pub mismatched: Vec<Uuid>,
+    /// Notes listed in the manifest but absent from the archive
+    pub missing: Vec<Uuid>,
+    /// Note entries present in the archive but not listed in the manifest
+    pub unlisted: Vec<String>,
+}
+
+impl VerifyReport {
+    /// Whether every note matched the manifest
+    pub fn is_ok(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty() && self.unlisted.is_empty()
+    }
+}
+
+/// Outcome of `verify_decryptable`
+#[derive(Debug, Default)]
+pub struct DecryptReport {
+    /// Note entries that decrypted and parsed
+    pub decryptable: Vec<String>,
+    /// Entry name and error for each note entry that didn't
+    pub failed: Vec<(String, VaultError)>,
+}
+
+impl DecryptReport {
+    /// Whether every note entry decrypted
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Outcome of `audit_index`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexAuditReport {
+    /// Notes stored on disk with no index entry
+    pub missing_from_index: Vec<Uuid>,
+    /// Index entries with no note file on disk
+    pub missing_on_disk: Vec<String>,
+    /// Notes whose index entry records a different version, or none
+    pub version_mismatch: Vec<Uuid>,
+}
+
+impl IndexAuditReport {
+    /// Whether the index and storage agree
+    pub fn is_ok(&self) -> bool {
+        self.missing_from_index.is_empty()
+            && self.missing_on_disk.is_empty()
+            && self.version_mismatch.is_empty()
+    }
+}
+
+/// Notes listed in `VaultReport::largest_notes`
+const REPORT_LARGEST_NOTES: usize = 10;
+
+/// A note's size, as listed in `VaultReport::largest_notes`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NoteSize {
+    pub id: Uuid,
+    pub title: String,
+    /// Content length in bytes
+    pub bytes: usize,
+}
+
+/// Aggregate statistics produced by `generate_report`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct VaultReport {
+    pub total_notes: usize,
+    /// Words across all note contents, Markdown syntax excluded
+    pub total_words: usize,
+    /// Notes carrying each tag (exact tags, not rolled up to ancestors)
+    pub tag_counts: BTreeMap<String, usize>,
+    /// Largest notes by content size, biggest first
+    pub largest_notes: Vec<NoteSize>,
+    /// Earliest `created_at` among the notes
+    pub oldest: Option<DateTime<Utc>>,
+    /// Latest `updated_at` among the notes
+    pub newest: Option<DateTime<Utc>>,
+    /// Notes with no tags and no `[[links]]` to or from other notes
+    pub orphans: Vec<Uuid>,
+}
+
+/// Outcome of `import_vault_lenient`
+#[derive(Debug)]
+pub struct LenientImport {
+    pub vault: Vault,
+    /// Notes that were read successfully
+    pub notes: Vec<Note>,
+    /// Entry name and error for each note that couldn't be imported
+    pub failures: Vec<(String, VaultError)>,
+}
+
+/// Iterator over the notes inside a vault archive, decoding one entry at a time
+///
+/// The iterator owns the open archive file, which is closed when it's dropped.
+/// It borrows the encryption manager, so the manager must outlive it.
+pub struct ZipNoteIter<'a> {
+    archive: ZipArchive<std::fs::File>,
+    next_index: usize,
+    encryption: Option<&'a EncryptionManager>,
+    limits: ImportLimits,
+    bytes_read: u64,
+}
+
+impl Iterator for ZipNoteIter<'_> {
+    type Item = Result<Note, VaultError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next_index < self.archive.len() {
+            let index = self.next_index;
+            self.next_index += 1;
+
+            let mut file = match self.archive.by_index(index) {
+                Ok(f) => f,
+                Err(e) => return Some(Err(e.into())),
+            };
+            let name = file.name().to_string();
+
+            if name.starts_with("notes/") && name.ends_with(".json") {
+                let data = match read_entry(&mut file, &name, &self.limits, &mut self.bytes_read) {
+                    Ok(d) => d,
+                    Err(e) => return Some(Err(e)),
+                };
+                return Some(decode_note(data, self.encryption));
+            }
+        }
+        None
+    }
+}
+
+/// Magic bytes and format version opening an append-only note log
+const LOG_FILE_MAGIC: &[u8; 6] = b"NSLOG\x01";
+
+/// One change recorded in an `AppendLog`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRecord {
+    /// Add a note, or replace the note with the same ID
+    Upsert(Note),
+    /// Remove the note with this ID
+    Delete(Uuid),
+}
+
+/// A single growing file of encrypted note changes
+///
+/// After the magic bytes, each record is a big-endian `u32` length followed by
+/// that many bytes of an individually encrypted JSON `LogRecord`. Records are
+/// only ever appended and each append is synced to disk, so a crash can at
+/// worst leave one partial record at the end, which `replay` ignores and the
+/// next `append` cuts off before writing. The log borrows the encryption
+/// manager, so the manager must outlive it.
+pub struct AppendLog<'a> {
+    path: PathBuf,
+    encryption: &'a EncryptionManager,
+    /// File length after this log's last append, to skip rescanning for a torn tail
+    known_end: std::sync::Mutex<Option<u64>>,
+}
+
+impl<'a> AppendLog<'a> {
+    /// Open the log at `path`; the file is created on the first append
+    pub fn new(path: PathBuf, encryption: &'a EncryptionManager) -> Self {
+        Self {
+            path,
+            encryption,
+            known_end: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Encrypt and append one record, syncing it to disk before returning
+    ///
+    /// A partial record left at the end by an earlier crash is truncated first,
+    /// so it can't swallow the start of the new one.
+    pub fn append(&self, record: &LogRecord) -> Result<(), VaultError> {
+        let json = serde_json::to_vec(record)?;
+        let encrypted = self
+            .encryption
+            .encrypt(&json)
+            .map_err(|e| VaultError::EncryptionError(e.to_string()))?;
+        let len = u32::try_from(encrypted.len())
+            .map_err(|_| VaultError::TooLarge(format!("{} byte log record", encrypted.len())))?;
+
+        let mut known_end = self
+            .known_end
+            .lock()
+            .map_err(|_| VaultError::IoError(std::io::Error::other("Log lock poisoned")))?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&self.path)?;
+        let file_len = file.metadata()?.len();
+        let end = match *known_end {
+            Some(end) if end == file_len => end,
+            _ => complete_log_len(&mut file, file_len)?,
+        };
+        if end < file_len {
+            file.set_len(end)?;
+        }
+        file.seek(std::io::SeekFrom::Start(end))?;
+
+        let mut frame = Vec::with_capacity(LOG_FILE_MAGIC.len() + 4 + encrypted.len());
+        if end == 0 {
+            frame.extend_from_slice(LOG_FILE_MAGIC);
+        }
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(&encrypted);
+        file.write_all(&frame)?;
+        file.sync_data()?;
+        *known_end = Some(end + frame.len() as u64);
+        Ok(())
+    }
+
+    /// Decrypt every record and fold them, in order, into the current notes
+    ///
+    /// Notes are returned in the order they were first added. A missing log
+    /// replays to no notes, as does one cut off within the magic bytes by a
+    /// crash during the first append. A truncated final record is skipped; a
+    /// record that fails to decrypt or parse is an error.
+    pub fn replay(&self) -> Result<Vec<Note>, VaultError> {
+        let data = match std::fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        if data.len() < LOG_FILE_MAGIC.len() && LOG_FILE_MAGIC.starts_with(&data) {
+            return Ok(Vec::new());
+        }
+        let mut rest = data
+            .strip_prefix(LOG_FILE_MAGIC.as_slice())
+            .ok_or(VaultError::InvalidFormat)?;
+
+        let mut order: Vec<Uuid> = Vec::new();
+        let mut notes: std::collections::HashMap<Uuid, Note> = std::collections::HashMap::new();
+        while rest.len() >= 4 {
+            let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let Some(encrypted) = 4usize.checked_add(len).and_then(|end| rest.get(4..end)) else {
+                break;
+            };
+            rest = &rest[4 + len..];
+
+            let json = self
+                .encryption
+                .decrypt(encrypted)
+                .map_err(|e| VaultError::EncryptionError(e.to_string()))?;
+            match serde_json::from_slice(&json)? {
+                LogRecord::Upsert(note) => {
+                    if notes.insert(note.id, note.clone()).is_none() {
+                        order.push(note.id);
+                    }
+                }
+                LogRecord::Delete(id) => {
+                    if notes.remove(&id).is_some() {
+                        order.retain(|existing| *existing != id);
+                    }
+                }
+            }
+        }
+
+        Ok(order
+            .into_iter()
+            .filter_map(|id| notes.remove(&id))
+            .collect())
+    }
+}
+
+/// Length of an append log up to the end of its last complete record
+///
+/// Only the record lengths are read. A file too short to hold the magic bytes
+/// counts as empty; one that doesn't start with them is `InvalidFormat`.
+fn complete_log_len(file: &mut std::fs::File, file_len: u64) -> Result<u64, VaultError> {
+    let magic_len = LOG_FILE_MAGIC.len() as u64;
+    let mut magic = vec![0; file_len.min(magic_len) as usize];
+    file.seek(std::io::SeekFrom::Start(0))?;
+    file.read_exact(&mut magic)?;
+    if !LOG_FILE_MAGIC.starts_with(&magic) {
+        return Err(VaultError::InvalidFormat);
+    }
+    if file_len < magic_len {
+        return Ok(0);
+    }
+
+    let mut end = magic_len;
+    let mut header = [0; 4];
+    while file_len - end >= 4 {
+        file.read_exact(&mut header)?;
+        let record_end = end + 4 + u64::from(u32::from_be_bytes(header));
+        if record_end > file_len {
+            break;
+        }
+        end = record_end;
+        file.seek(std::io::SeekFrom::Start(end))?;
+    }
+    Ok(end)
+}
+
+/// Check `password` against the vault's `auth_tag` under `salt`, if one is recorded
+fn check_password(vault: &Vault, password: &str, salt: &str) -> Result<(), VaultError> {
+    let Some(auth_tag) = vault.auth_tag.as_deref() else {
+        return Ok(());
+    };
+    match EncryptionManager::verify_password(password, salt, auth_tag) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(VaultError::EncryptionError(
+            EncryptionError::AuthenticationFailed.to_string(),
+        )),
+        Err(e) => Err(VaultError::EncryptionError(e.to_string())),
+    }
 }
 
 /// Vault manager for export/import operations
 pub struct VaultManager {
     storage: FileStorage,
+    import_limits: ImportLimits,
+    compress_notes: bool,
+    tag_config: TagConfig,
 }
 
 impl VaultManager {
     /// Create a new vault manager
     pub fn new(storage: FileStorage) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            import_limits: ImportLimits::default(),
+            compress_notes: false,
+            tag_config: TagConfig::default(),
+        }
     }
 
-    /// Export a vault to a zip file
-    pub fn export_vault(
-        &self,
-        vault: &Vault,
-        notes: &[Note],
-        output_path: &Path,
-        encryption: Option<&EncryptionManager>,
-    ) -> Result<(), VaultError> {
-        let file = std::fs::File::create(output_path)?;
-        let mut zip = ZipWriter::new(file);
-        let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
-
-        // Write metadata
-        let metadata = VaultMetadata {
-            vault: vault.clone(),
-            note_count: notes.len(),
-            export_date: Utc::now(),
-            version: "1.0".to_string(),
-        };
-        let metadata_json = serde_json::to_string_pretty(&metadata)?;
-        zip.start_file("metadata.json", options)?;
-        zip.write_all(metadata_json.as_bytes())?;
+    /// Get the underlying file storage
+    pub fn storage(&self) -> &FileStorage {
+        &self.storage
+    }
 
-        // Write notes
-        for note in notes {
-            let note_json = serde_json::to_string_pretty(note)?;
-            let data = if let Some(enc) = encryption {
-                enc.encrypt(note_json.as_bytes())
-                    .map_err(|e| VaultError::EncryptionError(e.to_string()))?
-            } else {
-                note_json.into_bytes()
-            };
+    /// Set the decompressed size caps applied when reading archives
+    pub fn set_import_limits(&mut self, limits: ImportLimits) {
+        self.import_limits = limits;
+    }
 
-            let filename = format!("notes/{}.json", note.id);
-            zip.start_file(filename, options)?;
-            zip.write_all(&data)?;
-        }
+    /// Get the decompressed size caps applied when reading archives
+    pub fn import_limits(&self) -> ImportLimits {
+        self.import_limits
+    }
 
-        zip.finish()?;
-        Ok(())
+    /// Set how `rename_tag_indexed` compares tags
+    pub fn set_tag_config(&mut self, config: TagConfig) {
+        self.tag_config = config;
     }
 
-    /// Import a vault from a zip file
-    pub fn import_vault(
+    /// Gzip large notes written by `save_note`
+    ///
+    /// Off by default. Encrypted notes are compressed before encryption. Notes
+    /// are read back transparently whether or not they were compressed.
+    pub fn set_compress_notes(&mut self, compress: bool) {
+        self.compress_notes = compress;
+    }
+
+    /// Save a note to storage under `notes/<id>.json`, encrypting it if requested
+    pub fn save_note(
         &self,
-        input_path: &Path,
+        note: &Note,
         encryption: Option<&EncryptionManager>,
-        _conflict_resolution: ConflictResolution,
-    ) -> Result<(Vault, Vec<Note>), VaultError> {
-        let file = std::fs::File::open(input_path)?;
-        let mut zip = ZipArchive::new(file)?;
+    ) -> Result<(), VaultError> {
+        let data = encode_stored_note(note, encryption, self.compress_notes)?;
+        self.storage.write_file(&note_path(&note.id), &data)?;
+        Ok(())
+    }
 
-        // Read metadata
-        let metadata: VaultMetadata = {
-            let mut metadata_file = zip.by_name("metadata.json")?;
-            let mut metadata_json = String::new();
-            metadata_file.read_to_string(&mut metadata_json)?;
-            serde_json::from_str(&metadata_json)?
-        };
+    /// Load a note previously written with `save_note`
+    pub fn load_note(
+        &self,
+        id: &Uuid,
+        encryption: Option<&EncryptionManager>,
+    ) -> Result<Note, VaultError> {
+        decode_stored_note(self.storage.read_file(&note_path(id))?, encryption)
+    }
 
-        // Read notes
+    /// Load the stored notes updated at or after `since`
+    ///
+    /// Files whose modification time is older than the cutoff are skipped
+    /// without being read or decrypted; the rest are filtered precisely by
+    /// `updated_at`. A note is rewritten whenever it changes, so its file is
+    /// never older than its `updated_at`.
+    pub fn notes_modified_since(
+        &self,
+        since: DateTime<Utc>,
+        encryption: Option<&EncryptionManager>,
+    ) -> Result<Vec<Note>, VaultError> {
+        let cutoff = SystemTime::from(since);
         let mut notes = Vec::new();
-        let zip_len = zip.len();
-        for i in 0..zip_len {
-            let mut file = zip.by_index(i)?;
-            let name = file.name().to_string();
-
-            if name.starts_with("notes/") && name.ends_with(".json") {
-                let mut data = Vec::new();
-                file.read_to_end(&mut data)?;
-
-                let note_json = if let Some(enc) = encryption {
-                    let decrypted = enc
-                        .decrypt(&data)
-                        .map_err(|e| VaultError::EncryptionError(e.to_string()))?;
-                    String::from_utf8(decrypted).map_err(|_| VaultError::InvalidFormat)?
-                } else {
-                    String::from_utf8(data).map_err(|_| VaultError::InvalidFormat)?
-                };
 
-                let note: Note = serde_json::from_str(&note_json)?;
+        for path in self.storage.list_files("notes")? {
+            if !path.ends_with(".json") || self.storage.modified_time(&path)? < cutoff {
+                continue;
+            }
+            let note = decode_stored_note(self.storage.read_file(&path)?, encryption)?;
+            if note.updated_at >= since {
                 notes.push(note);
             }
         }
 
-        Ok((metadata.vault, notes))
+        Ok(notes)
     }
 
-    /// Detect conflicts when importing notes
-    pub fn detect_conflicts(
-        &self,
-        existing_notes: &[Note],
-        imported_notes: &[Note],
-    ) -> Vec<(Note, Note)> {
-        let mut conflicts = Vec::new();
+    /// Repair implausible note timestamps, e.g. after a rough import
+    ///
+    /// A timestamp is implausible when it's at or before the Unix epoch or more
+    /// than a day in the future. For each note:
+    /// 1. An implausible `created_at` or `updated_at` is replaced with the
+    ///    modification time of the note's file in storage, if there is one.
+    /// 2. Failing that, it falls back to the other timestamp when that's
+    ///    plausible, and is otherwise left alone.
+    /// 3. `updated_at` is then raised to `created_at` if it's earlier.
+    ///
+    /// Versions are not bumped. Notes are changed in memory only; save them to
+    /// persist the repair. Returns the number of notes changed.
+    pub fn infer_timestamps(&self, notes: &mut [Note]) -> usize {
+        let now = Utc::now();
+        let plausible =
+            |t: DateTime<Utc>| t.timestamp() > 0 && t <= now + chrono::Duration::days(1);
+        let mut changed = 0;
 
-        for imported in imported_notes {
-            if let Some(existing) = existing_notes.iter().find(|n| n.id == imported.id) {
-                // Same UUID but different versions - conflict!
-                if existing.version != imported.version
-                    || existing.updated_at != imported.updated_at
-                {
-                    conflicts.push((existing.clone(), imported.clone()));
+        for note in notes.iter_mut() {
+            let before = (note.created_at, note.updated_at);
+            let mtime = || {
+                self.storage
+                    .modified_time(&note_path(&note.id))
+                    .ok()
+                    .map(DateTime::<Utc>::from)
+                    .filter(|t| plausible(*t))
+            };
+
+            if !plausible(note.created_at) {
+                if let Some(t) = mtime().or(Some(note.updated_at).filter(|t| plausible(*t))) {
+                    note.created_at = t;
+                }
+            }
+            if !plausible(note.updated_at) {
+                if let Some(t) = mtime().or(Some(note.created_at).filter(|t| plausible(*t))) {
+                    note.updated_at = t;
                 }
             }
+            if note.updated_at < note.created_at {
+                note.updated_at = note.created_at;
+            }
+
+            if (note.created_at, note.updated_at) != before {
+                changed += 1;
+            }
         }
 
-        conflicts
+        changed
     }
 
-    /// Resolve a conflict based on the resolution strategy
-    pub fn resolve_conflict(
+    /// Move a note into another vault, keeping its ID
+    ///
+    /// The note is re-encrypted for the target and both indexes are updated.
+    /// Steps run in the order target file, target index, source index, source
+    /// file, and a failure undoes the completed ones, so the note ends up in
+    /// exactly one vault. Fails with `NoteExists` when the target already has a
+    /// note with this ID. Undo is best-effort: if an undo step itself fails,
+    /// the original error is still returned and `audit_index` will show the drift.
+    /// Neither index is left with a writer holding its lock (see
+    /// `SearchEngine::with_writer_once`).
+    pub fn move_note(
         &self,
-        existing: Note,
-        imported: Note,
-        resolution: ConflictResolution,
-    ) -> Vec<Note> {
-        match resolution {
-            ConflictResolution::Overwrite => vec![imported],
-            ConflictResolution::KeepBoth => {
-                let mut copy = imported.clone();
-                copy.id = Uuid::new_v4();
-                copy.title = format!("{} (Imported Copy)", copy.title);
-                vec![existing, copy]
-            }
-            ConflictResolution::Skip => vec![existing],
+        note_id: &Uuid,
+        source_index: &SearchEngine,
+        target: &VaultManager,
+        target_index: &SearchEngine,
+        source_enc: Option<&EncryptionManager>,
+        target_enc: Option<&EncryptionManager>,
+    ) -> Result<(), VaultError> {
+        let path = note_path(note_id);
+        if target.storage.exists(&path) {
+            return Err(VaultError::NoteExists(*note_id));
+        }
+        let note = self.load_note(note_id, source_enc)?;
+        let id = note_id.to_string();
+
+        target.save_note(&note, target_enc)?;
+        let undo_target_file = || {
+            let _ = target.storage.delete_file(&path);
+        };
+
+        if let Err(e) = target_index.with_writer_once(|writer| target_index.upsert_note(writer, &note)) {
+            undo_target_file();
+            return Err(e.into());
         }
+        let undo_target = || {
+            let _ = target_index.with_writer_once(|writer| {
+                target_index.delete_note(writer, &id);
+                Ok(())
+            });
+            undo_target_file();
+        };
+
+        if let Err(e) = source_index.with_writer_once(|writer| {
+            source_index.delete_note(writer, &id);
+            Ok(())
+        }) {
+            undo_target();
+            return Err(e.into());
+        }
+
+        if let Err(e) = self.storage.delete_file(&path) {
+            let _ = source_index.with_writer_once(|writer| source_index.upsert_note(writer, &note));
+            undo_target();
+            return Err(e.into());
+        }
+
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::crypto::EncryptionManager;
-    use tempfile::tempdir;
+    /// Bring one note's index entry in line with its file in storage
+    ///
+    /// Reads and decrypts the current file and re-indexes it, or removes the
+    /// entry when the file no longer exists. This is the per-note reconcile
+    /// step for a file watcher; the change is committed before returning, and
+    /// no writer is left holding the index lock (see
+    /// `SearchEngine::with_writer_once`).
+    pub fn reindex_note_from_storage(
+        &self,
+        id: &Uuid,
+        engine: &SearchEngine,
+        encryption: Option<&EncryptionManager>,
+    ) -> Result<(), VaultError> {
+        if !self.storage.exists(&note_path(id)) {
+            engine.with_writer_once(|writer| {
+                engine.delete_note(writer, &id.to_string());
+                Ok(())
+            })?;
+            return Ok(());
+        }
 
-    #[test]
-    fn test_export_import_vault() {
-        let temp_dir = tempdir().unwrap();
-        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
-        let manager = VaultManager::new(storage);
+        let note = self.load_note(id, encryption)?;
+        engine.with_writer_once(|writer| engine.upsert_note(writer, &note))?;
+        Ok(())
+    }
 
-        let vault = Vault::new(
-            "Test Vault".to_string(),
-            "Description".to_string(),
-            "salt123".to_string(),
-        );
+    /// Compare the notes in storage against a search index
+    ///
+    /// Every stored note is loaded to read its version. Entries indexed with
+    /// `SearchEngine::index_note`, which records no version, are reported as
+    /// mismatched. Index changes are only seen once committed.
+    pub fn audit_index(
+        &self,
+        engine: &SearchEngine,
+        encryption: Option<&EncryptionManager>,
+    ) -> Result<IndexAuditReport, VaultError> {
+        let mut indexed: std::collections::HashMap<String, Option<u64>> = engine
+            .indexed_notes()?
+            .into_iter()
+            .map(|note| (note.id, note.version))
+            .collect();
+        let mut report = IndexAuditReport::default();
 
-        let notes = vec![
-            Note::new("Note 1".to_string(), "Content 1".to_string(), vec![]),
-            Note::new("Note 2".to_string(), "Content 2".to_string(), vec![]),
-        ];
+        for path in self.storage.list_files("notes")? {
+            if !path.ends_with(".json") {
+                continue;
+            }
+            let note = decode_stored_note(self.storage.read_file(&path)?, encryption)?;
+            match indexed.remove(&note.id.to_string()) {
+                None => report.missing_from_index.push(note.id),
+                Some(version) if version != Some(note.version) => {
+                    report.version_mismatch.push(note.id)
+                }
+                Some(_) => {}
+            }
+        }
 
-        let export_path = temp_dir.path().join("export.zip");
-        manager
-            .export_vault(&vault, &notes, &export_path, None)
-            .unwrap();
+        report.missing_on_disk = indexed.into_keys().collect();
+        report.missing_from_index.sort();
+        report.missing_on_disk.sort();
+        report.version_mismatch.sort();
+        Ok(report)
+    }
 
-        let (imported_vault, imported_notes) = manager
-            .import_vault(&export_path, None, ConflictResolution::Overwrite)
-            .unwrap();
+    /// Rename a tag across notes, keeping storage and the search index in sync
+    ///
+    /// Every note whose tags change is rewritten on disk and re-indexed, with all
+    /// index updates applied in a single commit through
+    /// `SearchEngine::with_writer_once`. Returns the number of notes changed.
+    pub fn rename_tag_indexed(
+        &self,
+        engine: &SearchEngine,
+        notes: &mut [Note],
+        from: &str,
+        to: &str,
+        encryption: Option<&EncryptionManager>,
+    ) -> Result<usize, VaultError> {
+        let mut changed: Vec<&Note> = Vec::new();
+        for note in notes.iter_mut() {
+            if note.rename_tag_with(from, to, &self.tag_config) {
+                self.save_note(note, encryption)?;
+                changed.push(note);
+            }
+        }
 
-        assert_eq!(imported_vault.id, vault.id);
-        assert_eq!(imported_notes.len(), 2);
+        if !changed.is_empty() {
+            engine.with_writer_once(|writer| {
+                for note in &changed {
+                    engine.upsert_note(writer, note)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(changed.len())
     }
 
-    #[test]
-    fn test_conflict_detection() {
-        let temp_dir = tempdir().unwrap();
-        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
-        let manager = VaultManager::new(storage);
+    /// Summarize a vault's notes for the insights screen
+    ///
+    /// Counts are gathered in a single pass; links resolve against the titles
+    /// of `notes` only, so a link to a note outside the set doesn't count.
+    pub fn generate_report(&self, notes: &[Note]) -> VaultReport {
+        let mut report = VaultReport {
+            total_notes: notes.len(),
+            ..VaultReport::default()
+        };
+        let mut linked_titles: HashSet<&str> = HashSet::new();
+        let mut untagged_unlinking: Vec<&Note> = Vec::new();
+        let mut sizes: Vec<NoteSize> = Vec::with_capacity(notes.len());
 
-        let note1 = Note::new("Note".to_string(), "Content".to_string(), vec![]);
+        for note in notes {
+            report.total_words += note.word_count();
+            for tag in &note.tags {
+                *report.tag_counts.entry(tag.clone()).or_default() += 1;
+            }
+            report.oldest = Some(
+                report
+                    .oldest
+                    .map_or(note.created_at, |t| t.min(note.created_at)),
+            );
+            report.newest = report.newest.max(Some(note.updated_at));
 
-        let mut note2 = note1.clone();
-        note2.update("Updated".to_string(), "New Content".to_string(), vec![]);
+            let links = wiki_links(&note.content);
+            if note.tags.is_empty() && links.is_empty() {
+                untagged_unlinking.push(note);
+            }
+            linked_titles.extend(links);
+            sizes.push(NoteSize {
+                id: note.id,
+                title: note.title.clone(),
+                bytes: note.content.len(),
+            });
+        }
 
-        let conflicts = manager.detect_conflicts(&[note1], &[note2]);
-        assert_eq!(conflicts.len(), 1);
+        sizes.sort_by_key(|size| std::cmp::Reverse(size.bytes));
+        sizes.truncate(REPORT_LARGEST_NOTES);
+        report.largest_notes = sizes;
+        report.orphans = untagged_unlinking
+            .into_iter()
+            .filter(|note| !linked_titles.contains(note.title.trim()))
+            .map(|note| note.id)
+            .collect();
+        report
+    }
+
+    /// Export a vault to a zip file
+    pub fn export_vault(
+        &self,
+        vault: &Vault,
+        notes: &[Note],
+        output_path: &Path,
+        encryption: Option<&EncryptionManager>,
+    ) -> Result<ExportStats, VaultError> {
+        self.export_archive(
+            vault,
+            notes,
+            output_path,
+            encryption,
+            &ExportOptions::default(),
+            None,
+            &mut |_| {},
+        )
+    }
+
+    /// Export only the notes whose IDs are listed
+    ///
+    /// Notes keep their order in `notes`; IDs with no matching note are ignored.
+    pub fn export_vault_selective(
+        &self,
+        vault: &Vault,
+        notes: &[Note],
+        ids: &[Uuid],
+        output_path: &Path,
+        encryption: Option<&EncryptionManager>,
+    ) -> Result<ExportStats, VaultError> {
+        let wanted: std::collections::HashSet<&Uuid> = ids.iter().collect();
+        let selected: Vec<Note> = notes
+            .iter()
+            .filter(|note| wanted.contains(&note.id))
+            .cloned()
+            .collect();
+        self.export_vault(vault, &selected, output_path, encryption)
+    }
+
+    /// Export the notes matching a full-text query, e.g. to save a search as a vault
+    ///
+    /// Every hit is exported, in relevance order, paging through the index so
+    /// the engine's result limit doesn't cut the export short. Hits whose IDs
+    /// are missing from `notes_by_id` (a stale index entry), and any past
+    /// `MAX_SEARCH_OFFSET`, are skipped; their number is returned alongside the
+    /// stats.
+    pub fn export_search_results(
+        &self,
+        engine: &SearchEngine,
+        query: &str,
+        vault: &Vault,
+        notes_by_id: &std::collections::HashMap<Uuid, Note>,
+        output_path: &Path,
+        encryption: Option<&EncryptionManager>,
+    ) -> Result<(ExportStats, usize), VaultError> {
+        let mut notes: Vec<Note> = Vec::new();
+        let mut seen = 0;
+        let mut skipped = 0;
+        for page in 0.. {
+            let hits = engine.search_page(query, page, usize::MAX)?;
+            if hits.results.is_empty() {
+                skipped += hits.total.saturating_sub(seen);
+                break;
+            }
+            seen += hits.results.len();
+            for hit in hits.results {
+                match Uuid::parse_str(&hit.note_id)
+                    .ok()
+                    .and_then(|id| notes_by_id.get(&id))
+                {
+                    Some(note) => notes.push(note.clone()),
+                    None => skipped += 1,
+                }
+            }
+        }
+        let ids: Vec<Uuid> = notes.iter().map(|note| note.id).collect();
+        let stats = self.export_vault_selective(vault, &notes, &ids, output_path, encryption)?;
+        Ok((stats, skipped))
+    }
+
+    /// Export a vault with sensitive matches redacted from every note
+    ///
+    /// Applies `Note::redact` to each note before writing; the notes passed in
+    /// are not modified.
+    pub fn export_vault_redacted(
+        &self,
+        vault: &Vault,
+        notes: &[Note],
+        output_path: &Path,
+        encryption: Option<&EncryptionManager>,
+        patterns: &[regex::Regex],
+        replacement: &str,
+    ) -> Result<ExportStats, VaultError> {
+        let redacted: Vec<Note> = notes
+            .iter()
+            .map(|note| note.redact(patterns, replacement))
+            .collect();
+        self.export_vault(vault, &redacted, output_path, encryption)
+    }
+
+    /// Export a vault to a zip file with optional extras such as a manifest
+    pub fn export_vault_with_options(
+        &self,
+        vault: &Vault,
+        notes: &[Note],
+        output_path: &Path,
+        encryption: Option<&EncryptionManager>,
+        options: &ExportOptions,
+    ) -> Result<ExportStats, VaultError> {
+        self.export_archive(
+            vault,
+            notes,
+            output_path,
+            encryption,
+            options,
+            None,
+            &mut |_| {},
+        )
+    }
+
+    /// Export a vault to a zip file, stopping early if `cancel` is set
+    ///
+    /// The flag is checked before each note. On cancellation the partial archive
+    /// is removed and `VaultError::Cancelled` is returned.
+    pub fn export_vault_cancellable(
+        &self,
+        vault: &Vault,
+        notes: &[Note],
+        output_path: &Path,
+        encryption: Option<&EncryptionManager>,
+        cancel: &AtomicBool,
+    ) -> Result<ExportStats, VaultError> {
+        self.export_archive(
+            vault,
+            notes,
+            output_path,
+            encryption,
+            &ExportOptions::default(),
+            Some(cancel),
+            &mut |_| {},
+        )
+    }
+
+    /// Export a vault to a zip file, reporting progress in bytes
+    ///
+    /// `progress` receives the entry bytes written so far and the total, after
+    /// the metadata and after every 64 KiB of each note, so large notes advance
+    /// it steadily. The total comes from an `export_vault_validate` pass first,
+    /// which encrypts every note an extra time. Both figures count entry bytes
+    /// before compression (`ExportStats::bytes_uncompressed`); the last report
+    /// has them equal. `cancel` is honored as in `export_vault_cancellable`,
+    /// during the validation pass too.
+    pub fn export_vault_with_progress(
+        &self,
+        vault: &Vault,
+        notes: &[Note],
+        output_path: &Path,
+        encryption: Option<&EncryptionManager>,
+        cancel: Option<&AtomicBool>,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<ExportStats, VaultError> {
+        let total = self
+            .validate_archive(vault, notes, output_path, encryption, cancel)?
+            .bytes_uncompressed;
+        let stats = self.export_archive(
+            vault,
+            notes,
+            output_path,
+            encryption,
+            &ExportOptions::default(),
+            cancel,
+            // The metadata can differ from the estimate by a few bytes (the
+            // export timestamp's width varies); never report more than 100%
+            &mut |written| progress(written, total.max(written)),
+        )?;
+        if stats.bytes_uncompressed < total {
+            progress(stats.bytes_uncompressed, stats.bytes_uncompressed);
+        }
+        Ok(stats)
+    }
+
+    /// Write the archive beside `output_path` and move it into place once done
+    ///
+    /// A failed or cancelled export removes its partial output and leaves any
+    /// existing file at `output_path` untouched.
+    #[allow(clippy::too_many_arguments)]
+    fn export_archive(
+        &self,
+        vault: &Vault,
+        notes: &[Note],
+        output_path: &Path,
+        encryption: Option<&EncryptionManager>,
+        options: &ExportOptions,
+        cancel: Option<&AtomicBool>,
+        progress: &mut dyn FnMut(u64),
+    ) -> Result<ExportStats, VaultError> {
+        let mut partial = output_path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+
+        let result = self
+            .write_archive(vault, notes, &partial, encryption, options, cancel, progress)
+            .and_then(|stats| {
+                std::fs::rename(&partial, output_path)?;
+                Ok(ExportStats {
+                    output_path: output_path.to_path_buf(),
+                    ..stats
+                })
+            });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&partial);
+        }
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_archive(
+        &self,
+        vault: &Vault,
+        notes: &[Note],
+        output_path: &Path,
+        encryption: Option<&EncryptionManager>,
+        export_options: &ExportOptions,
+        cancel: Option<&AtomicBool>,
+        progress: &mut dyn FnMut(u64),
+    ) -> Result<ExportStats, VaultError> {
+        let file = std::fs::File::create(output_path)?;
+        let mut zip = ZipWriter::new(file);
+        let bytes_uncompressed = write_entries(
+            &mut zip,
+            vault,
+            notes,
+            encryption,
+            export_options,
+            cancel,
+            progress,
+        )?;
+        zip.finish()?;
+
+        Ok(ExportStats {
+            notes_written: notes.len(),
+            bytes_uncompressed,
+            bytes_compressed: std::fs::metadata(output_path)?.len(),
+            output_path: output_path.to_path_buf(),
+            encrypted: encryption.is_some(),
+        })
+    }
+
+    /// Check that an export would succeed, without writing the archive
+    ///
+    /// Every note is serialized, encrypted and compressed exactly as
+    /// `export_vault` would, but into a sink that only counts bytes, so the
+    /// returned stats match a real export (the compressed size may differ by a
+    /// few bytes, since the export timestamp is part of the archive). The output
+    /// path is checked for writability without being created or modified.
+    pub fn export_vault_validate(
+        &self,
+        vault: &Vault,
+        notes: &[Note],
+        output_path: &Path,
+        encryption: Option<&EncryptionManager>,
+    ) -> Result<ExportStats, VaultError> {
+        self.validate_archive(vault, notes, output_path, encryption, None)
+    }
+
+    /// `export_vault_validate`, stopping early if `cancel` is set
+    fn validate_archive(
+        &self,
+        vault: &Vault,
+        notes: &[Note],
+        output_path: &Path,
+        encryption: Option<&EncryptionManager>,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<ExportStats, VaultError> {
+        check_writable(output_path)?;
+
+        let mut zip = ZipWriter::new(CountingSink::default());
+        let bytes_uncompressed = write_entries(
+            &mut zip,
+            vault,
+            notes,
+            encryption,
+            &ExportOptions::default(),
+            cancel,
+            &mut |_| {},
+        )?;
+        let sink = zip.finish()?;
+
+        Ok(ExportStats {
+            notes_written: notes.len(),
+            bytes_uncompressed,
+            bytes_compressed: sink.len,
+            output_path: output_path.to_path_buf(),
+            encrypted: encryption.is_some(),
+        })
+    }
+
+    /// Import a vault from a zip file
+    pub fn import_vault(
+        &self,
+        input_path: &Path,
+        encryption: Option<&EncryptionManager>,
+        _conflict_resolution: ConflictResolution,
+    ) -> Result<(Vault, Vec<Note>), VaultError> {
+        let file = std::fs::File::open(input_path)?;
+
+        let mut notes = Vec::new();
+        let vault = self.import_archive(file, encryption, None, strict(|note| notes.push(note)))?;
+
+        Ok((vault, notes))
+    }
+
+    /// Import a vault from a zip file, stopping early if `cancel` is set
+    pub fn import_vault_cancellable(
+        &self,
+        input_path: &Path,
+        encryption: Option<&EncryptionManager>,
+        _conflict_resolution: ConflictResolution,
+        cancel: &AtomicBool,
+    ) -> Result<(Vault, Vec<Note>), VaultError> {
+        let file = std::fs::File::open(input_path)?;
+
+        let mut notes = Vec::new();
+        let vault = self.import_archive(
+            file,
+            encryption,
+            Some(cancel),
+            strict(|note| notes.push(note)),
+        )?;
+
+        Ok((vault, notes))
+    }
+
+    /// Import a vault from any seekable reader, delivering notes one at a time
+    ///
+    /// Each note entry is read, decrypted and handed to `on_note` before the next
+    /// entry is touched, so at most one note is held in memory. This suits
+    /// stream-based sources such as Android content providers.
+    pub fn import_vault_streaming<R, F>(
+        &self,
+        reader: R,
+        encryption: Option<&EncryptionManager>,
+        on_note: F,
+    ) -> Result<Vault, VaultError>
+    where
+        R: Read + Seek,
+        F: FnMut(Note),
+    {
+        self.import_archive(reader, encryption, None, strict(on_note))
+    }
+
+    /// Streaming import that stops with `VaultError::Cancelled` once `cancel` is set
+    ///
+    /// The flag is checked before each note entry; notes already delivered to
+    /// `on_note` are not rolled back.
+    pub fn import_vault_streaming_cancellable<R, F>(
+        &self,
+        reader: R,
+        encryption: Option<&EncryptionManager>,
+        cancel: &AtomicBool,
+        on_note: F,
+    ) -> Result<Vault, VaultError>
+    where
+        R: Read + Seek,
+        F: FnMut(Note),
+    {
+        self.import_archive(reader, encryption, Some(cancel), strict(on_note))
+    }
+
+    /// Import a vault, skipping note entries that can't be read or decoded
+    ///
+    /// For recovering damaged archives: instead of aborting on the first bad
+    /// note, every failure is recorded alongside its entry name and the rest of
+    /// the notes are still returned. Problems with the archive as a whole (not a
+    /// zip, missing or unsupported metadata) still fail the call.
+    pub fn import_vault_lenient(
+        &self,
+        input_path: &Path,
+        encryption: Option<&EncryptionManager>,
+    ) -> Result<LenientImport, VaultError> {
+        let file = std::fs::File::open(input_path)?;
+
+        let mut notes = Vec::new();
+        let mut failures = Vec::new();
+        let vault = self.import_archive(file, encryption, None, |name, note| {
+            match note {
+                Ok(note) => notes.push(note),
+                Err(e) => failures.push((name, e)),
+            }
+            Ok(())
+        })?;
+
+        Ok(LenientImport {
+            vault,
+            notes,
+            failures,
+        })
+    }
+
+    /// Read an archive's metadata, then hand each note entry to `on_entry`
+    ///
+    /// `on_entry` receives the entry name and the decoded note (or why it
+    /// couldn't be read); returning an error stops the import.
+    fn import_archive<R, F>(
+        &self,
+        reader: R,
+        encryption: Option<&EncryptionManager>,
+        cancel: Option<&AtomicBool>,
+        mut on_entry: F,
+    ) -> Result<Vault, VaultError>
+    where
+        R: Read + Seek,
+        F: FnMut(String, Result<Note, VaultError>) -> Result<(), VaultError>,
+    {
+        let mut zip = ZipArchive::new(reader)?;
+        let mut bytes_read = 0;
+        let metadata = read_archive_metadata(&mut zip, &self.import_limits, &mut bytes_read)?;
+        check_format_version(&metadata.version)?;
+        // Catch a tampered salt here rather than as a confusing decryption failure
+        if encryption.is_some() && !metadata.vault.verify_salt() {
+            return Err(VaultError::InvalidFormat);
+        }
+
+        // Read notes
+        let zip_len = zip.len();
+        for i in 0..zip_len {
+            let mut file = match zip.by_index(i) {
+                Ok(f) => f,
+                Err(e) => {
+                    on_entry(format!("entry #{}", i), Err(e.into()))?;
+                    continue;
+                }
+            };
+            let name = file.name().to_string();
+
+            if name.starts_with("notes/") && name.ends_with(".json") {
+                check_cancelled(cancel)?;
+
+                let note = read_entry(&mut file, &name, &self.import_limits, &mut bytes_read)
+                    .and_then(|data| decode_note(data, encryption));
+                on_entry(name, note)?;
+            }
+        }
+
+        Ok(metadata.vault)
+    }
+
+    /// Re-salt a vault whose salt is in a legacy format
+    ///
+    /// `notes` are expected to carry base64 encrypted content, as produced by
+    /// `null_space_encrypt`. Each note is decrypted with the key derived from the
+    /// normalized legacy salt and re-encrypted under a freshly generated salt.
+    /// A vault with an `auth_tag` has the password checked against it first,
+    /// like `change_password`. Returns the vault with its new salt and auth tag
+    /// alongside the re-encrypted notes.
+    pub fn migrate_vault_salt(
+        &self,
+        vault: &Vault,
+        old_salt_raw: &str,
+        password: &str,
+        notes: &[Note],
+    ) -> Result<(Vault, Vec<Note>), VaultError> {
+        let legacy_salt =
+            normalize_salt(old_salt_raw).map_err(|e| VaultError::EncryptionError(e.to_string()))?;
+        check_password(vault, password, &legacy_salt)?;
+        let old_manager = EncryptionManager::new_from_password(password, &legacy_salt)
+            .map_err(|e| VaultError::EncryptionError(e.to_string()))?;
+
+        let new_salt = EncryptionManager::generate_salt();
+        let new_manager = EncryptionManager::new_from_password(password, &new_salt)
+            .map_err(|e| VaultError::EncryptionError(e.to_string()))?;
+
+        let mut migrated_notes = Vec::with_capacity(notes.len());
+        for note in notes {
+            let ciphertext = general_purpose::STANDARD
+                .decode(&note.content)
+                .map_err(|_| VaultError::InvalidFormat)?;
+            let plaintext = old_manager
+                .decrypt(&ciphertext)
+                .map_err(|e| VaultError::EncryptionError(e.to_string()))?;
+            let reencrypted = new_manager
+                .encrypt(&plaintext)
+                .map_err(|e| VaultError::EncryptionError(e.to_string()))?;
+
+            let mut migrated = note.clone();
+            migrated.content = general_purpose::STANDARD.encode(reencrypted);
+            migrated_notes.push(migrated);
+        }
+
+        let mut migrated_vault = vault.clone();
+        migrated_vault.salt = new_salt;
+        migrated_vault.auth_tag = Some(new_manager.auth_tag());
+        migrated_vault.updated_at = Utc::now();
+
+        Ok((migrated_vault, migrated_notes))
+    }
+
+    /// Change a vault's password, re-encrypting every stored note
+    ///
+    /// Runs `stage_password_change`, then `commit_password_change`. Returns
+    /// the vault with its new salt and auth tag, which the caller must persist.
+    /// If moving the re-encrypted notes into place fails partway, the error is
+    /// `PasswordChangeIncomplete`, carrying that vault; the notes not yet moved
+    /// keep their staged copies, so the change can be finished later.
+    pub fn change_password(
+        &self,
+        vault: &Vault,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<Vault, VaultError> {
+        let rekeyed = self.stage_password_change(vault, old_password, new_password)?;
+        match self.commit_password_change() {
+            Ok(_) => Ok(rekeyed),
+            Err(e) => Err(VaultError::PasswordChangeIncomplete {
+                vault: Box::new(rekeyed),
+                source: Box::new(e),
+            }),
+        }
+    }
+
+    /// Re-encrypt every stored note under a new password, beside the originals
+    ///
+    /// `old_password` is checked against the vault's `auth_tag` before any
+    /// note is touched; a mismatch fails with `EncryptionError` carrying
+    /// `EncryptionError::AuthenticationFailed`'s message. Vaults without a
+    /// recorded tag are checked by decrypting their notes instead. All notes
+    /// are decrypted into memory, then re-encrypted into `.rekey` files beside
+    /// the originals, which stay untouched; a failure removes the staged files.
+    /// Notes are compressed as configured by `set_compress_notes`. Fails if
+    /// an earlier change is still staged.
+    ///
+    /// Returns the vault with its new salt and auth tag. Persist it before
+    /// `commit_password_change` moves the staged notes into place, since from
+    /// then on they can only be decrypted with it.
+    pub fn stage_password_change(
+        &self,
+        vault: &Vault,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<Vault, VaultError> {
+        if !self.staged_password_change()?.is_empty() {
+            return Err(VaultError::EncryptionError(
+                "A password change is already staged; commit it first".to_string(),
+            ));
+        }
+        check_password(vault, old_password, &vault.salt)?;
+
+        let old_manager = EncryptionManager::new_from_password(old_password, &vault.salt)
+            .map_err(|e| VaultError::EncryptionError(e.to_string()))?;
+        let new_salt = EncryptionManager::generate_salt();
+        let new_manager = EncryptionManager::new_from_password(new_password, &new_salt)
+            .map_err(|e| VaultError::EncryptionError(e.to_string()))?;
+
+        let mut notes = Vec::new();
+        for path in self.storage.list_files("notes")? {
+            if !path.ends_with(".json") {
+                continue;
+            }
+            let data = self.storage.read_file(&path)?;
+            let note = match decode_stored_note(data, Some(&old_manager)) {
+                Ok(note) => note,
+                Err(VaultError::EncryptionError(_)) if vault.auth_tag.is_none() => {
+                    return Err(VaultError::EncryptionError(
+                        EncryptionError::AuthenticationFailed.to_string(),
+                    ));
+                }
+                Err(e) => return Err(e),
+            };
+            notes.push((path, note));
+        }
+
+        let mut staged: Vec<String> = Vec::with_capacity(notes.len());
+        for (path, note) in &notes {
+            let staging = format!("{}{}", path, REKEY_SUFFIX);
+            let written = encode_stored_note(note, Some(&new_manager), self.compress_notes)
+                .and_then(|data| Ok(self.storage.write_file(&staging, &data)?));
+            staged.push(staging);
+            if let Err(e) = written {
+                for staging in &staged {
+                    let _ = self.storage.delete_file(staging);
+                }
+                return Err(e);
+            }
+        }
+
+        let mut rekeyed = vault.clone();
+        rekeyed.salt = new_salt;
+        rekeyed.auth_tag = Some(new_manager.auth_tag());
+        rekeyed.updated_at = Utc::now();
+        Ok(rekeyed)
+    }
+
+    /// Move the notes staged by `stage_password_change` over the originals
+    ///
+    /// Stops at the first failure; notes already moved stay moved and the
+    /// rest keep their staged copies, so calling this again resumes the
+    /// change. Returns how many notes were moved.
+    pub fn commit_password_change(&self) -> Result<usize, VaultError> {
+        let staged = self.staged_password_change()?;
+        for staging in &staged {
+            let path = &staging[..staging.len() - REKEY_SUFFIX.len()];
+            self.storage.rename_file(staging, path)?;
+        }
+        Ok(staged.len())
+    }
+
+    /// Paths of the notes staged by `stage_password_change`, in order
+    fn staged_password_change(&self) -> Result<Vec<String>, VaultError> {
+        let mut staged: Vec<String> = self
+            .storage
+            .list_files("notes")?
+            .into_iter()
+            .filter(|path| path.ends_with(&format!(".json{}", REKEY_SUFFIX)))
+            .collect();
+        staged.sort();
+        Ok(staged)
+    }
+
+    /// Export one encrypted note as a compact share file
+    ///
+    /// Layout: `NSNOTE` magic, a format version byte, a length-prefixed salt
+    /// (so the recipient can derive the key from the password), then the
+    /// encrypted note JSON.
+    pub fn export_note(
+        &self,
+        note: &Note,
+        output_path: &Path,
+        encryption: &EncryptionManager,
+    ) -> Result<(), VaultError> {
+        let salt = encryption.salt().as_bytes();
+        let salt_len = u8::try_from(salt.len()).map_err(|_| VaultError::InvalidFormat)?;
+        let ciphertext = encode_note(note, Some(encryption))?;
+
+        let mut data =
+            Vec::with_capacity(NOTE_FILE_MAGIC.len() + 2 + salt.len() + ciphertext.len());
+        data.extend_from_slice(NOTE_FILE_MAGIC);
+        data.push(NOTE_FILE_VERSION);
+        data.push(salt_len);
+        data.extend_from_slice(salt);
+        data.extend_from_slice(&ciphertext);
+
+        std::fs::write(output_path, data)?;
+        Ok(())
+    }
+
+    /// Import a note from a share file written by `export_note`
+    pub fn import_note(&self, input_path: &Path, password: &str) -> Result<Note, VaultError> {
+        let data = std::fs::read(input_path)?;
+
+        let rest = data
+            .strip_prefix(NOTE_FILE_MAGIC.as_slice())
+            .ok_or(VaultError::InvalidFormat)?;
+        let (&version, rest) = rest.split_first().ok_or(VaultError::InvalidFormat)?;
+        if version != NOTE_FILE_VERSION {
+            return Err(VaultError::InvalidFormat);
+        }
+        let (&salt_len, rest) = rest.split_first().ok_or(VaultError::InvalidFormat)?;
+        if rest.len() < salt_len as usize {
+            return Err(VaultError::InvalidFormat);
+        }
+        let (salt, ciphertext) = rest.split_at(salt_len as usize);
+        let salt = std::str::from_utf8(salt).map_err(|_| VaultError::InvalidFormat)?;
+
+        let encryption = EncryptionManager::new_from_password(password, salt)
+            .map_err(|e| VaultError::EncryptionError(e.to_string()))?;
+        decode_note(ciphertext.to_vec(), Some(&encryption))
+    }
+
+    /// Export a single note as a self-contained HTML file
+    pub fn export_note_html(&self, note: &Note, output_path: &Path) -> Result<(), VaultError> {
+        std::fs::write(output_path, note.to_html())?;
+        Ok(())
+    }
+
+    /// Check every note entry against the archive's `manifest.json`
+    ///
+    /// Hashes are computed over the stored bytes, so encrypted archives can be
+    /// verified without a password. Fails with `VaultError::InvalidFormat` if
+    /// the archive has no manifest.
+    pub fn verify_vault(&self, input_path: &Path) -> Result<VerifyReport, VaultError> {
+        let file = std::fs::File::open(input_path)?;
+        let mut zip = ZipArchive::new(file)?;
+        let mut bytes_read = 0;
+
+        let manifest: VaultManifest = {
+            let mut manifest_file = match zip.by_name("manifest.json") {
+                Ok(f) => f,
+                Err(zip::result::ZipError::FileNotFound) => return Err(VaultError::InvalidFormat),
+                Err(e) => return Err(e.into()),
+            };
+            let data = read_entry(
+                &mut manifest_file,
+                "manifest.json",
+                &self.import_limits,
+                &mut bytes_read,
+            )?;
+            serde_json::from_slice(&data)?
+        };
+
+        let mut report = VerifyReport::default();
+        for entry in &manifest.entries {
+            let mut file = match zip.by_name(&entry.filename) {
+                Ok(f) => f,
+                Err(zip::result::ZipError::FileNotFound) => {
+                    report.missing.push(entry.id);
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let data = read_entry(
+                &mut file,
+                &entry.filename,
+                &self.import_limits,
+                &mut bytes_read,
+            )?;
+            if sha256_hex(&data) != entry.sha256 {
+                report.mismatched.push(entry.id);
+            }
+        }
+
+        for name in zip.file_names() {
+            if name.starts_with("notes/")
+                && name.ends_with(".json")
+                && !manifest.entries.iter().any(|e| e.filename == name)
+            {
+                report.unlisted.push(name.to_string());
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Read only the metadata of a vault archive, without touching its notes
+    ///
+    /// Useful for previewing an archive (name, note count, export date) before
+    /// committing to a full import.
+    pub fn read_metadata(&self, input_path: &Path) -> Result<VaultMetadata, VaultError> {
+        let file = std::fs::File::open(input_path)?;
+        let mut zip = ZipArchive::new(file)?;
+        read_archive_metadata(&mut zip, &self.import_limits, &mut 0)
+    }
+
+    /// Read the key derivation salt of a vault archive
+    ///
+    /// Notes in an encrypted archive can't be read without an
+    /// `EncryptionManager`, and building one needs this salt. The salt sits in
+    /// the cleartext metadata, so this reads only that entry; derive the key
+    /// from it, then pass the manager to `import_vault`. Fails with
+    /// `InvalidFormat` if the salt isn't well formed (`Vault::verify_salt`).
+    /// Only the format is checked: there is no checksum, so a well-formed but
+    /// altered salt shows up later, as notes failing to decrypt.
+    pub fn read_salt(&self, input_path: &Path) -> Result<String, VaultError> {
+        let metadata = self.read_metadata(input_path)?;
+        if !metadata.vault.verify_salt() {
+            return Err(VaultError::InvalidFormat);
+        }
+        Ok(metadata.vault.salt)
+    }
+
+    /// Check that every note in an encrypted vault archive decrypts with `password`
+    ///
+    /// A test restore: the salt comes from `read_salt`, and each note entry is
+    /// decrypted and parsed, then discarded. Only entry names and errors are
+    /// reported, never note contents. Problems with the archive as a whole
+    /// still fail the call.
+    pub fn verify_decryptable(
+        &self,
+        input_path: &Path,
+        password: &str,
+    ) -> Result<DecryptReport, VaultError> {
+        let salt = self.read_salt(input_path)?;
+        let encryption = EncryptionManager::new_from_password(password, &salt)
+            .map_err(|e| VaultError::EncryptionError(e.to_string()))?;
+        let file = std::fs::File::open(input_path)?;
+
+        let mut report = DecryptReport::default();
+        self.import_archive(file, Some(&encryption), None, |name, note| {
+            match note {
+                Ok(_) => report.decryptable.push(name),
+                Err(e) => report.failed.push((name, e)),
+            }
+            Ok(())
+        })?;
+        Ok(report)
+    }
+
+    /// Estimate the peak memory `import_vault` needs for an archive, in bytes
+    ///
+    /// Only the metadata and the central directory's uncompressed entry sizes
+    /// are read; nothing else is extracted. `import_vault` keeps every decoded
+    /// note, so the estimate is the total size of the note entries, plus twice
+    /// the largest entry for the raw and decrypted copies held while decoding
+    /// it. The streaming importers only need about three times the largest
+    /// entry, so prefer them when this figure is high. Encryption overhead and
+    /// JSON parsing make the real figure differ somewhat either way.
+    pub fn estimate_import_memory(&self, input_path: &Path) -> Result<u64, VaultError> {
+        let file = std::fs::File::open(input_path)?;
+        let mut zip = ZipArchive::new(file)?;
+        let metadata = read_archive_metadata(&mut zip, &self.import_limits, &mut 0)?;
+        check_format_version(&metadata.version)?;
+
+        let mut total: u64 = 0;
+        let mut largest: u64 = 0;
+        for index in 0..zip.len() {
+            let entry = zip.by_index_raw(index)?;
+            if entry.name().starts_with("notes/") && entry.name().ends_with(".json") {
+                total += entry.size();
+                largest = largest.max(entry.size());
+            }
+        }
+
+        Ok(total + 2 * largest)
+    }
+
+    /// Iterate over the notes in a vault archive without importing it all
+    ///
+    /// Only the archive's central directory is read up front; each note is read
+    /// and decrypted as the iterator advances. A corrupt entry yields an `Err`
+    /// item and iteration can continue past it.
+    pub fn iter_notes_in_zip<'a>(
+        &self,
+        input_path: &Path,
+        encryption: Option<&'a EncryptionManager>,
+    ) -> Result<ZipNoteIter<'a>, VaultError> {
+        let file = std::fs::File::open(input_path)?;
+        Ok(ZipNoteIter {
+            archive: ZipArchive::new(file)?,
+            next_index: 0,
+            encryption,
+            limits: self.import_limits,
+            bytes_read: 0,
+        })
+    }
+
+    /// Detect conflicts when importing notes
+    ///
+    /// Uses `ConflictSensitivity::VersionAndTimestamp`.
+    pub fn detect_conflicts(
+        &self,
+        existing_notes: &[Note],
+        imported_notes: &[Note],
+    ) -> Vec<(Note, Note)> {
+        self.detect_conflicts_with(
+            existing_notes,
+            imported_notes,
+            ConflictSensitivity::VersionAndTimestamp,
+        )
+    }
+
+    /// Detect conflicts when importing notes, with a chosen sensitivity
+    pub fn detect_conflicts_with(
+        &self,
+        existing_notes: &[Note],
+        imported_notes: &[Note],
+        sensitivity: ConflictSensitivity,
+    ) -> Vec<(Note, Note)> {
+        let mut conflicts = Vec::new();
+
+        for imported in imported_notes {
+            if let Some(existing) = existing_notes.iter().find(|n| n.id == imported.id) {
+                let conflicting = match sensitivity {
+                    ConflictSensitivity::VersionOnly => existing.version != imported.version,
+                    ConflictSensitivity::VersionAndTimestamp => {
+                        existing.version != imported.version
+                            || existing.updated_at != imported.updated_at
+                    }
+                    ConflictSensitivity::ContentAware => {
+                        existing.fingerprint() != imported.fingerprint()
+                    }
+                };
+                if conflicting {
+                    conflicts.push((existing.clone(), imported.clone()));
+                }
+            }
+        }
+
+        conflicts
+    }
+
+    /// Resolve a conflict based on the resolution strategy
+    ///
+    /// With `KeepBoth`, the imported copy gets a fresh ID and records the
+    /// original's ID in `derived_from`.
+    pub fn resolve_conflict(
+        &self,
+        existing: Note,
+        imported: Note,
+        resolution: ConflictResolution,
+    ) -> Vec<Note> {
+        match resolution {
+            ConflictResolution::Overwrite => vec![imported],
+            ConflictResolution::KeepBoth => {
+                let mut copy = imported.clone();
+                copy.derived_from = Some(imported.id);
+                copy.id = Uuid::new_v4();
+                copy.title = format!("{} (Imported Copy)", copy.title);
+                vec![existing, copy]
+            }
+            ConflictResolution::Skip => vec![existing],
+        }
+    }
+}
+
+/// Path of a note's file, both in storage and inside vault archives
+fn note_path(id: &Uuid) -> String {
+    format!("notes/{}.json", id)
+}
+
+/// Archive path of a note grouped under its first tag
+///
+/// Tag segments that are empty, `.` or `..`, or contain a backslash, are
+/// dropped so a tag can't escape the `notes/` directory.
+fn grouped_note_path(note: &Note) -> String {
+    let folder = note
+        .primary_tag()
+        .map(|tag| {
+            tag.split('/')
+                .filter(|s| !s.is_empty() && *s != "." && *s != ".." && !s.contains('\\'))
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .filter(|folder| !folder.is_empty())
+        .unwrap_or_else(|| "_untagged".to_string());
+    format!("notes/{}/{}.json", folder, note.id)
+}
+
+/// Serialize (and encrypt, if requested) a note for writing
+fn encode_note(note: &Note, encryption: Option<&EncryptionManager>) -> Result<Vec<u8>, VaultError> {
+    encode_stored_note(note, encryption, false)
+}
+
+/// Serialize a note for a file in storage
+///
+/// With `compress`, large notes are gzipped before any encryption, since
+/// ciphertext no longer compresses. `decode_stored_note` reads either form.
+fn encode_stored_note(
+    note: &Note,
+    encryption: Option<&EncryptionManager>,
+    compress: bool,
+) -> Result<Vec<u8>, VaultError> {
+    let note_json = note.to_canonical_json().into_bytes();
+    let payload = if compress {
+        crate::storage::compress(&note_json)?
+    } else {
+        note_json
+    };
+    match encryption {
+        Some(enc) => enc
+            .encrypt(&payload)
+            .map_err(|e| VaultError::EncryptionError(e.to_string())),
+        None => Ok(payload),
+    }
+}
+
+/// Bytes of a note entry written between progress reports and cancel checks
+const EXPORT_PROGRESS_CHUNK: usize = 64 * 1024;
+
+/// Write the metadata, note and (optional) manifest entries of an archive
+///
+/// Returns the total size of the entries before compression.
+#[allow(clippy::too_many_arguments)]
+fn write_entries<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    vault: &Vault,
+    notes: &[Note],
+    encryption: Option<&EncryptionManager>,
+    export_options: &ExportOptions,
+    cancel: Option<&AtomicBool>,
+    progress: &mut dyn FnMut(u64),
+) -> Result<u64, VaultError> {
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    // Write metadata, recording the password verifier if the vault lacks one
+    let mut vault = vault.clone();
+    if let (Some(enc), None) = (encryption, &vault.auth_tag) {
+        vault.auth_tag = Some(enc.auth_tag());
+    }
+    let metadata = VaultMetadata {
+        vault,
+        note_count: notes.len(),
+        export_date: Utc::now(),
+        version: VAULT_FORMAT_VERSION.to_string(),
+    };
+    let metadata_json = serde_json::to_string_pretty(&metadata)?;
+    zip.start_file("metadata.json", options)?;
+    zip.write_all(metadata_json.as_bytes())?;
+    let mut bytes_uncompressed = metadata_json.len() as u64;
+    progress(bytes_uncompressed);
+
+    // Write notes
+    let mut manifest = VaultManifest {
+        entries: Vec::new(),
+    };
+    for note in notes {
+        check_cancelled(cancel)?;
+
+        let data = encode_note(note, encryption)?;
+
+        let filename = if export_options.group_by_tag {
+            grouped_note_path(note)
+        } else {
+            note_path(&note.id)
+        };
+        if export_options.include_manifest {
+            manifest.entries.push(ManifestEntry {
+                id: note.id,
+                filename: filename.clone(),
+                sha256: sha256_hex(&data),
+            });
+        }
+        zip.start_file(filename, options)?;
+        for chunk in data.chunks(EXPORT_PROGRESS_CHUNK) {
+            check_cancelled(cancel)?;
+            zip.write_all(chunk)?;
+            bytes_uncompressed += chunk.len() as u64;
+            progress(bytes_uncompressed);
+        }
+    }
+
+    if export_options.include_manifest {
+        let manifest_json = serde_json::to_string_pretty(&manifest)?;
+        zip.start_file("manifest.json", options)?;
+        zip.write_all(manifest_json.as_bytes())?;
+        bytes_uncompressed += manifest_json.len() as u64;
+        progress(bytes_uncompressed);
+    }
+
+    Ok(bytes_uncompressed)
+}
+
+/// A seekable writer that discards data, tracking only the resulting length
+#[derive(Default)]
+struct CountingSink {
+    position: u64,
+    len: u64,
+}
+
+impl Write for CountingSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.position += buf.len() as u64;
+        self.len = self.len.max(self.position);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for CountingSink {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            std::io::SeekFrom::Start(offset) => Some(offset),
+            std::io::SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            std::io::SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = target
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek"))?;
+        Ok(self.position)
+    }
+}
+
+/// Fail unless `path` could be opened for writing, leaving the filesystem as found
+fn check_writable(path: &Path) -> Result<(), VaultError> {
+    if path.exists() {
+        std::fs::OpenOptions::new().append(true).open(path)?;
+    } else {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Adapt a note callback for `import_archive`, failing on the first bad entry
+fn strict<F: FnMut(Note)>(
+    mut on_note: F,
+) -> impl FnMut(String, Result<Note, VaultError>) -> Result<(), VaultError> {
+    move |_, note| {
+        on_note(note?);
+        Ok(())
+    }
+}
+
+/// Fail with `VaultError::Cancelled` if the cancellation flag is set
+fn check_cancelled(cancel: Option<&AtomicBool>) -> Result<(), VaultError> {
+    match cancel {
+        Some(flag) if flag.load(Ordering::SeqCst) => Err(VaultError::Cancelled),
+        _ => Ok(()),
+    }
+}
+
+/// Lowercase hex SHA-256 of `data`
+fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+/// Parse a `major.minor` format version
+fn parse_format_version(version: &str) -> Option<(u32, u32)> {
+    let (major, minor) = version.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Fail with `VaultError::UnsupportedVaultVersion` unless this build can read `version`
+fn check_format_version(version: &str) -> Result<(), VaultError> {
+    let supported = parse_format_version(VAULT_FORMAT_VERSION).expect("valid format version");
+    match parse_format_version(version) {
+        Some(found) if found <= supported => Ok(()),
+        _ => Err(VaultError::UnsupportedVaultVersion(version.to_string())),
+    }
+}
+
+/// Read and parse `metadata.json` from an open vault archive
+fn read_archive_metadata<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    limits: &ImportLimits,
+    bytes_read: &mut u64,
+) -> Result<VaultMetadata, VaultError> {
+    let mut metadata_file = zip.by_name("metadata.json")?;
+    let metadata_json = read_entry(&mut metadata_file, "metadata.json", limits, bytes_read)?;
+    Ok(serde_json::from_slice(&metadata_json)?)
+}
+
+/// Read an archive entry, failing with `VaultError::TooLarge` past the limits
+///
+/// `bytes_read` is the running total for the current import and is advanced by
+/// the size of the entry.
+fn read_entry<R: Read>(
+    entry: &mut R,
+    name: &str,
+    limits: &ImportLimits,
+    bytes_read: &mut u64,
+) -> Result<Vec<u8>, VaultError> {
+    let remaining_total = limits.max_total_bytes.saturating_sub(*bytes_read);
+    let cap = limits.max_entry_bytes.min(remaining_total);
+
+    let mut data = Vec::new();
+    entry.take(cap.saturating_add(1)).read_to_end(&mut data)?;
+    if data.len() as u64 > cap {
+        return Err(VaultError::TooLarge(if cap == limits.max_entry_bytes {
+            format!("entry '{}' exceeds {} bytes", name, limits.max_entry_bytes)
+        } else {
+            format!("archive exceeds {} bytes", limits.max_total_bytes)
+        }));
+    }
+
+    *bytes_read += data.len() as u64;
+    Ok(data)
+}
+
+/// Decrypt (if needed) and parse a note entry read from a vault archive
+fn decode_note(data: Vec<u8>, encryption: Option<&EncryptionManager>) -> Result<Note, VaultError> {
+    parse_note_json(decrypt_note_data(data, encryption)?)
+}
+
+/// Decode a note file written by `encode_stored_note`
+///
+/// The gzip check runs on the decrypted payload, which is JSON unless
+/// compressed, so ciphertext that happens to start with the gzip magic
+/// bytes can't be mistaken for a compressed note.
+fn decode_stored_note(
+    data: Vec<u8>,
+    encryption: Option<&EncryptionManager>,
+) -> Result<Note, VaultError> {
+    let payload = decrypt_note_data(data, encryption)?;
+    parse_note_json(crate::storage::decompress(payload)?)
+}
+
+/// Decrypt note data if an encryption manager is given
+fn decrypt_note_data(
+    data: Vec<u8>,
+    encryption: Option<&EncryptionManager>,
+) -> Result<Vec<u8>, VaultError> {
+    match encryption {
+        Some(enc) => enc
+            .decrypt(&data)
+            .map_err(|e| VaultError::EncryptionError(e.to_string())),
+        None => Ok(data),
+    }
+}
+
+/// Parse a note from its JSON bytes, migrating older layouts to the current one
+fn parse_note_json(data: Vec<u8>) -> Result<Note, VaultError> {
+    let note_json = String::from_utf8(data).map_err(|_| VaultError::InvalidFormat)?;
+    Ok(migrate_note(serde_json::from_str(&note_json)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::EncryptionManager;
+    use crate::search::IndexChange;
+    use std::io::Cursor;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export_import_vault() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let vault = Vault::new(
+            "Test Vault".to_string(),
+            "Description".to_string(),
+            "salt123".to_string(),
+        );
+
+        let notes = vec![
+            Note::new("Note 1".to_string(), "Content 1".to_string(), vec![]),
+            Note::new("Note 2".to_string(), "Content 2".to_string(), vec![]),
+        ];
+
+        let export_path = temp_dir.path().join("export.zip");
+        manager
+            .export_vault(&vault, &notes, &export_path, None)
+            .unwrap();
+
+        let (imported_vault, imported_notes) = manager
+            .import_vault(&export_path, None, ConflictResolution::Overwrite)
+            .unwrap();
+
+        assert_eq!(imported_vault.id, vault.id);
+        assert_eq!(imported_notes.len(), 2);
+    }
+
+    #[test]
+    fn test_conflict_detection() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let note1 = Note::new("Note".to_string(), "Content".to_string(), vec![]);
+
+        let mut note2 = note1.clone();
+        note2.update("Updated".to_string(), "New Content".to_string(), vec![]);
+
+        let conflicts = manager.detect_conflicts(&[note1], &[note2]);
+        assert_eq!(conflicts.len(), 1);
+    }
+
+    #[test]
+    fn test_conflict_sensitivity() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+        let modes = [
+            ConflictSensitivity::VersionOnly,
+            ConflictSensitivity::VersionAndTimestamp,
+            ConflictSensitivity::ContentAware,
+        ];
+
+        let note = Note::new("Note".to_string(), "Content".to_string(), vec![]);
+        let mut skewed = note.clone();
+        skewed.updated_at += chrono::Duration::seconds(30);
+        let counts: Vec<usize> = modes
+            .iter()
+            .map(|&mode| {
+                manager
+                    .detect_conflicts_with(
+                        std::slice::from_ref(&note),
+                        std::slice::from_ref(&skewed),
+                        mode,
+                    )
+                    .len()
+            })
+            .collect();
+        assert_eq!(counts, vec![0, 1, 0]);
+
+        let mut edited = note.clone();
+        edited.update("Note".to_string(), "Changed".to_string(), vec![]);
+        for mode in modes {
+            let conflicts = manager.detect_conflicts_with(
+                std::slice::from_ref(&note),
+                std::slice::from_ref(&edited),
+                mode,
+            );
+            assert_eq!(conflicts.len(), 1, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn test_import_vault_streaming() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let vault = Vault::new("Test Vault".to_string(), "Description".to_string(), salt);
+
+        let notes: Vec<Note> = (0..3)
+            .map(|i| Note::new(format!("Note {}", i), format!("Content {}", i), vec![]))
+            .collect();
+
+        let export_path = temp_dir.path().join("export.zip");
+        manager
+            .export_vault(&vault, &notes, &export_path, Some(&encryption))
+            .unwrap();
+
+        let bytes = std::fs::read(&export_path).unwrap();
+        let mut delivered = Vec::new();
+        let imported_vault = manager
+            .import_vault_streaming(Cursor::new(bytes), Some(&encryption), |note| {
+                delivered.push(note.id)
+            })
+            .unwrap();
+
+        assert_eq!(imported_vault.id, vault.id);
+        assert_eq!(delivered.len(), notes.len());
+        for note in &notes {
+            assert_eq!(delivered.iter().filter(|id| **id == note.id).count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_export_stats() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let vault = Vault::new(
+            "Test Vault".to_string(),
+            "Description".to_string(),
+            "salt123".to_string(),
+        );
+        let notes: Vec<Note> = (0..5)
+            .map(|i| Note::new(format!("Note {}", i), "word ".repeat(200), vec![]))
+            .collect();
+
+        let export_path = temp_dir.path().join("export.zip");
+        let stats = manager
+            .export_vault(&vault, &notes, &export_path, None)
+            .unwrap();
+
+        assert_eq!(stats.notes_written, 5);
+        assert_eq!(stats.output_path, export_path);
+        assert_eq!(
+            stats.bytes_compressed,
+            std::fs::metadata(&export_path).unwrap().len()
+        );
+        assert!(stats.bytes_compressed > 0);
+        assert!(stats.bytes_compressed < stats.bytes_uncompressed);
+    }
+
+    #[test]
+    fn test_export_note_html() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let note = Note::new("Shared".to_string(), "Hello **world**".to_string(), vec![]);
+        let path = temp_dir.path().join("note.html");
+        manager.export_note_html(&note, &path).unwrap();
+
+        let html = std::fs::read_to_string(&path).unwrap();
+        assert!(html.contains("<h1>Shared</h1>"));
+        assert!(html.contains("<strong>world</strong>"));
+    }
+
+    #[test]
+    fn test_migrate_vault_salt() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        // A legacy vault with a padded salt that SaltString rejects
+        let legacy_salt = "bGVnYWN5LXNhbHQtdmFsdWU=";
+        assert!(EncryptionManager::new_from_password("password", legacy_salt).is_err());
+        let vault = Vault::new("Legacy".to_string(), String::new(), legacy_salt.to_string());
+
+        let legacy_key =
+            EncryptionManager::new_from_password("password", &normalize_salt(legacy_salt).unwrap())
+                .unwrap();
+        let mut note = Note::new("Old note".to_string(), String::new(), vec![]);
+        note.content = general_purpose::STANDARD.encode(legacy_key.encrypt(b"recovered").unwrap());
+
+        let (migrated_vault, migrated_notes) = manager
+            .migrate_vault_salt(&vault, legacy_salt, "password", &[note.clone()])
+            .unwrap();
+
+        assert_eq!(migrated_vault.id, vault.id);
+        assert_ne!(migrated_vault.salt, legacy_salt);
+        let new_key =
+            EncryptionManager::new_from_password("password", &migrated_vault.salt).unwrap();
+
+        assert_eq!(migrated_notes.len(), 1);
+        assert_eq!(migrated_notes[0].id, note.id);
+        let ciphertext = general_purpose::STANDARD
+            .decode(&migrated_notes[0].content)
+            .unwrap();
+        assert_eq!(new_key.decrypt(&ciphertext).unwrap(), b"recovered");
+
+        // The wrong password cannot migrate
+        assert!(manager
+            .migrate_vault_salt(&vault, legacy_salt, "wrong", &[note.clone()])
+            .is_err());
+
+        // The migrated vault records a verifier for the new salt, which
+        // later migrations check before touching any note
+        let tag = migrated_vault.auth_tag.as_deref().unwrap();
+        assert!(EncryptionManager::verify_password("password", &migrated_vault.salt, tag).unwrap());
+        let mut tagged = vault.clone();
+        tagged.auth_tag = Some(legacy_key.auth_tag());
+        assert!(matches!(
+            manager.migrate_vault_salt(&tagged, legacy_salt, "wrong", &[]),
+            Err(VaultError::EncryptionError(msg))
+                if msg == EncryptionError::AuthenticationFailed.to_string()
+        ));
+        assert!(manager
+            .migrate_vault_salt(&tagged, legacy_salt, "password", &[note])
+            .is_ok());
+    }
+
+    #[test]
+    fn test_export_compression_ratio() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let vault = Vault::new("Test Vault".to_string(), String::new(), salt);
+        let notes: Vec<Note> = (0..5)
+            .map(|i| Note::new(format!("Note {}", i), "repeat ".repeat(500), vec![]))
+            .collect();
+
+        let plain = manager
+            .export_vault(&vault, &notes, &temp_dir.path().join("plain.zip"), None)
+            .unwrap();
+        let encrypted = manager
+            .export_vault(
+                &vault,
+                &notes,
+                &temp_dir.path().join("encrypted.zip"),
+                Some(&encryption),
+            )
+            .unwrap();
+
+        assert!(!plain.encrypted);
+        assert!(encrypted.encrypted);
+        assert!(plain.compression_ratio() < 0.5);
+        assert!(plain.bytes_saved() > 0);
+        assert!(encrypted.compression_ratio() > 0.9);
+        assert!(plain.compression_ratio() < encrypted.compression_ratio());
+    }
+
+    #[test]
+    fn test_cancel_export_and_import() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let vault = Vault::new(
+            "Test Vault".to_string(),
+            String::new(),
+            "salt123".to_string(),
+        );
+        let notes: Vec<Note> = (0..3)
+            .map(|i| Note::new(format!("Note {}", i), String::new(), vec![]))
+            .collect();
+
+        // A cancelled export stops and leaves no partial file behind
+        let cancel = Arc::new(AtomicBool::new(true));
+        let cancelled_path = temp_dir.path().join("cancelled.zip");
+        let result =
+            manager.export_vault_cancellable(&vault, &notes, &cancelled_path, None, &cancel);
+        assert!(matches!(result, Err(VaultError::Cancelled)));
+        assert!(!cancelled_path.exists());
+
+        // An uncancelled export completes
+        cancel.store(false, Ordering::SeqCst);
+        let export_path = temp_dir.path().join("export.zip");
+        manager
+            .export_vault_cancellable(&vault, &notes, &export_path, None, &cancel)
+            .unwrap();
+
+        // Cancelling partway through writing keeps the previous archive intact
+        let previous = std::fs::read(&export_path).unwrap();
+        let mut reports = 0;
+        let result = manager.export_vault_with_progress(
+            &vault,
+            &notes,
+            &export_path,
+            None,
+            Some(&*cancel),
+            &mut |_, _| {
+                reports += 1;
+                cancel.store(true, Ordering::SeqCst);
+            },
+        );
+        assert!(matches!(result, Err(VaultError::Cancelled)));
+        assert_eq!(reports, 1);
+        assert_eq!(std::fs::read(&export_path).unwrap(), previous);
+        assert!(!temp_dir.path().join("export.zip.partial").exists());
+        cancel.store(false, Ordering::SeqCst);
+
+        // Cancelling after the first note stops the import early
+        let file = std::fs::File::open(&export_path).unwrap();
+        let mut delivered = 0;
+        let flag = cancel.clone();
+        let result = manager.import_vault_streaming_cancellable(file, None, &cancel, |_| {
+            delivered += 1;
+            flag.store(true, Ordering::SeqCst);
+        });
+        assert!(matches!(result, Err(VaultError::Cancelled)));
+        assert_eq!(delivered, 1);
+
+        let result = manager.import_vault_cancellable(
+            &export_path,
+            None,
+            ConflictResolution::Overwrite,
+            &cancel,
+        );
+        assert!(matches!(result, Err(VaultError::Cancelled)));
+    }
+
+    #[test]
+    fn test_rename_tag_indexed() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().join("vault")).unwrap();
+        let manager = VaultManager::new(storage);
+        let engine = SearchEngine::new(temp_dir.path().join("index")).unwrap();
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+
+        let mut notes = vec![
+            Note::new("A".to_string(), String::new(), vec!["drafts".to_string()]),
+            Note::new("B".to_string(), String::new(), vec!["drafts".to_string()]),
+            Note::new("C".to_string(), String::new(), vec!["other".to_string()]),
+        ];
+        let mut writer = engine.get_writer().unwrap();
+        for note in &notes {
+            manager.save_note(note, Some(&encryption)).unwrap();
+            engine.upsert_note(&mut writer, note).unwrap();
+        }
+        engine.commit(&mut writer).unwrap();
+        drop(writer);
+
+        let changed = manager
+            .rename_tag_indexed(&engine, &mut notes, "drafts", "ideas", Some(&encryption))
+            .unwrap();
+        assert_eq!(changed, 2);
+
+        // In memory
+        assert_eq!(notes[0].tags, vec!["ideas"]);
+        assert_eq!(notes[2].tags, vec!["other"]);
+
+        // On disk
+        let loaded = manager.load_note(&notes[1].id, Some(&encryption)).unwrap();
+        assert_eq!(loaded.tags, vec!["ideas"]);
+        assert_eq!(loaded.version, 2);
+
+        // In the index
+        assert_eq!(engine.search("tags:ideas", 10).unwrap().len(), 2);
+        assert!(engine.search("tags:drafts", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_iter_notes_in_zip() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let vault = Vault::new("Test Vault".to_string(), String::new(), salt);
+        let notes: Vec<Note> = (0..4)
+            .map(|i| Note::new(format!("Note {}", i), String::new(), vec![]))
+            .collect();
+
+        let export_path = temp_dir.path().join("export.zip");
+        manager
+            .export_vault(&vault, &notes, &export_path, Some(&encryption))
+            .unwrap();
+
+        let ids: Vec<Uuid> = manager
+            .iter_notes_in_zip(&export_path, Some(&encryption))
+            .unwrap()
+            .map(|note| note.unwrap().id)
+            .collect();
+
+        assert_eq!(ids.len(), notes.len());
+        for note in &notes {
+            assert_eq!(ids.iter().filter(|id| **id == note.id).count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_export_import_single_note() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let note = Note::new(
+            "Shared".to_string(),
+            "Secret".to_string(),
+            vec!["share".to_string()],
+        );
+
+        let path = temp_dir.path().join("note.nsnote");
+        manager.export_note(&note, &path, &encryption).unwrap();
+        assert!(std::fs::read(&path).unwrap().starts_with(NOTE_FILE_MAGIC));
+
+        let imported = manager.import_note(&path, "password").unwrap();
+        assert_eq!(imported.id, note.id);
+        assert_eq!(imported.content, "Secret");
+
+        assert!(matches!(
+            manager.import_note(&path, "wrong password"),
+            Err(VaultError::EncryptionError(_))
+        ));
+
+        std::fs::write(&path, b"not a note").unwrap();
+        assert!(matches!(
+            manager.import_note(&path, "password"),
+            Err(VaultError::InvalidFormat)
+        ));
+    }
+
+    #[test]
+    fn test_read_metadata() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let vault = Vault::new(
+            "Preview Vault".to_string(),
+            "Description".to_string(),
+            "salt123".to_string(),
+        );
+        let notes = vec![
+            Note::new("Note 1".to_string(), "Content 1".to_string(), vec![]),
+            Note::new("Note 2".to_string(), "Content 2".to_string(), vec![]),
+            Note::new("Note 3".to_string(), "Content 3".to_string(), vec![]),
+        ];
+
+        let export_path = temp_dir.path().join("export.zip");
+        manager
+            .export_vault(&vault, &notes, &export_path, None)
+            .unwrap();
+
+        let metadata = manager.read_metadata(&export_path).unwrap();
+        assert_eq!(metadata.vault.id, vault.id);
+        assert_eq!(metadata.vault.name, "Preview Vault");
+        assert_eq!(metadata.note_count, 3);
+    }
+
+    #[test]
+    fn test_import_rejects_oversized_entry() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut manager = VaultManager::new(storage);
+
+        let vault = Vault::new(
+            "Test Vault".to_string(),
+            "Description".to_string(),
+            "salt123".to_string(),
+        );
+        let export_path = temp_dir.path().join("export.zip");
+        manager
+            .export_vault(&vault, &[], &export_path, None)
+            .unwrap();
+
+        // Append a highly compressible 8 MiB entry; it shrinks to a few KiB
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&export_path)
+            .unwrap();
+        let mut zip = ZipWriter::new_append(file).unwrap();
+        zip.start_file(
+            format!("notes/{}.json", Uuid::new_v4()),
+            FileOptions::default().compression_method(zip::CompressionMethod::Deflated),
+        )
+        .unwrap();
+        zip.write_all(&vec![b' '; 8 * 1024 * 1024]).unwrap();
+        zip.finish().unwrap();
+        assert!(std::fs::metadata(&export_path).unwrap().len() < 1024 * 1024);
+
+        manager.set_import_limits(ImportLimits {
+            max_entry_bytes: 1024 * 1024,
+            ..ImportLimits::default()
+        });
+        assert!(matches!(
+            manager.import_vault(&export_path, None, ConflictResolution::Overwrite),
+            Err(VaultError::TooLarge(_))
+        ));
+        assert!(matches!(
+            manager
+                .iter_notes_in_zip(&export_path, None)
+                .unwrap()
+                .next(),
+            Some(Err(VaultError::TooLarge(_)))
+        ));
+
+        manager.set_import_limits(ImportLimits {
+            max_entry_bytes: 16 * 1024 * 1024,
+            max_total_bytes: 4 * 1024 * 1024,
+        });
+        assert!(matches!(
+            manager.import_vault(&export_path, None, ConflictResolution::Overwrite),
+            Err(VaultError::TooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_vault_manifest() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let vault = Vault::new("Test Vault".to_string(), "Description".to_string(), salt);
+        let notes = vec![
+            Note::new("Note 1".to_string(), "Content 1".to_string(), vec![]),
+            Note::new("Note 2".to_string(), "Content 2".to_string(), vec![]),
+            Note::new("Note 3".to_string(), "Content 3".to_string(), vec![]),
+        ];
+
+        let export_path = temp_dir.path().join("export.zip");
+        let options = ExportOptions {
+            include_manifest: true,
+            ..ExportOptions::default()
+        };
+        manager
+            .export_vault_with_options(&vault, &notes, &export_path, Some(&encryption), &options)
+            .unwrap();
+        assert!(manager.verify_vault(&export_path).unwrap().is_ok());
+
+        // Rewrite the archive with one byte of the second note flipped
+        let tampered_path = temp_dir.path().join("tampered.zip");
+        let mut source = ZipArchive::new(std::fs::File::open(&export_path).unwrap()).unwrap();
+        let mut target = ZipWriter::new(std::fs::File::create(&tampered_path).unwrap());
+        for i in 0..source.len() {
+            let mut entry = source.by_index(i).unwrap();
+            let name = entry.name().to_string();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            if name == note_path(&notes[1].id) {
+                let last = data.len() - 1;
+                data[last] ^= 0x01;
+            }
+            target.start_file(name, FileOptions::default()).unwrap();
+            target.write_all(&data).unwrap();
+        }
+        target.finish().unwrap();
+
+        let report = manager.verify_vault(&tampered_path).unwrap();
+        assert_eq!(report.mismatched, vec![notes[1].id]);
+        assert!(report.missing.is_empty());
+        assert!(report.unlisted.is_empty());
+
+        // Archives exported without a manifest can't be verified
+        manager
+            .export_vault(&vault, &notes, &export_path, None)
+            .unwrap();
+        assert!(matches!(
+            manager.verify_vault(&export_path),
+            Err(VaultError::InvalidFormat)
+        ));
+    }
+
+    #[test]
+    fn test_notes_modified_since() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let since = Utc::now() - chrono::Duration::hours(1);
+
+        let recent = Note::new("Recent".to_string(), "New".to_string(), vec![]);
+        manager.save_note(&recent, Some(&encryption)).unwrap();
+
+        // Recently written file, but the note itself was last updated long ago
+        let mut stale = Note::new("Stale".to_string(), "Old".to_string(), vec![]);
+        stale.updated_at = since - chrono::Duration::days(30);
+        manager.save_note(&stale, Some(&encryption)).unwrap();
+
+        // An old file that can't be decrypted: reading it would fail the call
+        let old_id = Uuid::new_v4();
+        let old_path = note_path(&old_id);
+        manager
+            .storage()
+            .write_file(&old_path, b"not decryptable")
+            .unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(manager.storage().get_path(&old_path))
+            .unwrap()
+            .set_modified(SystemTime::from(since - chrono::Duration::days(30)))
+            .unwrap();
+
+        let notes = manager
+            .notes_modified_since(since, Some(&encryption))
+            .unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].id, recent.id);
+    }
+
+    #[test]
+    fn test_infer_timestamps() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let mut zeroed = Note::new("Zeroed".to_string(), String::new(), vec![]);
+        zeroed.created_at = DateTime::<Utc>::from_timestamp(0, 0).unwrap();
+        manager.save_note(&zeroed, None).unwrap();
+        let mtime = DateTime::<Utc>::from_timestamp(1_600_000_000, 0).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(manager.storage().get_path(&note_path(&zeroed.id)))
+            .unwrap()
+            .set_modified(SystemTime::from(mtime))
+            .unwrap();
+
+        // Not in storage, so only the ordering can be fixed
+        let mut reversed = Note::new("Reversed".to_string(), String::new(), vec![]);
+        reversed.updated_at = reversed.created_at - chrono::Duration::days(3);
+        let fine = Note::new("Fine".to_string(), String::new(), vec![]);
+
+        let mut notes = vec![zeroed.clone(), reversed.clone(), fine.clone()];
+        assert_eq!(manager.infer_timestamps(&mut notes), 2);
+        assert_eq!(notes[0].created_at, mtime);
+        assert_eq!(notes[0].updated_at, zeroed.updated_at);
+        assert_eq!(notes[1].created_at, reversed.created_at);
+        assert_eq!(notes[1].updated_at, reversed.created_at);
+        assert_eq!(notes[2].updated_at, fine.updated_at);
+    }
+
+    #[test]
+    fn test_move_note() {
+        let temp_dir = tempdir().unwrap();
+        let open = |name: &str| {
+            let storage = FileStorage::new(temp_dir.path().join(name)).unwrap();
+            let engine =
+                SearchEngine::new(temp_dir.path().join(format!("{}-index", name))).unwrap();
+            let salt = EncryptionManager::generate_salt();
+            let encryption = EncryptionManager::new_from_password(name, &salt).unwrap();
+            (VaultManager::new(storage), engine, encryption)
+        };
+        let (source, source_index, source_enc) = open("source");
+        let (target, target_index, target_enc) = open("target");
+
+        let note = Note::new("Traveller".to_string(), "moving".to_string(), vec![]);
+        source.save_note(&note, Some(&source_enc)).unwrap();
+        source_index
+            .with_writer_once(|writer| source_index.upsert_note(writer, &note))
+            .unwrap();
+
+        source
+            .move_note(
+                &note.id,
+                &source_index,
+                &target,
+                &target_index,
+                Some(&source_enc),
+                Some(&target_enc),
+            )
+            .unwrap();
+
+        let moved = target.load_note(&note.id, Some(&target_enc)).unwrap();
+        assert_eq!((moved.id, moved.content.as_str()), (note.id, "moving"));
+        assert!(source.load_note(&note.id, Some(&source_enc)).is_err());
+        assert!(target_index
+            .get_by_id(&note.id.to_string())
+            .unwrap()
+            .is_some());
+        assert!(source_index
+            .get_by_id(&note.id.to_string())
+            .unwrap()
+            .is_none());
+        // Neither index is left locked
+        drop(source_index.session().unwrap());
+        drop(target_index.session().unwrap());
+
+        // Moving onto an existing note is refused and leaves both copies alone
+        source.save_note(&note, Some(&source_enc)).unwrap();
+        assert!(matches!(
+            source.move_note(
+                &note.id,
+                &source_index,
+                &target,
+                &target_index,
+                Some(&source_enc),
+                Some(&target_enc),
+            ),
+            Err(VaultError::NoteExists(_))
+        ));
+        assert!(source.load_note(&note.id, Some(&source_enc)).is_ok());
+    }
+
+    #[test]
+    fn test_search_keywords_with_encrypted_storage() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().join("vault")).unwrap();
+        let manager = VaultManager::new(storage);
+        let engine = SearchEngine::new(temp_dir.path().join("index")).unwrap();
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+
+        let mut note = Note::new("Taxes".to_string(), "secretsalary".to_string(), vec![]);
+        note.search_keywords = vec!["finance".to_string()];
+        manager.save_note(&note, Some(&encryption)).unwrap();
+        manager
+            .reindex_note_from_storage(&note.id, &engine, Some(&encryption))
+            .unwrap();
+
+        assert_eq!(engine.search("finance", 10).unwrap().len(), 1);
+        assert!(engine.search("secretsalary", 10).unwrap().is_empty());
+
+        let stored = manager.storage().read_file(&note_path(&note.id)).unwrap();
+        let needle = b"secretsalary";
+        assert!(!stored.windows(needle.len()).any(|w| w == needle));
+        assert!(!String::from_utf8_lossy(&stored).contains("finance"));
+    }
+
+    #[test]
+    fn test_reindex_note_from_storage() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().join("vault")).unwrap();
+        let manager = VaultManager::new(storage);
+        let engine = SearchEngine::new(temp_dir.path().join("index")).unwrap();
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+
+        let mut note = Note::new("Watched".to_string(), "original".to_string(), vec![]);
+        manager.save_note(&note, Some(&encryption)).unwrap();
+        manager
+            .reindex_note_from_storage(&note.id, &engine, Some(&encryption))
+            .unwrap();
+        assert_eq!(engine.search("original", 10).unwrap().len(), 1);
+
+        // Simulate another process rewriting the file
+        note.update(note.title.clone(), "rewritten".to_string(), vec![]);
+        manager.save_note(&note, Some(&encryption)).unwrap();
+        manager
+            .reindex_note_from_storage(&note.id, &engine, Some(&encryption))
+            .unwrap();
+        assert!(engine.search("original", 10).unwrap().is_empty());
+        assert_eq!(engine.search("rewritten", 10).unwrap().len(), 1);
+
+        manager.storage().delete_file(&note_path(&note.id)).unwrap();
+        manager
+            .reindex_note_from_storage(&note.id, &engine, Some(&encryption))
+            .unwrap();
+        assert!(engine.get_by_id(&note.id.to_string()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_reindex_then_rename_tag_on_one_engine() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().join("vault")).unwrap();
+        let manager = VaultManager::new(storage);
+        let engine = SearchEngine::new(temp_dir.path().join("index")).unwrap();
+
+        let mut notes = vec![Note::new(
+            "Tagged".to_string(),
+            "body".to_string(),
+            vec!["old".to_string()],
+        )];
+        manager.save_note(&notes[0], None).unwrap();
+        manager
+            .reindex_note_from_storage(&notes[0].id, &engine, None)
+            .unwrap();
+
+        // The reindex left no writer holding the index lock
+        assert_eq!(
+            manager
+                .rename_tag_indexed(&engine, &mut notes, "old", "new", None)
+                .unwrap(),
+            1
+        );
+        assert_eq!(engine.notes_with_tag("new", false, 10).unwrap().len(), 1);
+        drop(engine.session().unwrap());
+
+        // A writer the caller pooled is reused rather than competed with
+        engine.with_writer(|_| Ok(())).unwrap();
+        assert_eq!(
+            manager
+                .rename_tag_indexed(&engine, &mut notes, "new", "newer", None)
+                .unwrap(),
+            1
+        );
+        assert_eq!(engine.notes_with_tag("newer", false, 10).unwrap().len(), 1);
+        assert!(matches!(engine.session(), Err(SearchError::IndexBusy)));
+        engine.release_writer();
+        drop(engine.session().unwrap());
+    }
+
+    #[test]
+    fn test_export_vault_redacted() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+        let vault = Vault::new(
+            "Shared".to_string(),
+            String::new(),
+            EncryptionManager::generate_salt(),
+        );
+        let notes = vec![Note::new(
+            "Keys".to_string(),
+            "mail carol@example.com".to_string(),
+            vec![],
+        )];
+
+        let path = temp_dir.path().join("redacted.zip");
+        manager
+            .export_vault_redacted(
+                &vault,
+                &notes,
+                &path,
+                None,
+                &crate::models::default_redaction_patterns(),
+                "***",
+            )
+            .unwrap();
+
+        let (_, imported) = manager
+            .import_vault(&path, None, ConflictResolution::Overwrite)
+            .unwrap();
+        assert_eq!(imported[0].content, "mail ***");
+        assert_eq!(notes[0].content, "mail carol@example.com");
+    }
+
+    #[test]
+    fn test_append_log_replay() {
+        let temp_dir = tempdir().unwrap();
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let path = temp_dir.path().join("notes.log");
+        let log = AppendLog::new(path.clone(), &encryption);
+        assert!(log.replay().unwrap().is_empty());
+
+        let mut journal = Note::new("Journal".to_string(), "day 1".to_string(), vec![]);
+        let scratch = Note::new("Scratch".to_string(), "temp".to_string(), vec![]);
+        log.append(&LogRecord::Upsert(journal.clone())).unwrap();
+        log.append(&LogRecord::Upsert(scratch.clone())).unwrap();
+        for day in 2..=3 {
+            journal.update(journal.title.clone(), format!("day {}", day), vec![]);
+            log.append(&LogRecord::Upsert(journal.clone())).unwrap();
+        }
+        log.append(&LogRecord::Delete(scratch.id)).unwrap();
+
+        let notes = log.replay().unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].id, journal.id);
+        assert_eq!(notes[0].content, "day 3");
+        assert_eq!(notes[0].version, 3);
+
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("day 3"));
+
+        // A record cut off by a crash is ignored
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&[0, 0, 1, 0, 42]).unwrap();
+        assert_eq!(log.replay().unwrap()[0].content, "day 3");
+
+        // The next append, even from a fresh handle, cuts it off first
+        let log = AppendLog::new(path.clone(), &encryption);
+        let later = Note::new("Later".to_string(), "after crash".to_string(), vec![]);
+        log.append(&LogRecord::Upsert(later.clone())).unwrap();
+        let notes = log.replay().unwrap();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].content, "day 3");
+        assert_eq!(notes[1].id, later.id);
+
+        // A torn magic header is restarted rather than rejected
+        let torn = temp_dir.path().join("torn.log");
+        std::fs::write(&torn, &LOG_FILE_MAGIC[..3]).unwrap();
+        let log = AppendLog::new(torn.clone(), &encryption);
+        assert!(log.replay().unwrap().is_empty());
+        log.append(&LogRecord::Upsert(later.clone())).unwrap();
+        assert_eq!(log.replay().unwrap().len(), 1);
+
+        // Anything else short is still not a log
+        std::fs::write(&torn, b"NSX").unwrap();
+        assert!(matches!(log.replay(), Err(VaultError::InvalidFormat)));
+    }
+
+    #[test]
+    fn test_estimate_import_memory() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let vault = Vault::new("Sized".to_string(), String::new(), salt);
+
+        let estimate = |count: usize, size: usize| {
+            let notes: Vec<Note> = (0..count)
+                .map(|i| Note::new(format!("Note {}", i), "x".repeat(size), vec![]))
+                .collect();
+            let path = temp_dir.path().join(format!("{}-{}.zip", count, size));
+            manager
+                .export_vault(&vault, &notes, &path, Some(&encryption))
+                .unwrap();
+            manager.estimate_import_memory(&path).unwrap()
+        };
+
+        let base = estimate(10, 1_000);
+        let more_notes = estimate(20, 1_000);
+        let bigger_notes = estimate(10, 10_000);
+        assert!(base >= 10 * 1_000);
+        assert!(more_notes > base * 3 / 2, "{} vs {}", more_notes, base);
+        assert!(bigger_notes > base * 5, "{} vs {}", bigger_notes, base);
+    }
+
+    #[test]
+    fn test_export_search_results() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().join("vault")).unwrap();
+        let manager = VaultManager::new(storage);
+        let engine = SearchEngine::new(temp_dir.path().join("index")).unwrap();
+        let vault = Vault::new(
+            "Work".to_string(),
+            String::new(),
+            EncryptionManager::generate_salt(),
+        );
+
+        let notes = [
+            Note::new("Apollo kickoff".to_string(), "scope".to_string(), vec![]),
+            Note::new("Budget".to_string(), "apollo costs".to_string(), vec![]),
+            Note::new("Holiday".to_string(), "beach".to_string(), vec![]),
+        ];
+        let changes: Vec<IndexChange> = notes.iter().cloned().map(IndexChange::Add).collect();
+        engine.apply_changes(&changes).unwrap();
+        let notes_by_id: std::collections::HashMap<Uuid, Note> =
+            notes.iter().map(|note| (note.id, note.clone())).collect();
+
+        let path = temp_dir.path().join("apollo.zip");
+        let (stats, skipped) = manager
+            .export_search_results(&engine, "apollo", &vault, &notes_by_id, &path, None)
+            .unwrap();
+        assert_eq!(stats.notes_written, 2);
+        assert_eq!(skipped, 0);
+
+        let (_, imported) = manager
+            .import_vault(&path, None, ConflictResolution::Overwrite)
+            .unwrap();
+        let mut ids: Vec<Uuid> = imported.iter().map(|note| note.id).collect();
+        ids.sort();
+        let mut expected = vec![notes[0].id, notes[1].id];
+        expected.sort();
+        assert_eq!(ids, expected);
+
+        // A result cap smaller than the hits still exports every match, and a
+        // stale hit is reported rather than taking a real match's place
+        let mut engine = engine;
+        engine.set_max_results(Some(1));
+        let stale = Note::new("Apollo retro".to_string(), String::new(), vec![]);
+        engine.apply_changes(&[IndexChange::Add(stale)]).unwrap();
+        let (stats, skipped) = manager
+            .export_search_results(&engine, "apollo", &vault, &notes_by_id, &path, None)
+            .unwrap();
+        assert_eq!(stats.notes_written, 2);
+        assert_eq!(skipped, 1);
+    }
+
+    #[test]
+    fn test_audit_index() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().join("vault")).unwrap();
+        let manager = VaultManager::new(storage);
+        let engine = SearchEngine::new(temp_dir.path().join("index")).unwrap();
+
+        let in_sync = Note::new("In sync".to_string(), String::new(), vec![]);
+        let deleted = Note::new("Deleted".to_string(), String::new(), vec![]);
+        let unindexed = Note::new("Unindexed".to_string(), String::new(), vec![]);
+        let mut edited = Note::new("Edited".to_string(), String::new(), vec![]);
+
+        let mut writer = engine.get_writer().unwrap();
+        for note in [&in_sync, &deleted, &edited] {
+            engine.upsert_note(&mut writer, note).unwrap();
+        }
+        engine.commit(&mut writer).unwrap();
+
+        edited.add_tag("changed");
+        for note in [&in_sync, &unindexed, &edited] {
+            manager.save_note(note, None).unwrap();
+        }
+
+        let report = manager.audit_index(&engine, None).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.missing_on_disk, vec![deleted.id.to_string()]);
+        assert_eq!(report.missing_from_index, vec![unindexed.id]);
+        assert_eq!(report.version_mismatch, vec![edited.id]);
+    }
+
+    #[test]
+    fn test_export_vault_validate() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let vault = Vault::new(
+            "Test Vault".to_string(),
+            "Description".to_string(),
+            "salt123".to_string(),
+        );
+        let notes = vec![
+            Note::new("Note 1".to_string(), "Content 1".to_string(), vec![]),
+            Note::new("Note 2".to_string(), "Content 2".to_string(), vec![]),
+        ];
+        let export_path = temp_dir.path().join("export.zip");
+
+        let validated = manager
+            .export_vault_validate(&vault, &notes, &export_path, None)
+            .unwrap();
+        assert!(!export_path.exists());
+
+        let exported = manager
+            .export_vault(&vault, &notes, &export_path, None)
+            .unwrap();
+        assert_eq!(validated.notes_written, exported.notes_written);
+        assert_eq!(validated.bytes_uncompressed, exported.bytes_uncompressed);
+        assert!(
+            validated
+                .bytes_compressed
+                .abs_diff(exported.bytes_compressed)
+                < 16
+        );
+
+        // A note that can't be encoded: this manager refuses to encrypt until
+        // its counter state is restored
+        let salt = EncryptionManager::generate_salt();
+        let mut encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let key_id = encryption.key_id();
+        encryption.enable_counter_nonces(Some(&key_id));
+        let fresh_path = temp_dir.path().join("fresh.zip");
+        assert!(matches!(
+            manager.export_vault_validate(&vault, &notes, &fresh_path, Some(&encryption)),
+            Err(VaultError::EncryptionError(_))
+        ));
+        assert!(!fresh_path.exists());
+
+        // Unwritable output location
+        let bad_path = temp_dir.path().join("missing-dir").join("export.zip");
+        assert!(matches!(
+            manager.export_vault_validate(&vault, &notes, &bad_path, None),
+            Err(VaultError::IoError(_))
+        ));
+    }
+
+    #[test]
+    fn test_export_grouped_by_tag() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let vault = Vault::new(
+            "Test Vault".to_string(),
+            "Description".to_string(),
+            "salt123".to_string(),
+        );
+        let notes = vec![
+            Note::new(
+                "Tagged".to_string(),
+                "Content".to_string(),
+                vec!["work/project".to_string(), "other".to_string()],
+            ),
+            Note::new("Untagged".to_string(), "Content".to_string(), vec![]),
+            Note::new(
+                "Sneaky".to_string(),
+                "Content".to_string(),
+                vec!["../../etc".to_string()],
+            ),
+        ];
+
+        let grouped_path = temp_dir.path().join("grouped.zip");
+        let options = ExportOptions {
+            group_by_tag: true,
+            ..ExportOptions::default()
+        };
+        manager
+            .export_vault_with_options(&vault, &notes, &grouped_path, None, &options)
+            .unwrap();
+
+        let archive = ZipArchive::new(std::fs::File::open(&grouped_path).unwrap()).unwrap();
+        let names: Vec<&str> = archive.file_names().collect();
+        assert!(names.contains(&format!("notes/work/project/{}.json", notes[0].id).as_str()));
+        assert!(names.contains(&format!("notes/_untagged/{}.json", notes[1].id).as_str()));
+        assert!(names.contains(&format!("notes/etc/{}.json", notes[2].id).as_str()));
+
+        let flat_path = temp_dir.path().join("flat.zip");
+        manager
+            .export_vault(&vault, &notes, &flat_path, None)
+            .unwrap();
+
+        let ids = |path: &Path| {
+            let (_, imported) = manager
+                .import_vault(path, None, ConflictResolution::Overwrite)
+                .unwrap();
+            let mut ids: Vec<Uuid> = imported.iter().map(|n| n.id).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(&grouped_path), ids(&flat_path));
+        assert_eq!(ids(&grouped_path).len(), 3);
+    }
+
+    #[test]
+    fn test_import_checks_format_version() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let vault = Vault::new(
+            "Test Vault".to_string(),
+            "Description".to_string(),
+            "salt123".to_string(),
+        );
+        let note = Note::new("Note".to_string(), "Content".to_string(), vec![]);
+
+        let write_archive = |version: &str| {
+            let path = temp_dir.path().join(format!("v{}.zip", version));
+            let metadata = VaultMetadata {
+                vault: vault.clone(),
+                note_count: 1,
+                export_date: Utc::now(),
+                version: version.to_string(),
+            };
+            let mut zip = ZipWriter::new(std::fs::File::create(&path).unwrap());
+            zip.start_file("metadata.json", FileOptions::default())
+                .unwrap();
+            zip.write_all(&serde_json::to_vec(&metadata).unwrap())
+                .unwrap();
+            zip.start_file(note_path(&note.id), FileOptions::default())
+                .unwrap();
+            zip.write_all(&encode_note(&note, None).unwrap()).unwrap();
+            zip.finish().unwrap();
+            path
+        };
+
+        for version in [VAULT_FORMAT_VERSION, "0.9"] {
+            let (_, notes) = manager
+                .import_vault(&write_archive(version), None, ConflictResolution::Overwrite)
+                .unwrap();
+            assert_eq!(notes.len(), 1);
+        }
+
+        for version in ["1.1", "2.0", "garbage"] {
+            assert!(matches!(
+                manager.import_vault(&write_archive(version), None, ConflictResolution::Overwrite),
+                Err(VaultError::UnsupportedVaultVersion(found)) if found == version
+            ));
+        }
+    }
+
+    #[test]
+    fn test_import_rejects_malformed_salt() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let notes = vec![Note::new("Note".to_string(), "Content".to_string(), vec![])];
+
+        let good = Vault::new("Vault".to_string(), String::new(), salt.clone());
+        let good_path = temp_dir.path().join("good.zip");
+        manager
+            .export_vault(&good, &notes, &good_path, Some(&encryption))
+            .unwrap();
+        assert!(manager
+            .import_vault(&good_path, Some(&encryption), ConflictResolution::Overwrite)
+            .is_ok());
+
+        let tampered = Vault::new("Vault".to_string(), String::new(), salt[..8].to_string());
+        let tampered_path = temp_dir.path().join("tampered.zip");
+        manager
+            .export_vault(&tampered, &notes, &tampered_path, Some(&encryption))
+            .unwrap();
+        assert!(matches!(
+            manager.import_vault(
+                &tampered_path,
+                Some(&encryption),
+                ConflictResolution::Overwrite
+            ),
+            Err(VaultError::InvalidFormat)
+        ));
+    }
+
+    #[test]
+    fn test_import_vault_lenient() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let vault = Vault::new(
+            "Test Vault".to_string(),
+            "Description".to_string(),
+            "salt123".to_string(),
+        );
+        let notes = vec![
+            Note::new("Note 1".to_string(), "Content 1".to_string(), vec![]),
+            Note::new("Note 2".to_string(), "Content 2".to_string(), vec![]),
+        ];
+        let export_path = temp_dir.path().join("export.zip");
+        manager
+            .export_vault(&vault, &notes, &export_path, None)
+            .unwrap();
+
+        // Add a corrupt note entry
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&export_path)
+            .unwrap();
+        let mut zip = ZipWriter::new_append(file).unwrap();
+        let bad_name = format!("notes/{}.json", Uuid::new_v4());
+        zip.start_file(bad_name.as_str(), FileOptions::default())
+            .unwrap();
+        zip.write_all(b"{ not json").unwrap();
+        zip.finish().unwrap();
+
+        assert!(manager
+            .import_vault(&export_path, None, ConflictResolution::Overwrite)
+            .is_err());
+
+        let report = manager.import_vault_lenient(&export_path, None).unwrap();
+        assert_eq!(report.vault.id, vault.id);
+        assert_eq!(report.notes.len(), 2);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, bad_name);
+        assert!(matches!(
+            report.failures[0].1,
+            VaultError::SerializationError(_)
+        ));
+    }
+
+    #[test]
+    fn test_keep_both_links_copy_to_original() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let existing = Note::new("Note".to_string(), "Local".to_string(), vec![]);
+        let mut imported = existing.clone();
+        imported.update("Note".to_string(), "Remote".to_string(), vec![]);
+
+        let resolved = manager.resolve_conflict(
+            existing.clone(),
+            imported.clone(),
+            ConflictResolution::KeepBoth,
+        );
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved[0].id, existing.id);
+        assert_eq!(resolved[0].derived_from, None);
+
+        let copy = &resolved[1];
+        assert_ne!(copy.id, imported.id);
+        assert_eq!(copy.derived_from, Some(imported.id));
+        assert_eq!(copy.content, "Remote");
+
+        // The link survives serialization, and older notes without it still load
+        let json = serde_json::to_string(copy).unwrap();
+        let reloaded: Note = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded.derived_from, Some(imported.id));
+
+        let mut legacy = serde_json::to_value(&existing).unwrap();
+        legacy.as_object_mut().unwrap().remove("derived_from");
+        let legacy: Note = serde_json::from_value(legacy).unwrap();
+        assert_eq!(legacy.derived_from, None);
+    }
+
+    #[test]
+    fn test_generate_report() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect();
+
+        let hub = Note::new("Hub".to_string(), "See [[Target]] now".to_string(), vec![]);
+        let target = Note::new("Target".to_string(), "linked to".to_string(), vec![]);
+        let orphan = Note::new(
+            "Loose".to_string(),
+            "nobody links here at all".to_string(),
+            vec![],
+        );
+        let work = Note::new("Work".to_string(), "one".to_string(), tags(&["work", "q3"]));
+        let more = Note::new("More".to_string(), "two".to_string(), tags(&["work"]));
+        let notes = vec![hub, target, orphan.clone(), work, more];
+
+        let report = manager.generate_report(&notes);
+        assert_eq!(report.total_notes, 5);
+        assert_eq!(report.total_words, 3 + 2 + 5 + 1 + 1);
+        assert_eq!(report.tag_counts.get("work"), Some(&2));
+        assert_eq!(report.tag_counts.get("q3"), Some(&1));
+        assert_eq!(report.orphans, vec![orphan.id]);
+        assert_eq!(report.largest_notes[0].id, orphan.id);
+        assert!(report.oldest.unwrap() <= report.newest.unwrap());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["total_notes"], 5);
+
+        assert_eq!(manager.generate_report(&[]), VaultReport::default());
+    }
+
+    #[test]
+    fn test_read_salt_before_import() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let vault = Vault::new("Secret".to_string(), String::new(), salt.clone());
+        let note = Note::new("Hidden".to_string(), "Body".to_string(), vec![]);
+        let export_path = temp_dir.path().join("export.zip");
+        manager
+            .export_vault(
+                &vault,
+                std::slice::from_ref(&note),
+                &export_path,
+                Some(&encryption),
+            )
+            .unwrap();
+
+        let read = manager.read_salt(&export_path).unwrap();
+        assert_eq!(read, salt);
+        // Encrypted exports record the password verifier
+        let metadata = manager.read_metadata(&export_path).unwrap();
+        let tag = metadata.vault.auth_tag.as_deref().unwrap();
+        assert!(EncryptionManager::verify_password("password", &read, tag).unwrap());
+        let rebuilt = EncryptionManager::new_from_password("password", &read).unwrap();
+        let (_, notes) = manager
+            .import_vault(&export_path, Some(&rebuilt), ConflictResolution::Overwrite)
+            .unwrap();
+        assert_eq!(notes[0].content, note.content);
+    }
+
+    #[test]
+    fn test_load_note_migrates_old_layout() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let note = Note::new("Old".to_string(), "Body".to_string(), vec![]);
+        let mut v0 = serde_json::to_value(&note).unwrap();
+        for field in ["pinned", "schema_version", "derived_from", "search_keywords"] {
+            v0.as_object_mut().unwrap().remove(field);
+        }
+        manager
+            .storage()
+            .write_file(&note_path(&note.id), v0.to_string().as_bytes())
+            .unwrap();
+
+        let loaded = manager.load_note(&note.id, None).unwrap();
+        assert_eq!(loaded.id, note.id);
+        assert_eq!(loaded.schema_version, crate::models::NOTE_SCHEMA_VERSION);
+
+        // Notes from a newer layout are refused rather than misread
+        v0["schema_version"] = 99.into();
+        manager
+            .storage()
+            .write_file(&note_path(&note.id), v0.to_string().as_bytes())
+            .unwrap();
+        assert!(matches!(
+            manager.load_note(&note.id, None),
+            Err(VaultError::ModelError(ModelError::UnsupportedSchemaVersion(99)))
+        ));
+    }
+
+    #[test]
+    fn test_compressed_note_storage() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut manager = VaultManager::new(storage);
+        manager.set_compress_notes(true);
+
+        let note = Note::new("Big".to_string(), "lorem ipsum ".repeat(2000), vec![]);
+        manager.save_note(&note, None).unwrap();
+        let on_disk = manager.storage().read_file(&note_path(&note.id)).unwrap();
+        assert!(on_disk.len() < note.content.len() / 10);
+        assert_eq!(
+            manager.load_note(&note.id, None).unwrap().content,
+            note.content
+        );
+
+        // Encrypted notes are compressed before encryption
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        manager.save_note(&note, Some(&encryption)).unwrap();
+        let on_disk = manager.storage().read_file(&note_path(&note.id)).unwrap();
+        assert!(on_disk.len() < note.content.len() / 10);
+        let loaded = manager.load_note(&note.id, Some(&encryption)).unwrap();
+        assert_eq!(loaded.content, note.content);
+
+        // Notes saved without compression still load
+        manager.set_compress_notes(false);
+        manager.save_note(&note, Some(&encryption)).unwrap();
+        let on_disk = manager.storage().read_file(&note_path(&note.id)).unwrap();
+        assert!(on_disk.len() > note.content.len());
+        let loaded = manager.load_note(&note.id, Some(&encryption)).unwrap();
+        assert_eq!(loaded.content, note.content);
+    }
+
+    #[test]
+    fn test_verify_decryptable() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("password", &salt).unwrap();
+        let vault = Vault::new("V".to_string(), String::new(), salt);
+        let notes = vec![
+            Note::new("A".to_string(), "one".to_string(), vec![]),
+            Note::new("B".to_string(), "two".to_string(), vec![]),
+        ];
+        let export_path = temp_dir.path().join("export.zip");
+        manager
+            .export_vault(&vault, &notes, &export_path, Some(&encryption))
+            .unwrap();
+
+        let good = manager
+            .verify_decryptable(&export_path, "password")
+            .unwrap();
+        assert!(good.is_ok());
+        assert_eq!(good.decryptable.len(), 2);
+
+        let bad = manager.verify_decryptable(&export_path, "wrong").unwrap();
+        assert!(!bad.is_ok());
+        assert!(bad.decryptable.is_empty());
+        assert_eq!(bad.failed.len(), 2);
+    }
+
+    #[test]
+    fn test_change_password_verifies_old_password() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("old", &salt).unwrap();
+        let vault = Vault::new_encrypted("V".to_string(), String::new(), &encryption);
+        let note = Note::new("N".to_string(), "secret".to_string(), vec![]);
+        manager.save_note(&note, Some(&encryption)).unwrap();
+        let before = manager.storage().read_file(&note_path(&note.id)).unwrap();
+
+        let result = manager.change_password(&vault, "wrong", "new");
+        assert!(matches!(
+            result,
+            Err(VaultError::EncryptionError(msg))
+                if msg == EncryptionError::AuthenticationFailed.to_string()
+        ));
+        assert_eq!(
+            manager.storage().read_file(&note_path(&note.id)).unwrap(),
+            before
+        );
+
+        let rekeyed = manager.change_password(&vault, "old", "new").unwrap();
+        assert_ne!(rekeyed.salt, vault.salt);
+        let new_tag = rekeyed.auth_tag.as_deref().unwrap();
+        assert!(EncryptionManager::verify_password("new", &rekeyed.salt, new_tag).unwrap());
+        let new_encryption = EncryptionManager::new_from_password("new", &rekeyed.salt).unwrap();
+        let loaded = manager.load_note(&note.id, Some(&new_encryption)).unwrap();
+        assert_eq!(loaded.content, "secret");
+        assert!(manager.load_note(&note.id, Some(&encryption)).is_err());
+        assert_eq!(manager.storage().list_files("notes").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_change_password_without_auth_tag() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let mut manager = VaultManager::new(storage);
+        manager.set_compress_notes(true);
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("old", &salt).unwrap();
+        let vault = Vault::new("V".to_string(), String::new(), salt);
+        let note = Note::new("N".to_string(), "secret ".repeat(2000), vec![]);
+        manager.save_note(&note, Some(&encryption)).unwrap();
+        let before = manager.storage().read_file(&note_path(&note.id)).unwrap();
+
+        // The notes themselves reject the wrong password
+        assert!(matches!(
+            manager.change_password(&vault, "wrong", "new"),
+            Err(VaultError::EncryptionError(msg))
+                if msg == EncryptionError::AuthenticationFailed.to_string()
+        ));
+        assert_eq!(
+            manager.storage().read_file(&note_path(&note.id)).unwrap(),
+            before
+        );
+
+        let rekeyed = manager.change_password(&vault, "old", "new").unwrap();
+        assert!(rekeyed.auth_tag.is_some());
+        let new_encryption = EncryptionManager::new_from_password("new", &rekeyed.salt).unwrap();
+        let on_disk = manager.storage().read_file(&note_path(&note.id)).unwrap();
+        assert!(on_disk.len() < note.content.len() / 10);
+        let loaded = manager.load_note(&note.id, Some(&new_encryption)).unwrap();
+        assert_eq!(loaded.content, note.content);
+    }
+
+    #[test]
+    fn test_change_password_resumes_after_failed_rename() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+
+        let salt = EncryptionManager::generate_salt();
+        let encryption = EncryptionManager::new_from_password("old", &salt).unwrap();
+        let vault = Vault::new_encrypted("V".to_string(), String::new(), &encryption);
+        let mut notes: Vec<Note> = (0..3)
+            .map(|i| Note::new(format!("N{}", i), format!("secret {}", i), vec![]))
+            .collect();
+        notes.sort_by_key(|note| note_path(&note.id));
+        for note in &notes {
+            manager.save_note(note, Some(&encryption)).unwrap();
+        }
+
+        let rekeyed = manager.stage_password_change(&vault, "old", "new").unwrap();
+        assert!(manager.stage_password_change(&vault, "old", "other").is_err());
+
+        // The second note's original can't be replaced, so moving stops there
+        let blocked = manager.storage().get_path(&note_path(&notes[1].id));
+        std::fs::remove_file(&blocked).unwrap();
+        std::fs::create_dir(&blocked).unwrap();
+        std::fs::write(blocked.join("keep"), b"x").unwrap();
+        assert!(manager.commit_password_change().is_err());
+
+        let new_encryption = EncryptionManager::new_from_password("new", &rekeyed.salt).unwrap();
+        let first = manager.load_note(&notes[0].id, Some(&new_encryption)).unwrap();
+        assert_eq!(first.content, notes[0].content);
+        let last = manager.load_note(&notes[2].id, Some(&encryption)).unwrap();
+        assert_eq!(last.content, notes[2].content);
+
+        // Resuming finishes the remaining notes under the new password
+        std::fs::remove_dir_all(&blocked).unwrap();
+        assert_eq!(manager.commit_password_change().unwrap(), 2);
+        for note in &notes {
+            let loaded = manager.load_note(&note.id, Some(&new_encryption)).unwrap();
+            assert_eq!(loaded.content, note.content);
+        }
+        assert_eq!(manager.commit_password_change().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_export_vault_with_progress() {
+        let temp_dir = tempdir().unwrap();
+        let storage = FileStorage::new(temp_dir.path().to_path_buf()).unwrap();
+        let manager = VaultManager::new(storage);
+        let vault = Vault::new("V".to_string(), String::new(), "salt".to_string());
+        let notes = vec![
+            Note::new("Small".to_string(), "x".to_string(), vec![]),
+            Note::new("Huge".to_string(), "y".repeat(100_000), vec![]),
+        ];
+
+        let mut events = Vec::new();
+        let stats = manager
+            .export_vault_with_progress(
+                &vault,
+                &notes,
+                &temp_dir.path().join("export.zip"),
+                None,
+                None,
+                &mut |written, total| events.push((written, total)),
+            )
+            .unwrap();
+
+        assert!(events.len() >= 3);
+        assert!(events.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert_eq!(
+            *events.last().unwrap(),
+            (stats.bytes_uncompressed, stats.bytes_uncompressed)
+        );
+        // The huge note advances progress in steps while it is written
+        let steps: Vec<u64> = events.windows(2).map(|w| w[1].0 - w[0].0).collect();
+        assert!(events.len() >= 4);
+        assert!(steps.iter().all(|&step| step <= EXPORT_PROGRESS_CHUNK as u64));
+
+        // Cancelling stops the validation pass before anything is reported
+        let cancel = AtomicBool::new(true);
+        let mut reported = false;
+        let result = manager.export_vault_with_progress(
+            &vault,
+            &notes,
+            &temp_dir.path().join("cancelled.zip"),
+            None,
+            Some(&cancel),
+            &mut |_, _| reported = true,
+        );
+        assert!(matches!(result, Err(VaultError::Cancelled)));
+        assert!(!reported);
     }
 }